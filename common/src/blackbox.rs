@@ -0,0 +1,751 @@
+//! On disk format of the robot's black box recorder and an api to read it back
+//!
+//! A recording is a directory of numbered segments. Each `{seq:08}.bbx` segment starts with
+//! `MAGIC` followed by frames of `[len: u32 LE][crc32: u32 LE][record]`, where the record is a
+//! bincode encoded `Record`. A crash can leave a cut off frame at the end of the newest segment,
+//! readers treat that as the end of the segment.
+//!
+//! Next to each segment, `{seq:08}.idx` holds fixed size `[time: u64 LE][offset: u64 LE]` entries,
+//! the time in microseconds since the unix epoch and the offset of the first frame written at or
+//! after it. One is written every `INDEX_INTERVAL` so readers can seek to a time.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use bincode::{DefaultOptions, Options};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    protocol::Protocol,
+    types::{InertialFrame, MagFrame},
+};
+
+pub const MAGIC: &[u8; 8] = b"ROVBBOX3";
+pub const SEGMENT_EXTENSION: &str = "bbx";
+pub const INDEX_EXTENSION: &str = "idx";
+pub const INDEX_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keys of the entries that are not store updates
+pub const SENSOR_KEY: &str = "blackbox.sensor";
+pub const PACKET_TX_KEY: &str = "blackbox.packet_tx";
+pub const PACKET_RX_KEY: &str = "blackbox.packet_rx";
+pub const ERROR_KEY: &str = "blackbox.error";
+
+/// Frames larger than this are treated as corruption
+const MAX_FRAME: u32 = 16 * 1024 * 1024;
+const FRAME_HEADER: usize = 8;
+const INDEX_ENTRY: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub time: SystemTime,
+    pub entry: Entry,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Entry {
+    /// A store update serialized with its token's adapter, `None` is a delete
+    Store(String, Option<Vec<u8>>),
+    /// Each frame with how long before `Record::time` it was sampled, oldest first
+    Sensor {
+        inertial: Vec<(Duration, InertialFrame)>,
+        mag: Vec<(Duration, MagFrame)>,
+        secondary_mag: Vec<(Duration, MagFrame)>,
+    },
+    /// Traffic other than store updates, those are already recorded as `Store`
+    PacketTx(Protocol),
+    PacketRx(Protocol),
+    Error(String),
+}
+
+impl Entry {
+    /// The store key for store updates, otherwise one of the `*_KEY` constants
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Store(key, _) => key,
+            Entry::Sensor { .. } => SENSOR_KEY,
+            Entry::PacketTx(_) => PACKET_TX_KEY,
+            Entry::PacketRx(_) => PACKET_RX_KEY,
+            Entry::Error(_) => ERROR_KEY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WriterConfig {
+    /// Segments are rotated once they grow past this many bytes
+    pub max_segment_size: u64,
+    pub max_segment_age: Duration,
+    /// The oldest segments are deleted to stay under this many bytes
+    pub max_total_size: u64,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        Self {
+            max_segment_size: 64 * 1024 * 1024,
+            max_segment_age: Duration::from_secs(10 * 60),
+            max_total_size: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Appends records to the segments in a directory, starting a new segment each time it is
+/// opened
+pub struct BlackBoxWriter {
+    dir: PathBuf,
+    config: WriterConfig,
+    segment: Option<OpenSegment>,
+    next_seq: u64,
+}
+
+struct OpenSegment {
+    data: BufWriter<File>,
+    index: BufWriter<File>,
+    size: u64,
+    opened: Instant,
+    last_indexed: Option<SystemTime>,
+}
+
+impl BlackBoxWriter {
+    pub fn open(dir: &Path, config: WriterConfig) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir).context("Create black box dir")?;
+
+        let next_seq = list_segments(dir)?
+            .last()
+            .map(|(seq, _)| seq + 1)
+            .unwrap_or(0);
+
+        Ok(Self {
+            dir: dir.to_owned(),
+            config,
+            segment: None,
+            next_seq,
+        })
+    }
+
+    pub fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        let data = options()
+            .serialize(record)
+            .context("Serialize black box record")?;
+        if data.len() > MAX_FRAME as usize {
+            bail!("Black box record too large: {} bytes", data.len());
+        }
+
+        let rotate = match &self.segment {
+            Some(segment) => {
+                segment.size >= self.config.max_segment_size
+                    || segment.opened.elapsed() >= self.config.max_segment_age
+            }
+            None => true,
+        };
+        if rotate {
+            self.rotate()?;
+        }
+
+        let Some(segment) = &mut self.segment else {
+            bail!("No open segment");
+        };
+
+        let due = segment
+            .last_indexed
+            .and_then(|last| record.time.duration_since(last).ok())
+            .map(|since| since >= INDEX_INTERVAL)
+            .unwrap_or(true);
+        if due {
+            let mut entry = [0; INDEX_ENTRY];
+            entry[..8].copy_from_slice(&to_micros(record.time).to_le_bytes());
+            entry[8..].copy_from_slice(&segment.size.to_le_bytes());
+            segment.index.write_all(&entry).context("Write index")?;
+            segment.last_indexed = Some(record.time);
+        }
+
+        segment
+            .data
+            .write_all(&(data.len() as u32).to_le_bytes())
+            .and_then(|_| segment.data.write_all(&crc32(&data).to_le_bytes()))
+            .and_then(|_| segment.data.write_all(&data))
+            .context("Write record")?;
+        segment.size += (FRAME_HEADER + data.len()) as u64;
+
+        Ok(())
+    }
+
+    /// Hands buffered records to the os, they survive the process crashing
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(segment) = &mut self.segment {
+            segment.data.flush().context("Flush segment")?;
+            segment.index.flush().context("Flush index")?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for records to reach the disk, they survive losing power
+    pub fn sync(&mut self) -> anyhow::Result<()> {
+        self.flush()?;
+
+        if let Some(segment) = &mut self.segment {
+            segment.data.get_ref().sync_data().context("Sync segment")?;
+            segment.index.get_ref().sync_data().context("Sync index")?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.sync()?;
+        self.segment = None;
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let (data_path, index_path) = segment_paths(&self.dir, seq);
+        let mut data = BufWriter::new(File::create(&data_path).context("Create segment")?);
+        let index = BufWriter::new(File::create(index_path).context("Create index")?);
+        data.write_all(MAGIC).context("Write magic")?;
+
+        self.segment = Some(OpenSegment {
+            data,
+            index,
+            size: MAGIC.len() as u64,
+            opened: Instant::now(),
+            last_indexed: None,
+        });
+
+        self.prune(seq)
+    }
+
+    /// Deletes the oldest segments until the recording fits in `max_total_size`
+    fn prune(&self, current: u64) -> anyhow::Result<()> {
+        let segments = list_segments(&self.dir)?;
+
+        let mut total: u64 = segments
+            .iter()
+            .filter_map(|(_, path)| fs::metadata(path).ok())
+            .map(|it| it.len())
+            .sum();
+
+        for (seq, path) in segments {
+            if total <= self.config.max_total_size || seq == current {
+                break;
+            }
+
+            let size = fs::metadata(&path).map(|it| it.len()).unwrap_or(0);
+            fs::remove_file(&path).context("Remove old segment")?;
+            let _ = fs::remove_file(path.with_extension(INDEX_EXTENSION));
+            total = total.saturating_sub(size);
+        }
+
+        Ok(())
+    }
+}
+
+/// Time range and keys to read back, `None` matches everything
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+    pub keys: Option<Vec<String>>,
+}
+
+impl Query {
+    fn matches(&self, record: &Record) -> bool {
+        self.start.map(|it| record.time >= it).unwrap_or(true)
+            && self.end.map(|it| record.time <= it).unwrap_or(true)
+            && self
+                .keys
+                .as_ref()
+                .map(|keys| keys.iter().any(|key| key == record.entry.key()))
+                .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentInfo {
+    pub seq: u64,
+    pub path: PathBuf,
+    /// From the index, `None` if nothing was indexed
+    pub start: Option<SystemTime>,
+    /// Time of the last index entry, records can follow it by up to `INDEX_INTERVAL`
+    pub last_indexed: Option<SystemTime>,
+}
+
+/// Reads back the segments in a black box directory
+pub struct BlackBoxReader {
+    segments: Vec<SegmentInfo>,
+}
+
+impl BlackBoxReader {
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        let mut segments = Vec::new();
+
+        for (seq, path) in list_segments(dir)? {
+            let index = read_index(&path.with_extension(INDEX_EXTENSION)).unwrap_or_default();
+
+            segments.push(SegmentInfo {
+                seq,
+                path,
+                start: index.first().map(|(time, _)| *time),
+                last_indexed: index.last().map(|(time, _)| *time),
+            });
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[SegmentInfo] {
+        &self.segments
+    }
+
+    /// Iterates the records matching `query` in the order they were written
+    pub fn records(&self, query: Query) -> Records<'_> {
+        Records {
+            reader: self,
+            query,
+            next_segment: 0,
+            current: None,
+        }
+    }
+}
+
+pub struct Records<'a> {
+    reader: &'a BlackBoxReader,
+    query: Query,
+    next_segment: usize,
+    current: Option<SegmentReader>,
+}
+
+impl Records<'_> {
+    /// Opens the next segment that can hold records in the query's time range
+    fn open_next(&mut self) -> Option<anyhow::Result<()>> {
+        let segments = &self.reader.segments;
+
+        while self.next_segment < segments.len() {
+            let idx = self.next_segment;
+            self.next_segment += 1;
+            let segment = &segments[idx];
+
+            // Everything in this segment comes before the next one starts
+            let ends_before_start = segments
+                .get(idx + 1)
+                .and_then(|next| next.start)
+                .zip(self.query.start)
+                .map(|(next_start, start)| next_start <= start)
+                .unwrap_or(false);
+            let starts_after_end = segment
+                .start
+                .zip(self.query.end)
+                .map(|(segment_start, end)| segment_start > end)
+                .unwrap_or(false);
+            if ends_before_start || starts_after_end {
+                continue;
+            }
+
+            return Some(
+                SegmentReader::open(&segment.path, self.query.start).map(|reader| {
+                    self.current = Some(reader);
+                }),
+            );
+        }
+
+        None
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(current) = &mut self.current {
+                match current.next_record() {
+                    Ok(Some(record)) => {
+                        if self.query.matches(&record) {
+                            return Some(Ok(record));
+                        }
+                    }
+                    Ok(None) => self.current = None,
+                    Err(err) => {
+                        // Nothing after a corrupt frame can be trusted
+                        self.current = None;
+                        return Some(Err(err));
+                    }
+                }
+            } else {
+                match self.open_next()? {
+                    Ok(()) => {}
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+        }
+    }
+}
+
+struct SegmentReader {
+    path: PathBuf,
+    file: BufReader<File>,
+}
+
+impl SegmentReader {
+    /// Opens a segment, seeking close to `start` using the index
+    fn open(path: &Path, start: Option<SystemTime>) -> anyhow::Result<Self> {
+        let mut file =
+            BufReader::new(File::open(path).with_context(|| format!("Open {}", path.display()))?);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic).context("Read magic")?;
+        if &magic != MAGIC {
+            bail!("{} is not a black box segment", path.display());
+        }
+
+        if let Some(start) = start {
+            let index = read_index(&path.with_extension(INDEX_EXTENSION)).unwrap_or_default();
+            let offset = index
+                .iter()
+                .take_while(|(time, _)| *time <= start)
+                .last()
+                .map(|(_, offset)| *offset);
+
+            if let Some(offset) = offset {
+                file.seek(SeekFrom::Start(offset))
+                    .context("Seek to index")?;
+            }
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    /// Returns `None` at the end of the segment, including a frame cut off by a crash
+    fn next_record(&mut self) -> anyhow::Result<Option<Record>> {
+        let mut header = [0; FRAME_HEADER];
+        if !read_full(&mut self.file, &mut header)? {
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if len > MAX_FRAME {
+            bail!("Bad frame length {len} in {}", self.path.display());
+        }
+
+        let mut data = vec![0; len as usize];
+        if !read_full(&mut self.file, &mut data)? {
+            return Ok(None);
+        }
+        if crc32(&data) != crc {
+            bail!("Checksum mismatch in {}", self.path.display());
+        }
+
+        let record = options()
+            .deserialize(&data)
+            .with_context(|| format!("Deserialize record in {}", self.path.display()))?;
+
+        Ok(Some(record))
+    }
+}
+
+/// Fills `buf`, returning false if the file ends first
+fn read_full(file: &mut impl Read, buf: &mut [u8]) -> anyhow::Result<bool> {
+    match file.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err).context("Read segment"),
+    }
+}
+
+fn read_index(path: &Path) -> io::Result<Vec<(SystemTime, u64)>> {
+    let data = fs::read(path)?;
+
+    // A partial entry at the end is left over from a crash
+    Ok(data
+        .chunks_exact(INDEX_ENTRY)
+        .map(|entry| {
+            let time = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            let offset = u64::from_le_bytes(entry[8..].try_into().unwrap());
+
+            (SystemTime::UNIX_EPOCH + Duration::from_micros(time), offset)
+        })
+        .collect())
+}
+
+/// Segments in `dir` sorted by sequence number
+fn list_segments(dir: &Path) -> anyhow::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+
+    for entry in fs::read_dir(dir).context("Read black box dir")? {
+        let path = entry.context("Read black box dir entry")?.path();
+
+        if path.extension().and_then(|it| it.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        let Some(seq) = path
+            .file_stem()
+            .and_then(|it| it.to_str())
+            .and_then(|it| it.parse().ok())
+        else {
+            continue;
+        };
+
+        segments.push((seq, path));
+    }
+
+    segments.sort_by_key(|(seq, _)| *seq);
+
+    Ok(segments)
+}
+
+fn segment_paths(dir: &Path, seq: u64) -> (PathBuf, PathBuf) {
+    let data = dir.join(format!("{seq:08}.{SEGMENT_EXTENSION}"));
+    let index = data.with_extension(INDEX_EXTENSION);
+
+    (data, index)
+}
+
+fn to_micros(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// CRC-32 (IEEE)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// The serializeation settings used
+fn options() -> impl Options {
+    DefaultOptions::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, process};
+
+    use crate::types::{Dps, GForce, Gauss};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("blackbox-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    fn at(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_000_000 + millis)
+    }
+
+    fn store(millis: u64, key: &str) -> Record {
+        Record {
+            time: at(millis),
+            entry: Entry::Store(key.to_owned(), Some(vec![millis as u8])),
+        }
+    }
+
+    fn write_all(dir: &Path, config: WriterConfig, records: &[Record]) {
+        let mut writer = BlackBoxWriter::open(dir, config).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.sync().unwrap();
+    }
+
+    fn read_all(dir: &Path, query: Query) -> Vec<Record> {
+        BlackBoxReader::open(dir)
+            .unwrap()
+            .records(query)
+            .collect::<anyhow::Result<_>>()
+            .unwrap()
+    }
+
+    /// `Protocol` has no `PartialEq`, the debug output covers every field
+    fn assert_same(a: &[Record], b: &[Record]) {
+        assert_eq!(format!("{a:?}"), format!("{b:?}"));
+    }
+
+    /// Path of the newest segment in `dir`
+    fn last_segment(dir: &Path) -> PathBuf {
+        list_segments(dir).unwrap().pop().unwrap().1
+    }
+
+    #[test]
+    fn round_trip_every_entry() {
+        let dir = temp_dir("round-trip");
+
+        let inertial = InertialFrame {
+            gyro_x: Dps(1.0),
+            accel_z: GForce(-1.0),
+            ..Default::default()
+        };
+        let mag = MagFrame {
+            mag_x: Gauss(0.2),
+            mag_y: Gauss(-0.1),
+            mag_z: Gauss(0.4),
+        };
+        let entries = [
+            Entry::Store("robot.test".to_owned(), Some(vec![1, 2, 3])),
+            Entry::Store("robot.test".to_owned(), None),
+            Entry::Sensor {
+                inertial: vec![(Duration::from_millis(2), inertial)],
+                mag: vec![(Duration::from_millis(5), mag)],
+                secondary_mag: vec![],
+            },
+            Entry::PacketTx(Protocol::Ping(at(0))),
+            Entry::PacketRx(Protocol::Pong(at(0), at(1))),
+            Entry::Error("Something broke".to_owned()),
+        ];
+        let records: Vec<_> = entries
+            .into_iter()
+            .enumerate()
+            .map(|(idx, entry)| Record {
+                time: at(idx as u64 * 10),
+                entry,
+            })
+            .collect();
+
+        write_all(&dir, WriterConfig::default(), &records);
+
+        assert_same(&read_all(&dir, Query::default()), &records);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_frame_ends_segment() {
+        let dir = temp_dir("truncated");
+        let records = [store(0, "a"), store(10, "a")];
+
+        write_all(&dir, WriterConfig::default(), &records);
+
+        let segment = last_segment(&dir);
+        let len = fs::metadata(&segment).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&segment).unwrap();
+        file.set_len(len - 3).unwrap();
+
+        assert_same(&read_all(&dir, Query::default()), &records[..1]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatch_is_an_error() {
+        let dir = temp_dir("checksum");
+
+        write_all(&dir, WriterConfig::default(), &[store(0, "a")]);
+
+        let segment = last_segment(&dir);
+        let mut data = fs::read(&segment).unwrap();
+        *data.last_mut().unwrap() ^= 0xFF;
+        fs::write(&segment, data).unwrap();
+
+        let reader = BlackBoxReader::open(&dir).unwrap();
+        let mut records = reader.records(Query::default());
+        let err = records.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{err}");
+        assert!(records.next().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_and_prunes_segments() {
+        let dir = temp_dir("rotate");
+        // Whole seconds apart so every record, and every segment, is the same size
+        let records: Vec<_> = (0..4).map(|idx| store(idx * 1000, "a")).collect();
+
+        let frame = options().serialize(&records[0]).unwrap().len();
+        let segment = (MAGIC.len() + FRAME_HEADER + frame) as u64;
+        let config = WriterConfig {
+            // Already reached by the magic, each record gets its own segment
+            max_segment_size: 1,
+            max_total_size: 2 * segment,
+            ..Default::default()
+        };
+
+        write_all(&dir, config, &records);
+
+        let reader = BlackBoxReader::open(&dir).unwrap();
+        let seqs: Vec<_> = reader.segments().iter().map(|it| it.seq).collect();
+        assert_eq!(seqs, [1, 2, 3]);
+        for info in reader.segments() {
+            assert_eq!(fs::metadata(&info.path).unwrap().len(), segment);
+            assert!(info.path.with_extension(INDEX_EXTENSION).exists());
+        }
+        assert!(!dir.join(format!("{:08}.{INDEX_EXTENSION}", 0)).exists());
+
+        assert_same(&read_all(&dir, Query::default()), &records[1..]);
+
+        // Reopening continues the numbering instead of overwriting
+        write_all(&dir, config, &[store(4000, "a")]);
+        let seqs: Vec<_> = BlackBoxReader::open(&dir)
+            .unwrap()
+            .segments()
+            .iter()
+            .map(|it| it.seq)
+            .collect();
+        assert_eq!(seqs, [2, 3, 4]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn query_filters_through_index() {
+        // Every 500ms, so about every other record is indexed
+        let records: Vec<_> = (0..20)
+            .map(|idx| store(idx * 500, if idx % 2 == 0 { "a" } else { "b" }))
+            .collect();
+        let query = Query {
+            start: Some(at(3000)),
+            end: Some(at(6000)),
+            keys: Some(vec!["a".to_owned()]),
+        };
+        let expected: Vec<_> = records
+            .iter()
+            .filter(|it| it.time >= at(3000) && it.time <= at(6000) && it.entry.key() == "a")
+            .cloned()
+            .collect();
+        assert_eq!(expected.len(), 4);
+
+        let single = WriterConfig::default();
+        let split = WriterConfig {
+            // A new segment after every few records, whole segments are skipped
+            max_segment_size: 3 * (FRAME_HEADER + MAGIC.len()) as u64 + 20,
+            ..Default::default()
+        };
+
+        for (name, config) in [("single", single), ("split", split)] {
+            let dir = temp_dir(&format!("query-{name}"));
+
+            write_all(&dir, config, &records);
+
+            let segments = BlackBoxReader::open(&dir).unwrap().segments().len();
+            assert_eq!(segments > 1, name == "split");
+            assert_same(&read_all(&dir, query.clone()), &expected);
+
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
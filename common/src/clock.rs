@@ -0,0 +1,130 @@
+//! Time sources that can be swapped for a manually driven clock in tests
+
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Where control loops and the store get the current time from
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Blocks until this clock reaches `deadline`
+    fn sleep_until(&self, deadline: Instant);
+}
+
+/// The real monotonic clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// A clock that only moves when `advance` is called
+///
+/// Threads sleeping on it wake up once it has been advanced past their deadline
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+    advanced: Condvar,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            now: Mutex::new(Instant::now()),
+            advanced: Condvar::new(),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.lock();
+        *now += duration;
+        self.advanced.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Instant> {
+        // An `Instant` cannot be left half written
+        self.now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.lock()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let mut now = self.lock();
+
+        while *now < deadline {
+            now = self
+                .advanced
+                .wait(now)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn manual_clock_only_moves_when_advanced() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+
+        thread::sleep(Duration::from_millis(5));
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(clock.now() - start, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn manual_clock_wakes_sleepers() {
+        let clock = Arc::new(ManualClock::new());
+        let start = clock.now();
+
+        let sleeper = {
+            let clock = clock.clone();
+            thread::spawn(move || {
+                clock.sleep_until(start + Duration::from_millis(30));
+                clock.now()
+            })
+        };
+
+        clock.advance(Duration::from_millis(10));
+        clock.advance(Duration::from_millis(10));
+        assert!(!sleeper.is_finished());
+        clock.advance(Duration::from_millis(10));
+
+        let woke = sleeper.join().unwrap();
+        assert!(woke - start >= Duration::from_millis(30));
+    }
+}
@@ -1,6 +1,8 @@
 //! Code shared between both the surface and robot projects
 #![feature(const_fn_floating_point_arithmetic, const_float_classify)]
 
+pub mod blackbox;
+pub mod clock;
 pub mod error;
 pub mod protocol;
 pub mod store;
@@ -1,6 +1,6 @@
 //! Repersents the protocol used for two way communication
 
-use crate::types::LogLevel;
+use crate::types::{LogLevel, LogRecord};
 use anyhow::Context;
 use bincode::{DefaultOptions, Options};
 use serde::{Deserialize, Serialize};
@@ -15,10 +15,20 @@ pub enum Protocol {
     RequestSync,
     /// Logs a message on the peer's console
     Log(LogLevel, String),
+    /// Structured log record for the peer to display
+    LogRecord(LogRecord),
     /// Asks the peer to reply with a Pong, used to measure communication latency
     Ping(SystemTime),
     /// Response to a Ping, used to measure communication latency
     Pong(SystemTime, SystemTime),
+    /// Immediately disables all motor outputs, stays latched until `ResetEmergencyStop`
+    EmergencyStop,
+    /// Clears a latched emergency stop, the robot must be armed again afterwards
+    ResetEmergencyStop,
+    /// Confirms the state of the emergency stop latch
+    EmergencyStopState(bool),
+    /// Names the cameras the peer wants streamed to it, replacing what it asked for before
+    CameraSubscriptions(Vec<String>),
 }
 
 impl networking::Packet for Protocol {
@@ -15,7 +15,10 @@ use std::{
 use fxhash::FxHashMap as HashMap;
 use tracing::error;
 
-use crate::error::LogErrorExt;
+use crate::{
+    clock::{self, SharedClock},
+    error::LogErrorExt,
+};
 
 pub type Key = KeyImpl;
 pub type Value = Arc<dyn Any + Send + Sync>;
@@ -38,15 +41,22 @@ pub struct Store<C> {
     owned: HashMap<Key, Value>,
     shared: HashMap<Key, Value>,
     timestamps: HashMap<Key, Instant>,
+    clock: SharedClock,
     callback: C,
 }
 
 impl<C: UpdateCallback> Store<C> {
     pub fn new(update_callback: C) -> Self {
+        Self::with_clock(update_callback, clock::system_clock())
+    }
+
+    /// Creates a store that timestamps updates with `clock` instead of the system clock
+    pub fn with_clock(update_callback: C, clock: SharedClock) -> Self {
         Self {
             owned: Default::default(),
             shared: Default::default(),
             timestamps: Default::default(),
+            clock,
             callback: update_callback,
         }
     }
@@ -61,7 +71,7 @@ impl<C: UpdateCallback> Store<C> {
 
         self.callback.call((key.0.clone(), Some(value.clone())));
         self.owned.insert(key.0.clone(), value);
-        self.timestamps.insert(key.0.clone(), Instant::now());
+        self.timestamps.insert(key.0.clone(), self.clock.now());
     }
 
     pub fn remove<V: Any>(&mut self, key: &Token<V>) {
@@ -72,7 +82,7 @@ impl<C: UpdateCallback> Store<C> {
 
         self.callback.call((key.0.clone(), None));
         self.owned.remove(&key.0);
-        self.timestamps.insert(key.0.clone(), Instant::now());
+        self.timestamps.insert(key.0.clone(), self.clock.now());
     }
 
     pub fn refresh(&mut self) {
@@ -106,7 +116,7 @@ impl<C> Store<C> {
         max_age: Duration,
     ) -> Option<Arc<V>> {
         self.get_with_time(key).and_then(|(entry, timestamp)| {
-            if timestamp.elapsed() < max_age {
+            if self.clock.now().saturating_duration_since(timestamp) < max_age {
                 entry
             } else {
                 None
@@ -144,7 +154,7 @@ impl<C> Store<C> {
             self.shared.remove(&update.0);
         }
 
-        self.timestamps.insert(update.0.clone(), Instant::now());
+        self.timestamps.insert(update.0.clone(), self.clock.now());
     }
 
     pub fn handle_update_owned(&mut self, update: &Update) {
@@ -159,7 +169,7 @@ impl<C> Store<C> {
             self.owned.remove(&update.0);
         }
 
-        self.timestamps.insert(update.0.clone(), Instant::now());
+        self.timestamps.insert(update.0.clone(), self.clock.now());
     }
 }
 
@@ -4,13 +4,19 @@ use crate::{
     store::adapters::{Adapter, BackingType, TypeAdapter},
     store::{Key, Token},
     types::{
-        Armed, Camera, DepthControlMode, DepthCorrection, DepthFrame, InertialFrame,
-        LevelingCorrection, LevelingMode, MagFrame, MotorFrame, MotorId, Movement,
-        MovementOverride, Orientation, PidConfig, PidResult, RobotStatus, SystemInfo,
+        Armed, ArmingConfig, ArmingState, Camera, CameraHealth, CameraSettings, DepthCalibration,
+        DepthControlMode, DepthCorrection, DepthFrame, DepthSensorSettings, EnclosureFrame,
+        EscCalibration, EscSweepRequest, EscSweepState, GimbalMode, GimbalState, HoldDisengaged,
+        ImuSettings, InertialFrame, LeakProbeState, LeakResponse, LevelingCorrection, LevelingMode,
+        MagFrame, MagStatus, MotorFrame, MotorId, Movement, MovementOverride, Orientation, Percent,
+        PidConfig, PidResult, PowerConfig, PowerFault, PowerFrame, Recording, RobotStatus,
+        SealTestReport, SealTestRequest, SensorFault, SensorId, ServoCommand, ServoConfig,
+        SystemInfo, TaskStats, ThrusterTestConfig, ThrusterTestReport, WaterType,
     },
 };
 use fxhash::FxHashMap as HashMap;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
 
 // Adaptor Definitions
 
@@ -21,14 +27,64 @@ pub const SYSTEM_INFO: Token<SystemInfo> = Token::new_const("robot.system_info")
 pub const STATUS: Token<RobotStatus> = Token::new_const("robot.status");
 #[rustfmt::skip]
 pub const LEAK: Token<bool> = Token::new_const("robot.status.leak");
+#[rustfmt::skip]
+pub const LEAK_PROBES: Token<HashMap<String, LeakProbeState>> = Token::new_const("robot.status.leak.probes");
+#[rustfmt::skip]
+pub const LEAK_RESPONSE: Token<LeakResponse> = Token::new_const("robot.status.leak.response");
 
 #[rustfmt::skip]
 pub const CAMERAS: Token<Vec<Camera>> = Token::new_const("robot.cameras");
+#[rustfmt::skip]
+pub const CAMERA_SETTINGS: Token<HashMap<String, CameraSettings>> = Token::new_const("robot.cameras.settings");
+#[rustfmt::skip]
+pub const CAMERA_HEALTH: Token<HashMap<String, CameraHealth>> = Token::new_const("robot.cameras.health");
+#[rustfmt::skip]
+pub const CAMERA_RECORD: Token<HashMap<String, bool>> = Token::new_const("robot.cameras.record");
+#[rustfmt::skip]
+pub const RECORDINGS: Token<Vec<Recording>> = Token::new_const("robot.cameras.recordings");
+
+#[rustfmt::skip]
+pub const LOG_FILTER: Token<String> = Token::new_const("robot.logging.filter");
+
+#[rustfmt::skip]
+pub const SCHEDULER_STATS: Token<HashMap<String, TaskStats>> = Token::new_const("robot.scheduler.stats");
 
 #[rustfmt::skip]
 pub const ARMED: Token<Armed> = Token::new_const("robot.motors.armed");
 #[rustfmt::skip]
+pub const ARMING_FORCE: Token<bool> = Token::new_const("robot.motors.armed.force");
+#[rustfmt::skip]
+pub const ARMING_STATE: Token<ArmingState> = Token::new_const("robot.motors.armed.state");
+#[rustfmt::skip]
+pub const ARMING_CONFIG_OVERRIDE: Token<ArmingConfig> = Token::new_const("robot.motors.armed.config.override");
+#[rustfmt::skip]
+pub const EMERGENCY_STOP: Token<bool> = Token::new_const("robot.motors.emergency_stop");
+#[rustfmt::skip]
 pub const MOTOR_SPEED: Token<HashMap<MotorId, MotorFrame>> = Token::new_const("robot.motors.speed");
+#[rustfmt::skip]
+pub const ESC_CALIBRATION: Token<HashMap<MotorId, EscCalibration>> = Token::new_const("robot.motors.calibration");
+#[rustfmt::skip]
+pub const ESC_CALIBRATION_OVERRIDE: Token<HashMap<MotorId, EscCalibration>> = Token::new_const("robot.motors.calibration.override");
+#[rustfmt::skip]
+pub const ESC_SWEEP: Token<EscSweepRequest> = Token::new_const("robot.motors.calibration.sweep");
+#[rustfmt::skip]
+pub const ESC_SWEEP_STATE: Token<EscSweepState> = Token::new_const("robot.motors.calibration.sweep.state");
+#[rustfmt::skip]
+pub const THRUSTER_TEST: Token<ThrusterTestConfig> = Token::new_const("robot.motors.test");
+#[rustfmt::skip]
+pub const THRUSTER_TEST_REPORT: Token<ThrusterTestReport> = Token::new_const("robot.motors.test.report");
+#[rustfmt::skip]
+pub const SERVO_COMMANDS: Token<HashMap<MotorId, ServoCommand>> = Token::new_const("robot.servos.commands");
+#[rustfmt::skip]
+pub const SERVO_CONFIG_OVERRIDE: Token<HashMap<MotorId, ServoConfig>> = Token::new_const("robot.servos.config.override");
+#[rustfmt::skip]
+pub const SERVO_POSITIONS: Token<HashMap<MotorId, Percent>> = Token::new_const("robot.servos.positions");
+#[rustfmt::skip]
+pub const SERVO_PULSES: Token<HashMap<MotorId, Duration>> = Token::new_const("robot.servos.pulses");
+#[rustfmt::skip]
+pub const GIMBAL_MODE: Token<GimbalMode> = Token::new_const("robot.servos.gimbal.mode");
+#[rustfmt::skip]
+pub const GIMBAL_STATE: Token<GimbalState> = Token::new_const("robot.servos.gimbal.state");
 
 #[rustfmt::skip]
 pub const LEVELING_MODE: Token<LevelingMode> = Token::new_const("robot.leveling.mode");
@@ -40,6 +96,8 @@ pub const LEVELING_PITCH_RESULT: Token<PidResult> = Token::new_const("robot.leve
 pub const LEVELING_ROLL_RESULT: Token<PidResult> = Token::new_const("robot.leveling.roll");
 #[rustfmt::skip]
 pub const LEVELING_CORRECTION: Token<LevelingCorrection> = Token::new_const("robot.leveling.correction");
+#[rustfmt::skip]
+pub const LEVELING_DISENGAGED: Token<HoldDisengaged> = Token::new_const("robot.leveling.disengaged");
 
 #[rustfmt::skip]
 pub const DEPTH_CONTROL_MODE: Token<DepthControlMode> = Token::new_const("robot.depth.mode");
@@ -49,6 +107,8 @@ pub const DEPTH_CONTROL_PID_OVERRIDE: Token<PidConfig> = Token::new_const("robot
 pub const DEPTH_CONTROL_RESULT: Token<PidResult> = Token::new_const("robot.depth.pitch");
 #[rustfmt::skip]
 pub const DEPTH_CONTROL_CORRECTION: Token<DepthCorrection> = Token::new_const("robot.depth.correction");
+#[rustfmt::skip]
+pub const DEPTH_CONTROL_DISENGAGED: Token<HoldDisengaged> = Token::new_const("robot.depth.disengaged");
 
 #[rustfmt::skip]
 pub const MOVEMENT_JOYSTICK: Token<Movement> = Token::new_const("robot.movement.joystick");
@@ -59,6 +119,8 @@ pub const MOVEMENT_LEVELING: Token<Movement> = Token::new_const("robot.movement.
 #[rustfmt::skip]
 pub const MOVEMENT_DEPTH: Token<Movement> = Token::new_const("robot.movement.depth");
 #[rustfmt::skip]
+pub const MOVEMENT_LEAK: Token<Movement> = Token::new_const("robot.movement.leak");
+#[rustfmt::skip]
 pub const MOVEMENT_CALCULATED: Token<Movement> = Token::new_const("robot.movement.calculated");
 #[rustfmt::skip]
 pub const MOVEMENT_OVERRIDE: Token<MovementOverride> = Token::new_const("robot.movement.override");
@@ -66,10 +128,41 @@ pub const MOVEMENT_OVERRIDE: Token<MovementOverride> = Token::new_const("robot.m
 #[rustfmt::skip]
 pub const RAW_DEPTH: Token<DepthFrame> = Token::new_const("robot.sensors.depth");
 #[rustfmt::skip]
+pub const DEPTH_SENSOR_SETTINGS: Token<DepthSensorSettings> = Token::new_const("robot.sensors.depth.settings");
+#[rustfmt::skip]
+pub const DEPTH_CALIBRATION: Token<DepthCalibration> = Token::new_const("robot.sensors.depth.calibration");
+/// Writing a new request time captures the current pressure as the surface pressure
+#[rustfmt::skip]
+pub const DEPTH_TARE: Token<SystemTime> = Token::new_const("robot.sensors.depth.tare");
+#[rustfmt::skip]
+pub const DEPTH_WATER_TYPE: Token<WaterType> = Token::new_const("robot.sensors.depth.water");
+#[rustfmt::skip]
 pub const RAW_INERTIAL: Token<InertialFrame> = Token::new_const("robot.sensors.inertial");
 #[rustfmt::skip]
+pub const IMU_SETTINGS: Token<ImuSettings> = Token::new_const("robot.sensors.inertial.settings");
+#[rustfmt::skip]
 pub const RAW_MAGNETIC: Token<MagFrame> = Token::new_const("robot.sensors.mag");
 #[rustfmt::skip]
+pub const RAW_MAGNETIC_AK09915: Token<MagFrame> = Token::new_const("robot.sensors.mag.ak09915");
+#[rustfmt::skip]
+pub const MAGNETIC: Token<MagFrame> = Token::new_const("robot.sensors.mag.voted");
+#[rustfmt::skip]
+pub const MAG_STATUS: Token<MagStatus> = Token::new_const("robot.sensors.mag.status");
+#[rustfmt::skip]
+pub const SENSOR_HEALTH: Token<HashMap<SensorId, Vec<SensorFault>>> = Token::new_const("robot.sensors.health");
+#[rustfmt::skip]
+pub const POWER: Token<PowerFrame> = Token::new_const("robot.power");
+#[rustfmt::skip]
+pub const POWER_CONFIG: Token<PowerConfig> = Token::new_const("robot.power.config");
+#[rustfmt::skip]
+pub const POWER_FAULTS: Token<Vec<PowerFault>> = Token::new_const("robot.power.faults");
+#[rustfmt::skip]
+pub const ENCLOSURE: Token<EnclosureFrame> = Token::new_const("robot.sensors.enclosure");
+#[rustfmt::skip]
+pub const SEAL_TEST: Token<SealTestRequest> = Token::new_const("robot.sensors.enclosure.seal_test");
+#[rustfmt::skip]
+pub const SEAL_TEST_REPORT: Token<SealTestReport> = Token::new_const("robot.sensors.enclosure.seal_test.report");
+#[rustfmt::skip]
 pub const ORIENTATION: Token<Orientation> = Token::new_const("robot.sensors.fusion");
 
 /// Returns a map between `Key` and `TypeAdapter`
@@ -86,27 +179,69 @@ pub fn generate_adaptors() -> HashMap<Key, Box<dyn TypeAdapter<BackingType> + Se
         from(SYSTEM_INFO),
         from(STATUS),
         from(LEAK),
+        from(LEAK_PROBES),
+        from(LEAK_RESPONSE),
         from(CAMERAS),
+        from(CAMERA_SETTINGS),
+        from(CAMERA_HEALTH),
+        from(CAMERA_RECORD),
+        from(RECORDINGS),
+        from(LOG_FILTER),
+        from(SCHEDULER_STATS),
         from(ARMED),
+        from(ARMING_FORCE),
+        from(ARMING_STATE),
+        from(ARMING_CONFIG_OVERRIDE),
+        from(EMERGENCY_STOP),
         from(MOTOR_SPEED),
+        from(ESC_CALIBRATION),
+        from(ESC_CALIBRATION_OVERRIDE),
+        from(ESC_SWEEP),
+        from(ESC_SWEEP_STATE),
+        from(THRUSTER_TEST),
+        from(THRUSTER_TEST_REPORT),
+        from(SERVO_COMMANDS),
+        from(SERVO_CONFIG_OVERRIDE),
+        from(SERVO_POSITIONS),
+        from(SERVO_PULSES),
+        from(GIMBAL_MODE),
+        from(GIMBAL_STATE),
         from(LEVELING_MODE),
         from(LEVELING_PID_OVERRIDE),
         from(LEVELING_PITCH_RESULT),
         from(LEVELING_ROLL_RESULT),
         from(LEVELING_CORRECTION),
+        from(LEVELING_DISENGAGED),
         from(DEPTH_CONTROL_MODE),
         from(DEPTH_CONTROL_PID_OVERRIDE),
         from(DEPTH_CONTROL_RESULT),
         from(DEPTH_CONTROL_CORRECTION),
+        from(DEPTH_CONTROL_DISENGAGED),
         from(MOVEMENT_JOYSTICK),
         from(MOVEMENT_OPENCV),
         from(MOVEMENT_LEVELING),
         from(MOVEMENT_DEPTH),
+        from(MOVEMENT_LEAK),
         from(MOVEMENT_CALCULATED),
         from(MOVEMENT_OVERRIDE),
         from(RAW_DEPTH),
+        from(DEPTH_SENSOR_SETTINGS),
+        from(DEPTH_CALIBRATION),
+        from(DEPTH_TARE),
+        from(DEPTH_WATER_TYPE),
         from(RAW_INERTIAL),
+        from(IMU_SETTINGS),
         from(RAW_MAGNETIC),
+        from(RAW_MAGNETIC_AK09915),
+        from(MAGNETIC),
+        from(MAG_STATUS),
+        from(SENSOR_HEALTH),
+        from(POWER),
+        from(POWER_CONFIG),
+        from(POWER_FAULTS),
+        from(ENCLOSURE),
+        from(SEAL_TEST),
+        from(SEAL_TEST_REPORT),
         from(ORIENTATION),
     ]
     .into_iter()
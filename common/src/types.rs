@@ -7,7 +7,7 @@ use std::fmt::{Display, Formatter};
 use std::iter::Sum;
 use std::net::SocketAddr;
 use std::ops::{Add, AddAssign, Neg, Sub};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Orientation(pub Quaternion<f32>);
@@ -168,13 +168,93 @@ pub enum MotorId {
 
 // Raw Data Frames
 
-#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DepthFrame {
+    /// When the conversions finished, frames can be handled long after
+    pub sampled: SystemTime,
+
     pub depth: Meters,
     pub altitude: Meters,
     pub pressure: Mbar,
 
     pub temperature: Celsius,
+
+    /// What depth was calculated with
+    pub surface_pressure: Mbar,
+    /// In kg/m³
+    pub fluid_density: f64,
+    pub water_type: WaterType,
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LeakProbeState {
+    pub leaking: bool,
+    /// When the probe first detected water, stays set once it dries
+    pub first_detected: Option<SystemTime>,
+}
+
+/// What the robot does by itself once a leak is detected, carried out without the surface
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum LeakResponse {
+    /// Only report the leak
+    #[default]
+    Notify,
+    /// Thrust up at a fixed speed while the leak lasts, keeps the robot armed if the link drops
+    Ascend(Percent),
+    Disarm,
+}
+
+/// Supply measured by the power sense module
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PowerFrame {
+    pub voltage: Volts,
+    pub current: Amps,
+    /// Since the robot started
+    pub energy_used: WattHours,
+}
+
+/// Scaling and thresholds for the power sense module on the adc
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PowerConfig {
+    pub voltage_channel: u8,
+    pub current_channel: u8,
+    /// Supply volts per volt at the adc, set by the voltage divider
+    pub voltage_scale: f64,
+    /// Amps per volt at the adc, set by the shunt and its amplifier
+    pub current_scale: f64,
+    /// Adc voltage at zero current
+    pub current_offset: Volts,
+
+    pub low_voltage: Volts,
+    pub max_current: Amps,
+}
+
+impl Default for PowerConfig {
+    /// Blue Robotics power sense module on the navigator
+    fn default() -> Self {
+        Self {
+            voltage_channel: 2,
+            current_channel: 3,
+            voltage_scale: 11.0,
+            current_scale: 37.8788,
+            current_offset: Volts(0.330),
+            low_voltage: Volts(13.2),
+            max_current: Amps(25.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum PowerFault {
+    LowVoltage,
+    OverCurrent,
+}
+
+/// Conditions inside the electronics enclosure
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EnclosureFrame {
+    pub pressure: Mbar,
+    pub temperature: Celsius,
 }
 
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
@@ -197,6 +277,247 @@ pub struct MagFrame {
     pub mag_z: Gauss,
 }
 
+/// What the depth sensor converts pressure to depth with, persisted on the robot
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DepthCalibration {
+    /// Atmospheric pressure at the surface, zero depth
+    pub surface_pressure: Mbar,
+    pub water_type: WaterType,
+    /// The tare request `surface_pressure` was captured for, each request is only handled once
+    pub tare_request: Option<SystemTime>,
+}
+
+impl Default for DepthCalibration {
+    fn default() -> Self {
+        Self {
+            surface_pressure: Mbar(1013.0),
+            water_type: WaterType::Fresh,
+            tare_request: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum WaterType {
+    /// Density follows the water temperature
+    #[default]
+    Fresh,
+    /// Seawater at 35 PSU, density follows the water temperature
+    Salt,
+    /// A fixed density in kg/m³
+    Custom(f64),
+}
+
+/// Measurement settings for the ICM20602 imu
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ImuSettings {
+    pub gyro_range: GyroRange,
+    pub accel_range: AccelRange,
+    pub gyro_filter: GyroFilter,
+    pub accel_filter: AccelFilter,
+    pub sample_rate: ImuSampleRate,
+}
+
+/// Gyro full scale range, a smaller range has finer resolution
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GyroRange {
+    Dps250,
+    #[default]
+    Dps500,
+    Dps1000,
+    Dps2000,
+}
+
+impl GyroRange {
+    pub const fn full_scale(&self) -> Dps {
+        match self {
+            GyroRange::Dps250 => Dps(250.0),
+            GyroRange::Dps500 => Dps(500.0),
+            GyroRange::Dps1000 => Dps(1000.0),
+            GyroRange::Dps2000 => Dps(2000.0),
+        }
+    }
+}
+
+/// Accelerometer full scale range, a smaller range has finer resolution
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AccelRange {
+    #[default]
+    G2,
+    G4,
+    G8,
+    G16,
+}
+
+impl AccelRange {
+    pub const fn full_scale(&self) -> GForce {
+        match self {
+            AccelRange::G2 => GForce(2.0),
+            AccelRange::G4 => GForce(4.0),
+            AccelRange::G8 => GForce(8.0),
+            AccelRange::G16 => GForce(16.0),
+        }
+    }
+}
+
+/// Gyro low pass filter bandwidth
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GyroFilter {
+    #[default]
+    Hz176,
+    Hz92,
+    Hz41,
+    Hz20,
+    Hz10,
+    Hz5,
+}
+
+/// Accelerometer low pass filter bandwidth
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AccelFilter {
+    #[default]
+    Hz218,
+    Hz99,
+    Hz45,
+    Hz21,
+    Hz10,
+    Hz5,
+}
+
+/// How often the imu writes a sample to its fifo
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ImuSampleRate {
+    #[default]
+    Hz1000,
+    Hz500,
+    Hz250,
+    Hz200,
+    Hz100,
+    Hz50,
+}
+
+impl ImuSampleRate {
+    pub fn period(&self) -> Duration {
+        let hz = match self {
+            Self::Hz1000 => 1000,
+            Self::Hz500 => 500,
+            Self::Hz250 => 250,
+            Self::Hz200 => 200,
+            Self::Hz100 => 100,
+            Self::Hz50 => 50,
+        };
+
+        Duration::from_secs(1) / hz
+    }
+}
+
+/// Measurement settings for the MS5837 pressure sensor
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DepthSensorSettings {
+    pub oversampling: Oversampling,
+}
+
+/// Pressure and temperature oversampling ratio, higher ratios have less noise but take longer
+///
+/// A reading takes two conversions, from 2ms at 256 to 40ms at 8192. From 2048 up the depth loop
+/// slows down to fit them.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Oversampling {
+    Osr256,
+    Osr512,
+    #[default]
+    Osr1024,
+    Osr2048,
+    Osr4096,
+    Osr8192,
+}
+
+impl Oversampling {
+    /// How long to wait for one conversion to finish
+    pub fn conversion_time(&self) -> Duration {
+        let millis = match self {
+            Self::Osr256 => 1,
+            Self::Osr512 => 2,
+            Self::Osr1024 => 3,
+            Self::Osr2048 => 5,
+            Self::Osr4096 => 10,
+            Self::Osr8192 => 20,
+        };
+
+        Duration::from_millis(millis)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MagSource {
+    Mmc5983,
+    Ak09915,
+}
+
+/// Which magnetometer the orientation pipeline uses and why the others are not
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MagStatus {
+    pub active: Option<MagSource>,
+    pub rejected: Vec<(MagSource, MagFault)>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MagFault {
+    /// No recent readings
+    Stale,
+    /// Field strength no compass on earth would read
+    OutOfRange(Gauss),
+    /// Field strength differs from the other compass by this much, and this one changed more
+    Disagrees(Gauss),
+    /// Field direction differs from the other compass by this much, and this one changed more
+    Misaligned(Degrees),
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SensorId {
+    Inertial,
+    Depth,
+    Magnetometer,
+}
+
+/// Why readings from a sensor are implausible
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SensorFault {
+    /// No recent readings
+    Stale,
+    /// Readings stopped changing, real sensors always have some noise
+    Stuck,
+    /// Depth outside what the sensor can measure
+    DepthOutOfRange(Meters),
+    /// Depth changed faster than the robot can move, in meters per second
+    DepthRate(f64),
+    /// An axis is at the end of the accelerometer's range
+    AccelSaturated(GForce),
+    /// An axis is at the end of the gyro's range
+    GyroSaturated(Dps),
+    /// Accelerometer magnitude is far from 1g while the gyro reports the robot is still
+    AccelInconsistent(GForce),
+    /// Field strength no compass on earth would read
+    MagOutOfRange(Gauss),
+}
+
+impl Display for SensorFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SensorFault::Stale => write!(f, "No recent readings"),
+            SensorFault::Stuck => write!(f, "Readings stopped changing"),
+            SensorFault::DepthOutOfRange(depth) => write!(f, "Depth out of range: {depth}"),
+            SensorFault::DepthRate(rate) => write!(f, "Depth changing at {rate:.2}M/s"),
+            SensorFault::AccelSaturated(accel) => write!(f, "Accelerometer saturated: {accel}"),
+            SensorFault::GyroSaturated(gyro) => write!(f, "Gyro saturated: {gyro}"),
+            SensorFault::AccelInconsistent(accel) => {
+                write!(f, "Accelerometer reads {accel} while still")
+            }
+            SensorFault::MagOutOfRange(field) => write!(f, "Field strength out of range: {field}"),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MotorFrame {
     Percent(Percent),
@@ -229,6 +550,418 @@ pub enum Armed {
     Disarmed,
 }
 
+/// The robot's answer to an arming request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum ArmingState {
+    #[default]
+    Disarmed,
+    /// All pre-arm checks passed
+    Armed,
+    /// Armed with the force flag set, includes the checks that were overridden
+    ForceArmed(Vec<PreArmFailure>),
+    /// Arming was refused, includes the checks that failed
+    Refused(Vec<PreArmFailure>),
+}
+
+impl ArmingState {
+    pub const fn is_armed(&self) -> bool {
+        matches!(self, ArmingState::Armed | ArmingState::ForceArmed(_))
+    }
+}
+
+/// Reason a pre-arm check failed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PreArmFailure {
+    EmergencyStopped,
+    LeakDetected,
+    NoLeakData,
+    StaleInertial,
+    StaleDepth,
+    InertialOutOfRange {
+        accel: GForce,
+        gyro: Dps,
+    },
+    DepthOutOfRange {
+        depth: Meters,
+        pressure: Mbar,
+    },
+    NoLatencyData,
+    LatencyTooHigh(Duration),
+    PilotInput(Percent),
+    SealTestRunning,
+    /// Includes the measured pressure rise per minute
+    SealTestFailed(Mbar),
+}
+
+impl PreArmFailure {
+    /// Can this failure be bypassed with the force arm flag
+    pub const fn overridable(&self) -> bool {
+        !matches!(
+            self,
+            PreArmFailure::EmergencyStopped
+                | PreArmFailure::LeakDetected
+                | PreArmFailure::PilotInput(_)
+        )
+    }
+
+    /// All of `failures` on one line, separated by commas
+    pub fn describe_all(failures: &[Self]) -> String {
+        failures
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Display for PreArmFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreArmFailure::EmergencyStopped => write!(f, "Emergency stop is latched"),
+            PreArmFailure::LeakDetected => write!(f, "Leak detected"),
+            PreArmFailure::NoLeakData => write!(f, "No leak sensor data"),
+            PreArmFailure::StaleInertial => write!(f, "IMU data is stale"),
+            PreArmFailure::StaleDepth => write!(f, "Depth data is stale"),
+            PreArmFailure::InertialOutOfRange { accel, gyro } => {
+                write!(f, "IMU out of range, accel: {accel}, gyro: {gyro}")
+            }
+            PreArmFailure::DepthOutOfRange { depth, pressure } => {
+                write!(
+                    f,
+                    "Depth out of range, depth: {depth}, pressure: {pressure}"
+                )
+            }
+            PreArmFailure::NoLatencyData => write!(f, "No link latency data"),
+            PreArmFailure::LatencyTooHigh(latency) => {
+                write!(f, "Link latency too high: {latency:.2?}")
+            }
+            PreArmFailure::PilotInput(input) => write!(f, "Pilot input not centered: {input}"),
+            PreArmFailure::SealTestRunning => write!(f, "Seal test in progress"),
+            PreArmFailure::SealTestFailed(rise_rate) => {
+                write!(f, "Seal test failed, pressure rose {rise_rate}/min")
+            }
+        }
+    }
+}
+
+/// Thresholds and enabled checks for the pre-arm checklist
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ArmingConfig {
+    pub check_leak: bool,
+    pub check_sensor_age: bool,
+    pub check_sensor_range: bool,
+    pub check_latency: bool,
+    pub check_pilot_input: bool,
+    /// Refuses to arm while a seal test is running or after one failed
+    pub check_seal: bool,
+
+    pub max_sensor_age: Duration,
+    pub max_latency: Duration,
+    pub max_pilot_input: Percent,
+
+    /// Min and max norm of the accelerometer
+    pub accel_range: (GForce, GForce),
+    /// Max rotation rate on any axis
+    pub max_gyro: Dps,
+    pub depth_range: (Meters, Meters),
+    pub pressure_range: (Mbar, Mbar),
+}
+
+/// Parameters for the thruster self-test, writing a new value starts a test
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThrusterTestConfig {
+    /// Speed each thruster is spun at
+    pub speed: Percent,
+    /// How long each thruster is spun for
+    pub spin_time: Duration,
+    /// Idle time before each spin, used to measure the baseline rotation rate
+    pub settle_time: Duration,
+    /// Smallest change in rotation rate that counts as a response
+    pub min_response: Dps,
+}
+
+impl Default for ThrusterTestConfig {
+    fn default() -> Self {
+        Self {
+            speed: Percent::new(0.15),
+            spin_time: Duration::from_secs(2),
+            settle_time: Duration::from_millis(1500),
+            min_response: Dps(3.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ThrusterTestState {
+    /// Spinning or settling before spinning the included thruster
+    Running(MotorId),
+    Complete,
+    Aborted(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThrusterTestResult {
+    /// Rotation matched the mixing model
+    Pass,
+    /// Rotation was opposite the mixing model, the thruster spins backwards
+    Reversed,
+    /// Rotation did not line up with the mixing model, the channel may be wrong
+    WrongAxis,
+    /// Rotation rate did not change enough to judge the direction
+    NoResponse,
+}
+
+impl ThrusterTestResult {
+    pub const fn passed(&self) -> bool {
+        matches!(self, ThrusterTestResult::Pass)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThrusterTestMeasurement {
+    pub result: ThrusterTestResult,
+    /// Change in rotation rate while spinning, in the same axes as `Movement`
+    pub pitch: Dps,
+    pub roll: Dps,
+    pub yaw: Dps,
+    /// Cosine similarity between the expected and measured rotation
+    pub agreement: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThrusterTestReport {
+    pub state: ThrusterTestState,
+    pub results: Vec<(MotorId, ThrusterTestMeasurement)>,
+}
+
+/// Parameters for the vacuum seal test
+/// The enclosure should already be pumped down when the test is started
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SealTestConfig {
+    /// Time for the pressure to settle after pumping down, not counted towards the rise rate
+    pub settle_time: Duration,
+    /// How long the pressure rise is watched for
+    pub window: Duration,
+    /// Highest pressure the enclosure can start the window at, anything above was not pumped down
+    pub max_start_pressure: Mbar,
+    /// Fastest pressure rise per minute that passes
+    pub max_rise_rate: Mbar,
+}
+
+impl Default for SealTestConfig {
+    fn default() -> Self {
+        Self {
+            settle_time: Duration::from_secs(30),
+            window: Duration::from_secs(300),
+            max_start_pressure: Mbar(850.0),
+            max_rise_rate: Mbar(1.0),
+        }
+    }
+}
+
+/// Starts a seal test, each request is only handled once
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SealTestRequest {
+    pub requested: SystemTime,
+    pub config: SealTestConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SealTestState {
+    /// Waiting out the settle time
+    Settling,
+    /// Watching the pressure rise
+    Measuring,
+    Passed,
+    Failed,
+    Aborted(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SealTestReport {
+    pub state: SealTestState,
+    /// Pressure when the window started
+    pub start_pressure: Mbar,
+    /// Latest pressure, corrected to the temperature the window started at
+    pub pressure: Mbar,
+    /// Pressure rise per minute fitted over the window so far
+    pub rise_rate: Mbar,
+    /// Time left in the current state
+    pub remaining: Duration,
+}
+
+/// Pulse widths for a single esc or servo channel
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EscCalibration {
+    /// Pulse for full reverse
+    pub min: Duration,
+    pub center: Duration,
+    /// Pulse for full forward
+    pub max: Duration,
+    /// Width of the band around center where the esc does not spin the motor
+    pub deadband: Duration,
+    /// Flips the direction the channel spins
+    pub reverse: bool,
+}
+
+/// Commands for the esc deadband sweep
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EscSweepRequest {
+    /// Slowly moves the channel away from center, forward first then reverse
+    Start {
+        motor: MotorId,
+        /// How far the pulse moves from center every second
+        rate: Duration,
+        /// Furthest the pulse moves from center
+        limit: Duration,
+    },
+    /// Operator saw thrust begin at the current pulse
+    MarkThrust,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EscSweepPhase {
+    Forward,
+    Reverse,
+    Complete,
+    Aborted(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EscSweepState {
+    pub motor: MotorId,
+    pub phase: EscSweepPhase,
+    /// Pulse currently being output
+    pub pulse: Duration,
+    /// Pulse above center where thrust began
+    pub forward_start: Option<Duration>,
+    /// Pulse below center where thrust began
+    pub reverse_start: Option<Duration>,
+}
+
+impl EscSweepState {
+    /// Recenters the deadband on the measured thrust starts, if both were found
+    pub fn apply(&self, calibration: EscCalibration) -> Option<EscCalibration> {
+        let (forward, reverse) = self.forward_start.zip(self.reverse_start)?;
+
+        Some(EscCalibration {
+            center: (forward + reverse) / 2,
+            deadband: forward.saturating_sub(reverse),
+            ..calibration
+        })
+    }
+}
+
+/// Command for a single servo
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServoCommand {
+    /// Move to a position, limited by the max rate
+    Position(Percent),
+    /// Move at a fraction of the max rate, stops once the commands go stale
+    Velocity(Percent),
+    /// Move to a named position from the servo's config
+    Preset(String),
+    /// Move to an angle, relative to the world when the servo is stabilized
+    Angle(Degrees),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServoConfig {
+    /// Soft travel limits
+    pub min: Percent,
+    pub max: Percent,
+    /// Fastest the servo is moved, in percent of full scale per second
+    pub max_rate: f64,
+    pub presets: HashMap<String, Percent>,
+    pub calibration: ServoAngleCalibration,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self {
+            min: Percent::MIN_VAL,
+            max: Percent::MAX_VAL,
+            max_rate: 1.0,
+            presets: Default::default(),
+            calibration: Default::default(),
+        }
+    }
+}
+
+/// Linear map between a servo's angle and the pulse that produces it
+/// Positions span from `min_angle` at -100% to `max_angle` at 100%
+/// Angles are positive in the same direction as the vehicle axis the servo tilts about
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ServoAngleCalibration {
+    pub min_angle: Degrees,
+    pub min_pulse: Duration,
+    pub max_angle: Degrees,
+    pub max_pulse: Duration,
+}
+
+impl Default for ServoAngleCalibration {
+    fn default() -> Self {
+        Self {
+            min_angle: Degrees(-45.0),
+            min_pulse: Duration::from_micros(1100),
+            max_angle: Degrees(45.0),
+            max_pulse: Duration::from_micros(1900),
+        }
+    }
+}
+
+impl ServoAngleCalibration {
+    pub fn angle(&self, position: Percent) -> Degrees {
+        let fraction = (position.get() + 1.0) / 2.0;
+        Degrees(self.min_angle.0 + (self.max_angle.0 - self.min_angle.0) * fraction)
+    }
+
+    /// Position for an angle, clamped to the calibrated travel
+    pub fn position(&self, angle: Degrees) -> Percent {
+        let span = self.max_angle.0 - self.min_angle.0;
+        if span == 0.0 {
+            return Percent::ZERO;
+        }
+
+        let fraction = (angle.0 - self.min_angle.0) / span;
+        Percent::new(fraction * 2.0 - 1.0)
+    }
+
+    pub fn pulse(&self, position: Percent) -> Duration {
+        let fraction = (position.get() + 1.0) / 2.0;
+        let min = self.min_pulse.as_secs_f64();
+        let max = self.max_pulse.as_secs_f64();
+
+        Duration::from_secs_f64(min + (max - min) * fraction)
+    }
+}
+
+/// Vehicle axis a stabilized servo counter-rotates against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GimbalAxis {
+    Pitch,
+    Roll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GimbalMode {
+    Stabilized { servo: MotorId, axis: GimbalAxis },
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GimbalState {
+    pub servo: MotorId,
+    /// Commanded angle relative to the horizon
+    pub tilt: Degrees,
+    /// Vehicle angle about the stabilized axis
+    pub vehicle: Degrees,
+    /// Angle the servo is driven to, relative to the vehicle
+    pub servo_angle: Degrees,
+    /// The tilt is outside the servo's travel at the current vehicle angle
+    pub saturated: bool,
+}
+
 // Basic Units
 
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialOrd, PartialEq)]
@@ -249,6 +982,33 @@ impl Display for Mbar {
     }
 }
 
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialOrd, PartialEq)]
+pub struct Volts(pub f64);
+
+impl Display for Volts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{:.2}V", self.0))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialOrd, PartialEq)]
+pub struct Amps(pub f64);
+
+impl Display for Amps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{:.2}A", self.0))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialOrd, PartialEq)]
+pub struct WattHours(pub f64);
+
+impl Display for WattHours {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{:.2}Wh", self.0))
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialOrd, PartialEq)]
 pub struct Celsius(pub f64);
 
@@ -444,10 +1204,85 @@ pub struct Network {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Camera {
     pub name: String,
+    /// Where to listen for the stream, the multicast group or the unspecified address for unicast
     pub location: SocketAddr,
+    /// What the stream was started with, so it can be decoded to match
+    pub settings: CameraSettings,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CameraSettings {
+    pub enabled: bool,
+    pub width: u32,
+    pub height: u32,
+    pub framerate: u32,
+    /// H.264 bitrate in kbit/s
+    pub bitrate: u32,
+    /// Applied by the viewer after decoding
+    pub rotation: CameraRotation,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// Streams to a multicast group for any viewer instead of to each subscribed surface
+    pub multicast: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            width: 1920,
+            height: 1080,
+            framerate: 30,
+            bitrate: 4000,
+            rotation: CameraRotation::None,
+            flip_horizontal: false,
+            flip_vertical: false,
+            multicast: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CameraHealth {
+    pub state: CameraStreamState,
+    /// Times the stream has been restarted after failing
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// How long the current stream has been running
+    pub uptime: Duration,
+    /// Also writing the stream to the robot's disk
+    pub recording: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CameraStreamState {
+    Running,
+    /// Failed and waiting to be restarted
+    Restarting,
+    /// Disabled or nothing to stream or record to
+    Stopped,
+}
+
+/// A segment of video recorded on the robot
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Recording {
+    pub camera: String,
+    /// Where the file is on the robot
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CameraRotation {
+    None,
+    Clockwise90,
+    Rotate180,
+    CounterClockwise90,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Debug,
     Info,
@@ -455,6 +1290,18 @@ pub enum LogLevel {
     Error,
 }
 
+/// A tracing event recorded by the peer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogRecord {
+    pub time: SystemTime,
+    pub level: LogLevel,
+    pub target: String,
+    /// Names of the spans the event happened in, outermost first
+    pub spans: Vec<String>,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum RobotStatus {
     // No peer is connected
@@ -465,11 +1312,18 @@ pub enum RobotStatus {
     Ready,
     // The robot is moving, includes speed
     Moving(Percent),
+    // Emergency stop is latched, outputs are disabled until it is reset
+    EmergencyStopped,
+    // Robot is disarmed and the last seal test failed
+    SealTestFailed,
+    // A power or sensor fault is active, the robot may still be armed
+    Degraded,
 }
 
 #[derive(Clone, Copy)]
 pub struct PidController {
     last_error: Option<f64>,
+    last_update: Option<Instant>,
     integral: f64,
     interval: Duration,
 }
@@ -500,14 +1354,34 @@ impl PidController {
     pub fn new(interval: Duration) -> Self {
         Self {
             last_error: None,
+            last_update: None,
             integral: 0.0,
             interval,
         }
     }
 
+    /// Updates the controller assuming exactly `interval` has passed since the last update
     pub fn update(&mut self, error: f64, config: PidConfig) -> PidResult {
+        self.step(error, config, self.interval)
+    }
+
+    /// Updates the controller with the time that actually passed since the last update at `now`
+    ///
+    /// The first update, and any update without time passing, uses `interval`
+    pub fn update_at(&mut self, error: f64, config: PidConfig, now: Instant) -> PidResult {
+        let interval = self
+            .last_update
+            .map(|last| now.saturating_duration_since(last))
+            .filter(|it| !it.is_zero())
+            .unwrap_or(self.interval);
+        self.last_update = Some(now);
+
+        self.step(error, config, interval)
+    }
+
+    fn step(&mut self, error: f64, config: PidConfig, interval: Duration) -> PidResult {
         let cfg = config;
-        let interval = self.interval.as_secs_f64();
+        let interval = interval.as_secs_f64();
 
         self.integral += error * interval;
         self.integral = self.integral.clamp(-cfg.max_integral, cfg.max_integral);
@@ -538,6 +1412,13 @@ pub enum DepthControlMode {
     Disabled,
 }
 
+/// Why the robot dropped a hold the surface engaged, it stays off until the surface changes mode
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HoldDisengaged {
+    pub sensor: SensorId,
+    pub fault: SensorFault,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct LevelingCorrection {
     pub pitch: f64,
@@ -548,3 +1429,20 @@ pub struct LevelingCorrection {
 pub struct DepthCorrection {
     pub depth: f64,
 }
+
+/// Timing of one of the robot's fixed rate loops over the last report interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskStats {
+    pub period: Duration,
+    pub ticks: u64,
+    /// Ticks that were skipped or finished after the next one was due
+    pub deadline_misses: u64,
+    /// Deadline misses since the robot started
+    pub total_deadline_misses: u64,
+    /// Time from a tick being released to the task finishing with it
+    pub mean_execution: Duration,
+    pub max_execution: Duration,
+    /// How late ticks were released
+    pub mean_jitter: Duration,
+    pub max_jitter: Duration,
+}
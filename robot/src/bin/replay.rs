@@ -0,0 +1,754 @@
+//! Replays a black box recording through the robot's sensor fusion and control systems
+//!
+//! Recordings are captured on the robot by `BlackBoxSystem` in the format documented in
+//! `common::blackbox`. Sensor batches and the store updates the replayed systems read are fed
+//! back in at the pace they were recorded, or as fast as orientation keeps up with `--fast`.
+//! The `ORIENTATION` and `MOVEMENT_*` updates the systems produce are then diffed against the
+//! recorded ones.
+//!
+//! With `--fast` the robot runs on a clock that follows the recording, so depth control and
+//! leveling tick as often as they did when it was recorded.
+//!
+//! Usage: `replay <dir> [--fast] [--start <unix secs>] [--end <unix secs>]`
+
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    iter,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, Scope},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Context};
+use common::{
+    blackbox::{BlackBoxReader, Entry, Query},
+    clock::{Clock as _, ManualClock},
+    store::{tokens, Key, Update, Value},
+    types::{Movement, Orientation},
+};
+use crossbeam::channel::{bounded, unbounded, Receiver, RecvTimeoutError};
+use fxhash::FxHashMap as HashMap;
+use glam::Quat;
+use robot::{
+    event::{Event, Sample, SensorBatch},
+    events::EventHandle,
+    systems::{
+        clock as robot_clock, depth_control::DepthControlSystem, error::ErrorSystem,
+        leveling::LevelingSystem, orientation::OrientationSystem, scheduler::SchedulerSystem, stop,
+        stop::StopSystem, System, SystemManager,
+    },
+    SystemId,
+};
+use tracing::{info, span, warn, Level};
+
+/// How long the replayed systems get to finish once the recording runs out
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+/// How often the output collector checks for the end of the replay
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long `--fast` waits for orientation to process a batch before moving on
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+/// Tick period of depth control and leveling, `--fast` moves the clock one period at a time so
+/// none of their ticks are skipped
+const CONTROL_PERIOD: Duration = Duration::from_millis(20);
+/// Largest acceptable orientation difference, in degrees
+const ORIENTATION_TOLERANCE: f64 = 1.0;
+/// Largest acceptable difference of any movement axis
+const MOVEMENT_TOLERANCE: f64 = 0.05;
+/// Fraction of outputs allowed to be present in one run and removed or missing in the other,
+/// mode changes and the end of the recording can land on either side of a tick
+const MAX_MISMATCH_RATIO: f64 = 0.02;
+
+static REPLAY: Mutex<Option<ReplayConfig>> = Mutex::new(None);
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+struct ReplayConfig {
+    dir: PathBuf,
+    query: Query,
+    clock: Clock,
+    /// Set for `Clock::Fast`, the replay moves it to each recorded time
+    manual_clock: Option<Arc<ManualClock>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clock {
+    /// Records are fed at the pace they were recorded
+    RealTime,
+    /// Each sensor batch is fed as soon as the last one was processed, with the robot's clock
+    /// advanced to the time it was recorded
+    Fast,
+}
+
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+
+    let config = parse_args(env::args().skip(1))?;
+    info!(
+        "Replaying {} with {:?} clock",
+        config.dir.display(),
+        config.clock
+    );
+    if let Some(manual_clock) = &config.manual_clock {
+        robot_clock::set_clock(manual_clock.clone());
+    }
+    *REPLAY
+        .lock()
+        .map_err(|_| anyhow!("Replay config poisoned"))? = Some(config);
+
+    let mut systems = SystemManager::default();
+
+    info!("---------- Registering systems ----------");
+    {
+        systems.add_system::<StopSystem>()?;
+        systems.add_system::<SchedulerSystem>()?;
+        systems.add_system::<ErrorSystem>()?;
+        systems.add_system::<OrientationSystem>()?;
+        systems.add_system::<DepthControlSystem>()?;
+        systems.add_system::<LevelingSystem>()?;
+        systems.add_system::<ReplaySystem>()?;
+    }
+    info!("--------------------------------------");
+
+    systems.start();
+
+    let report = REPORT
+        .lock()
+        .map_err(|_| anyhow!("Replay report poisoned"))?
+        .take()
+        .context("Replay did not finish")?;
+    print!("{report}");
+
+    if !report.passed() {
+        bail!("Regenerated outputs differ from the recording");
+    }
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<ReplayConfig> {
+    fn parse_time(arg: Option<String>) -> anyhow::Result<SystemTime> {
+        let arg = arg.context("Expected a unix timestamp")?;
+        let secs: f64 = arg
+            .parse()
+            .with_context(|| format!("Parse timestamp `{arg}`"))?;
+
+        Ok(SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs))
+    }
+
+    let mut dir = None;
+    let mut query = Query::default();
+    let mut clock = Clock::RealTime;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fast" => clock = Clock::Fast,
+            "--start" => query.start = Some(parse_time(args.next())?),
+            "--end" => query.end = Some(parse_time(args.next())?),
+            _ if dir.is_none() && !arg.starts_with("--") => dir = Some(PathBuf::from(arg)),
+            _ => bail!("Unexpected argument `{arg}`"),
+        }
+    }
+
+    let Some(dir) = dir else {
+        bail!("Usage: replay <dir> [--fast] [--start <unix secs>] [--end <unix secs>]");
+    };
+
+    let manual_clock = (clock == Clock::Fast).then(|| Arc::new(ManualClock::new()));
+
+    Ok(ReplayConfig {
+        dir,
+        query,
+        clock,
+        manual_clock,
+    })
+}
+
+/// Feeds the recording to the other systems and diffs what they produce
+struct ReplaySystem;
+
+impl System for ReplaySystem {
+    const ID: SystemId = SystemId::Replay;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listner = events.take_listner().unwrap();
+
+        let ReplayConfig {
+            dir,
+            query,
+            clock,
+            manual_clock,
+        } = REPLAY
+            .lock()
+            .map_err(|_| anyhow!("Replay config poisoned"))?
+            .take()
+            .context("Replay was not configured")?;
+        let reader = BlackBoxReader::open(&dir).context("Open recording")?;
+
+        // Recorded time of the last input fed, in micros since the epoch
+        let cursor = Arc::new(AtomicU64::new(0));
+        let (output_tx, output_rx) = unbounded();
+        let (ack_tx, ack_rx) = bounded(1);
+        let (control_ack_tx, control_ack_rx) = bounded(2);
+
+        {
+            let cursor = cursor.clone();
+            spawner.spawn(move || {
+                span!(Level::INFO, "Replay output collector");
+
+                // The feeder sends the exit, which never comes back to this system
+                while !stop::world_stopped() {
+                    let event = match listner.recv_timeout(POLL_INTERVAL) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    };
+
+                    if let Event::Store(update) = &*event {
+                        if !is_output(&update.0) {
+                            continue;
+                        }
+
+                        if update.0 == tokens::ORIENTATION.0 {
+                            let _ = ack_tx.try_send(());
+                        } else {
+                            let _ = control_ack_tx.try_send(update.0.clone());
+                        }
+
+                        let stamp = from_micros(cursor.load(Ordering::Relaxed));
+                        let _ = output_tx.send((stamp, update.clone()));
+                    }
+                }
+            });
+        }
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Replay feeder");
+
+            let adapters = tokens::generate_adaptors();
+            let replay_clock = robot_clock::clock();
+
+            let mut recorded: Vec<(SystemTime, Update)> = Vec::new();
+            let mut started: Option<(SystemTime, Instant)> = None;
+            let mut batches = 0;
+
+            for record in reader.records(query) {
+                let record = match record {
+                    Ok(record) => record,
+                    Err(err) => {
+                        events.send(Event::Error(err.context("Read recording")));
+                        break;
+                    }
+                };
+
+                if stop::world_stopped() {
+                    break;
+                }
+
+                let (start, clock_start) =
+                    *started.get_or_insert((record.time, replay_clock.now()));
+                let offset = record.time.duration_since(start).unwrap_or_default();
+                match &manual_clock {
+                    Some(manual_clock) => {
+                        advance_clock(manual_clock, clock_start + offset, &control_ack_rx)
+                    }
+                    None => replay_clock.sleep_until(clock_start + offset),
+                }
+
+                match record.entry {
+                    Entry::Sensor {
+                        inertial,
+                        mag,
+                        secondary_mag,
+                    } => {
+                        // Sample spacing is kept, times are moved onto the replay's clock
+                        let now = replay_clock.now();
+                        let inertial = samples(inertial, now);
+                        let mag = samples(mag, now);
+                        let secondary_mag = samples(secondary_mag, now);
+
+                        // Drop a late ack from a batch that timed out
+                        let _ = ack_rx.try_recv();
+
+                        cursor.store(to_micros(record.time), Ordering::Relaxed);
+                        events.send_to(
+                            Event::SensorFrame(SensorBatch {
+                                inertial,
+                                mag,
+                                secondary_mag,
+                            }),
+                            iter::once(SystemId::Orientation),
+                        );
+                        batches += 1;
+
+                        if clock == Clock::Fast && ack_rx.recv_timeout(ACK_TIMEOUT).is_err() {
+                            warn!("Orientation did not process batch {batches} in time");
+                        }
+                    }
+                    Entry::Store(key, data) => {
+                        let key: Key = key.into();
+                        if !is_output(&key) && !is_input(&key) {
+                            continue;
+                        }
+
+                        let Some(adapter) = adapters.get(&key) else {
+                            warn!("No adapter found for {key:?}");
+                            continue;
+                        };
+                        let value: Option<Value> = match data {
+                            Some(data) => {
+                                let Some(value) = adapter.deserialize(&data) else {
+                                    warn!("Could not deserialize for {key:?}");
+                                    continue;
+                                };
+
+                                Some(value.into())
+                            }
+                            None => None,
+                        };
+
+                        if is_output(&key) {
+                            recorded.push((record.time, (key, value)));
+                        } else {
+                            cursor.store(to_micros(record.time), Ordering::Relaxed);
+                            events.send(Event::Store((key, value)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            info!("Fed {batches} sensor batches, waiting for outputs");
+            thread::sleep(SETTLE_TIME);
+
+            stop::stop_world();
+            events.send(Event::Exit);
+            // The scheduler sleeps on the replay clock and only sees the stop once it moves
+            if let Some(manual_clock) = &manual_clock {
+                manual_clock.advance(SETTLE_TIME);
+            }
+
+            // Ends once the collector has seen the exit and dropped its sender
+            let regenerated: Vec<_> = output_rx.iter().collect();
+            let report = Report::new(clock, batches, &recorded, &regenerated);
+
+            match REPORT.lock() {
+                Ok(mut slot) => *slot = Some(report),
+                Err(_) => warn!("Replay report poisoned"),
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Moves `clock` up to `target` one control period at a time, waiting for depth control and
+/// leveling to handle the tick each period releases
+fn advance_clock(clock: &ManualClock, target: Instant, control_acks: &Receiver<Key>) {
+    while clock.now() + CONTROL_PERIOD <= target {
+        // Drop late acks from a tick that timed out
+        while control_acks.try_recv().is_ok() {}
+
+        clock.advance(CONTROL_PERIOD);
+
+        let mut pending = vec![tokens::MOVEMENT_DEPTH.0, tokens::MOVEMENT_LEVELING.0];
+        while !pending.is_empty() {
+            let Ok(key) = control_acks.recv_timeout(ACK_TIMEOUT) else {
+                warn!("Control systems did not handle a tick in time");
+                break;
+            };
+
+            pending.retain(|it| *it != key);
+        }
+    }
+}
+
+/// Store keys the replayed systems read
+fn is_input(key: &Key) -> bool {
+    [
+        &tokens::DEPTH_CONTROL_MODE.0,
+        &tokens::DEPTH_CONTROL_PID_OVERRIDE.0,
+        &tokens::RAW_DEPTH.0,
+        &tokens::LEVELING_MODE.0,
+        &tokens::LEVELING_PID_OVERRIDE.0,
+        &tokens::SENSOR_HEALTH.0,
+    ]
+    .contains(&key)
+}
+
+/// Store keys the replayed systems produce that get diffed
+fn is_output(key: &Key) -> bool {
+    [
+        &tokens::ORIENTATION.0,
+        &tokens::MOVEMENT_DEPTH.0,
+        &tokens::MOVEMENT_LEVELING.0,
+    ]
+    .contains(&key)
+}
+
+/// How far apart two values of an output are and how far they are allowed to be
+fn difference(key: &Key, recorded: &Value, regenerated: &Value) -> Option<(f64, f64)> {
+    if let (Some(recorded), Some(regenerated)) = (
+        recorded.downcast_ref::<Orientation>(),
+        regenerated.downcast_ref::<Orientation>(),
+    ) {
+        let recorded: Quat = recorded.0.into();
+        let regenerated: Quat = regenerated.0.into();
+        let angle = recorded.angle_between(regenerated).to_degrees() as f64;
+
+        return Some((angle, ORIENTATION_TOLERANCE));
+    }
+
+    if let (Some(recorded), Some(regenerated)) = (
+        recorded.downcast_ref::<Movement>(),
+        regenerated.downcast_ref::<Movement>(),
+    ) {
+        let axes = |it: &Movement| [it.x, it.y, it.z, it.x_rot, it.y_rot, it.z_rot];
+        let max = iter::zip(axes(recorded), axes(regenerated))
+            .map(|(a, b)| (a.get() - b.get()).abs())
+            .fold(0.0, f64::max);
+
+        return Some((max, MOVEMENT_TOLERANCE));
+    }
+
+    warn!("Cannot diff {key:?}");
+    None
+}
+
+/// Places recorded samples, stored as their age at recording time, before `now`
+fn samples<T>(recorded: Vec<(Duration, T)>, now: Instant) -> Vec<Sample<T>> {
+    recorded
+        .into_iter()
+        .map(|(age, frame)| Sample {
+            time: now.checked_sub(age).unwrap_or(now),
+            frame,
+        })
+        .collect()
+}
+
+fn to_micros(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+fn from_micros(micros: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_micros(micros)
+}
+
+/// Differences between the recorded and regenerated outputs
+struct Report {
+    clock: Clock,
+    batches: usize,
+    outputs: Vec<(String, OutputStats)>,
+}
+
+#[derive(Default)]
+struct OutputStats {
+    recorded: usize,
+    regenerated: usize,
+    compared: usize,
+    /// Present in one run and removed in the other
+    mismatched: usize,
+    /// Regenerated after the last recorded value
+    unmatched: usize,
+    total_error: f64,
+    max_error: f64,
+    tolerance: f64,
+}
+
+impl Report {
+    /// Pairs each regenerated output with the first recorded value of the same key at or after
+    /// the input that produced it
+    fn new(
+        clock: Clock,
+        batches: usize,
+        recorded: &[(SystemTime, Update)],
+        regenerated: &[(SystemTime, Update)],
+    ) -> Self {
+        let mut timelines: HashMap<&Key, Vec<(SystemTime, &Option<Value>)>> = HashMap::default();
+        for (time, (key, value)) in recorded {
+            timelines.entry(key).or_default().push((*time, value));
+        }
+
+        let mut stats: HashMap<String, OutputStats> = HashMap::default();
+        for (key, timeline) in &timelines {
+            stats.entry(key.to_string()).or_default().recorded = timeline.len();
+        }
+
+        for (stamp, (key, value)) in regenerated {
+            let stats = stats.entry(key.to_string()).or_default();
+            stats.regenerated += 1;
+
+            let Some(timeline) = timelines.get(key) else {
+                stats.unmatched += 1;
+                continue;
+            };
+            let idx = timeline.partition_point(|(time, _)| time < stamp);
+            let Some((_, recorded)) = timeline.get(idx) else {
+                stats.unmatched += 1;
+                continue;
+            };
+
+            match (recorded, value) {
+                (Some(recorded), Some(value)) => {
+                    if let Some((error, tolerance)) = difference(key, recorded, value) {
+                        stats.compared += 1;
+                        stats.total_error += error;
+                        stats.max_error = stats.max_error.max(error);
+                        stats.tolerance = tolerance;
+                    }
+                }
+                (None, None) => stats.compared += 1,
+                _ => {
+                    stats.compared += 1;
+                    stats.mismatched += 1;
+                }
+            }
+        }
+
+        let mut outputs: Vec<_> = stats.into_iter().collect();
+        outputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self {
+            clock,
+            batches,
+            outputs,
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.outputs.iter().all(|(_, stats)| stats.passed())
+    }
+}
+
+impl OutputStats {
+    fn passed(&self) -> bool {
+        // The output stopped being produced
+        if self.recorded > 0 && self.compared == 0 {
+            return false;
+        }
+
+        let mismatch_ratio = if self.regenerated > 0 {
+            (self.mismatched + self.unmatched) as f64 / self.regenerated as f64
+        } else {
+            0.0
+        };
+
+        // Catches outputs produced far more or less often, and outputs that were never recorded
+        let most = self.recorded.max(self.regenerated);
+        let count_ratio = if most > 0 {
+            self.recorded.abs_diff(self.regenerated) as f64 / most as f64
+        } else {
+            0.0
+        };
+
+        self.max_error <= self.tolerance
+            && mismatch_ratio <= MAX_MISMATCH_RATIO
+            && count_ratio <= MAX_MISMATCH_RATIO
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Replayed {} sensor batches with {:?} clock",
+            self.batches, self.clock
+        )?;
+
+        for (key, stats) in &self.outputs {
+            let mean = if stats.compared > 0 {
+                stats.total_error / stats.compared as f64
+            } else {
+                0.0
+            };
+            let verdict = if stats.passed() { "ok" } else { "FAILED" };
+
+            writeln!(
+                f,
+                "  {key}: {verdict}, {} recorded, {} regenerated, {} compared, {} mismatched, \
+                 {} unmatched, mean error {mean:.4}, max error {:.4} (tolerance {})",
+                stats.recorded,
+                stats.regenerated,
+                stats.compared,
+                stats.mismatched,
+                stats.unmatched,
+                stats.max_error,
+                stats.tolerance,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        store::create_update,
+        types::{Orientation, Percent},
+    };
+
+    use super::*;
+
+    fn depth(z: f64) -> Update {
+        create_update(
+            &tokens::MOVEMENT_DEPTH,
+            Movement {
+                z: Percent::new(z),
+                ..Movement::default()
+            },
+        )
+    }
+
+    fn at(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn timeline(values: &[f64]) -> Vec<(SystemTime, Update)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(idx, z)| (at(idx as u64 * 20), depth(*z)))
+            .collect()
+    }
+
+    fn stats<'a>(report: &'a Report, token: &str) -> &'a OutputStats {
+        &report
+            .outputs
+            .iter()
+            .find(|(key, _)| key == token)
+            .expect("Output in report")
+            .1
+    }
+
+    #[test]
+    fn identical_runs_pass() {
+        let recorded = timeline(&[0.1; 100]);
+        let report = Report::new(Clock::RealTime, 100, &recorded, &recorded);
+
+        let stats = stats(&report, &tokens::MOVEMENT_DEPTH.0.to_string());
+        assert_eq!(stats.recorded, 100);
+        assert_eq!(stats.compared, 100);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn errors_past_tolerance_fail() {
+        let recorded = timeline(&[0.1; 100]);
+        let regenerated = timeline(&[0.2; 100]);
+        let report = Report::new(Clock::RealTime, 100, &recorded, &regenerated);
+
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn outputs_that_stop_being_produced_fail() {
+        let recorded = timeline(&[0.1; 100]);
+        let report = Report::new(Clock::RealTime, 100, &recorded, &[]);
+
+        assert_eq!(
+            stats(&report, &tokens::MOVEMENT_DEPTH.0.to_string()).compared,
+            0
+        );
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn outputs_never_recorded_fail() {
+        let regenerated = timeline(&[0.1; 100]);
+        let report = Report::new(Clock::RealTime, 100, &[], &regenerated);
+
+        assert_eq!(
+            stats(&report, &tokens::MOVEMENT_DEPTH.0.to_string()).unmatched,
+            100
+        );
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn outputs_produced_less_often_fail() {
+        let recorded = timeline(&[0.1; 100]);
+        let regenerated: Vec<_> = recorded.iter().step_by(2).cloned().collect();
+        let report = Report::new(Clock::RealTime, 100, &recorded, &regenerated);
+
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn a_trailing_output_passes() {
+        let recorded = timeline(&[0.1; 100]);
+        let mut regenerated = recorded.clone();
+        regenerated.push((at(5000), depth(0.1)));
+        let report = Report::new(Clock::RealTime, 100, &recorded, &regenerated);
+
+        assert_eq!(
+            stats(&report, &tokens::MOVEMENT_DEPTH.0.to_string()).unmatched,
+            1
+        );
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn fast_clock_checks_control_outputs() {
+        let mut recorded = timeline(&[0.1; 100]);
+        recorded.push((
+            at(0),
+            create_update(&tokens::ORIENTATION, Orientation::default()),
+        ));
+        let mut regenerated = vec![(
+            at(0),
+            create_update(&tokens::ORIENTATION, Orientation::default()),
+        )];
+
+        let report = Report::new(Clock::Fast, 100, &recorded, &regenerated);
+        assert!(!report.passed());
+
+        regenerated.extend(timeline(&[0.1; 100]));
+        let report = Report::new(Clock::Fast, 100, &recorded, &regenerated);
+        assert_eq!(
+            stats(&report, &tokens::MOVEMENT_DEPTH.0.to_string()).compared,
+            100
+        );
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn fast_clock_ticks_every_control_period() {
+        let clock = Arc::new(ManualClock::new());
+        let start = clock.now();
+        let (acks_tx, acks_rx) = unbounded();
+        let ticks = 5;
+
+        // Stands in for depth control and leveling
+        let controls = {
+            let clock = clock.clone();
+            thread::spawn(move || {
+                for tick in 1..=ticks {
+                    clock.sleep_until(start + CONTROL_PERIOD * tick);
+                    acks_tx.send(tokens::MOVEMENT_DEPTH.0).unwrap();
+                    acks_tx.send(tokens::MOVEMENT_LEVELING.0).unwrap();
+                }
+            })
+        };
+
+        let began = Instant::now();
+        advance_clock(
+            &clock,
+            start + CONTROL_PERIOD * ticks + CONTROL_PERIOD / 2,
+            &acks_rx,
+        );
+        controls.join().unwrap();
+
+        assert_eq!(clock.now() - start, CONTROL_PERIOD * ticks);
+        // No tick had to wait out the timeout
+        assert!(began.elapsed() < ACK_TIMEOUT);
+    }
+}
@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Instant};
 
 use common::{
     protocol::Protocol,
@@ -11,6 +11,8 @@ use common::{
 pub enum Event {
     PeerConnected(SocketAddr),
     PeerDisconnected(Option<SocketAddr>),
+    /// A peer changed which cameras it wants to receive
+    CameraSubscriptions(SocketAddr, Vec<String>),
 
     PacketTx(Protocol),
     PacketRx(Protocol),
@@ -25,9 +27,19 @@ pub enum Event {
     Exit,
 }
 
-// Repersents 20ms of sensor data
-#[derive(Debug, Copy, Clone)]
+/// A burst of sensor data, oldest samples first
+#[derive(Debug, Clone, Default)]
 pub struct SensorBatch {
-    pub inertial: [InertialFrame; 20],
-    pub mag: [MagFrame; 2],
+    pub inertial: Vec<Sample<InertialFrame>>,
+    /// From the MMC5983
+    pub mag: Vec<Sample<MagFrame>>,
+    /// From the AK09915
+    pub secondary_mag: Vec<Sample<MagFrame>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Sample<T> {
+    /// When the sensor took the sample, on the robot's clock
+    pub time: Instant,
+    pub frame: T,
 }
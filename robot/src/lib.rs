@@ -0,0 +1,37 @@
+//! Robot Code for the MATE Sea Owls Team
+#![feature(split_array)]
+#![warn(meta_variable_misuse)]
+
+pub mod event;
+pub mod events;
+pub mod peripheral;
+pub mod systems;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SystemId {
+    Stop,
+    Scheduler,
+    LogEvents,
+    LogForward,
+    BlackBox,
+    Error,
+    Store,
+    Network,
+    HwStatus,
+    RobotStatus,
+    Arming,
+    Motor,
+    Indicators,
+    Leak,
+    Inertial,
+    Orientation,
+    DepthControl,
+    Leveling,
+    Servo,
+    Depth,
+    Enclosure,
+    Power,
+    SensorHealth,
+    Camera,
+    Replay,
+}
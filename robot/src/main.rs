@@ -1,31 +1,26 @@
 //! Robot Code for the MATE Sea Owls Team
-#![feature(split_array)]
-#![warn(meta_variable_misuse)]
 
-pub mod event;
-pub mod events;
-pub mod peripheral;
-mod systems;
+use robot::systems::blackbox::BlackBoxSystem;
+use robot::systems::error::ErrorSystem;
+use robot::systems::log_forward::{self, LogForwardSystem};
+use robot::systems::scheduler::SchedulerSystem;
 
-use crate::systems::error::ErrorSystem;
-
-use crate::systems::SystemManager;
+use robot::systems::SystemManager;
+use robot::systems::{
+    arming::ArmingSystem, hw_stat::HwStatSystem, networking::NetworkSystem, robot::StoreSystem,
+    status::StatusSystem, stop::StopSystem,
+};
 #[cfg(rpi)]
-use crate::systems::{
+use robot::systems::{
     cameras::CameraSystem, depth::DepthSystem, depth_control::DepthControlSystem,
-    indicators::IndicatorsSystem, inertial::InertialSystem, leak::LeakSystem,
-    leveling::LevelingSystem, motor::MotorSystem, orientation::OrientationSystem,
-};
-use crate::systems::{
-    hw_stat::HwStatSystem, networking::NetworkSystem, robot::StoreSystem, status::StatusSystem,
-    stop::StopSystem,
+    enclosure::EnclosureSystem, indicators::IndicatorsSystem, inertial::InertialSystem,
+    leak::LeakSystem, leveling::LevelingSystem, motor::MotorSystem, orientation::OrientationSystem,
+    power::PowerSystem, sensor_health::SensorHealthSystem, servo::ServoSystem,
 };
-use tracing::{info, Level};
+use tracing::info;
 
 fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::DEBUG)
-        .init();
+    log_forward::init_tracing();
     info!("Starting robot");
 
     let mut systems = SystemManager::default();
@@ -33,12 +28,16 @@ fn main() -> anyhow::Result<()> {
     info!("---------- Registering systems ----------");
     {
         systems.add_system::<StopSystem>()?;
+        systems.add_system::<SchedulerSystem>()?;
         // systems.add_system::<LogEventSystem>()?;
         systems.add_system::<ErrorSystem>()?;
+        systems.add_system::<LogForwardSystem>()?;
+        systems.add_system::<BlackBoxSystem>()?;
         systems.add_system::<StoreSystem>()?;
         systems.add_system::<NetworkSystem>()?;
         systems.add_system::<HwStatSystem>()?;
         systems.add_system::<StatusSystem>()?;
+        systems.add_system::<ArmingSystem>()?;
     }
     #[cfg(rpi)]
     {
@@ -49,7 +48,11 @@ fn main() -> anyhow::Result<()> {
         systems.add_system::<OrientationSystem>()?;
         systems.add_system::<DepthControlSystem>()?;
         systems.add_system::<LevelingSystem>()?;
+        systems.add_system::<ServoSystem>()?;
         systems.add_system::<DepthSystem>()?;
+        systems.add_system::<EnclosureSystem>()?;
+        systems.add_system::<PowerSystem>()?;
+        systems.add_system::<SensorHealthSystem>()?;
         systems.add_system::<CameraSystem>()?;
     }
     info!("--------------------------------------");
@@ -60,23 +63,3 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-pub enum SystemId {
-    Stop,
-    LogEvents,
-    Error,
-    Store,
-    Network,
-    HwStatus,
-    RobotStatus,
-    Motor,
-    Indicators,
-    Leak,
-    Inertial,
-    Orientation,
-    DepthControl,
-    Leveling,
-    Depth,
-    Camera,
-}
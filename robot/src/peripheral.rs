@@ -1,6 +1,10 @@
+pub mod ads1115;
+pub mod ak09915;
+pub mod bmp280;
 pub mod icm20602;
 pub mod mmc5983;
 pub mod motor;
 pub mod ms5937;
 pub mod neopixel;
 pub mod pca9685;
+pub mod v4l2;
@@ -0,0 +1,101 @@
+use std::{thread, time::Duration};
+
+use anyhow::{bail, Context};
+use common::types::Volts;
+use rppal::i2c::I2c;
+
+/// The navigator's four channel adc
+pub struct Ads1115 {
+    i2c: I2c,
+}
+
+impl Ads1115 {
+    pub const I2C_BUS: u8 = 1;
+    pub const I2C_ADDRESS: u8 = 0x48;
+
+    pub const CHANNELS: u8 = 4;
+
+    pub fn new(bus: u8, address: u8) -> anyhow::Result<Self> {
+        let mut i2c = I2c::with_bus(bus).context("Open i2c")?;
+
+        i2c.set_slave_address(address as u16)
+            .context("Set addres for ADS1115")?;
+
+        let mut this = Self { i2c };
+        this.read_register(Self::REG_CONFIG)
+            .context("Init ADS1115")?;
+
+        Ok(this)
+    }
+
+    /// Runs a single shot conversion of one channel against ground
+    pub fn read_channel(&mut self, channel: u8) -> anyhow::Result<Volts> {
+        if channel >= Self::CHANNELS {
+            bail!("No adc channel {channel}");
+        }
+
+        let config = Self::START_CONVERSION
+            | (Self::MUX_SINGLE_ENDED | channel as u16) << 12
+            | Self::PGA_4_096V
+            | Self::MODE_SINGLE_SHOT
+            | Self::DATA_RATE_860SPS
+            | Self::COMPARATOR_DISABLED;
+        self.i2c
+            .write(&[Self::REG_CONFIG, (config >> 8) as u8, config as u8])
+            .context("Start conversion")?;
+
+        let mut attempts = 0;
+        loop {
+            thread::sleep(Self::CONVERSION_TIME);
+
+            // The bit reads back as set once the conversion is done
+            let config = self
+                .read_register(Self::REG_CONFIG)
+                .context("Read status")?;
+            if config & Self::START_CONVERSION != 0 {
+                break;
+            }
+
+            attempts += 1;
+            if attempts >= 5 {
+                bail!("Conversion timed out");
+            }
+        }
+
+        let raw = self
+            .read_register(Self::REG_CONVERSION)
+            .context("Read conversion")?;
+
+        Ok(raw_to_volts(raw as i16))
+    }
+}
+
+// Implementation based on the ADS1115 datasheet
+impl Ads1115 {
+    const REG_CONVERSION: u8 = 0x00;
+    const REG_CONFIG: u8 = 0x01;
+
+    const START_CONVERSION: u16 = 1 << 15;
+    const MUX_SINGLE_ENDED: u16 = 0b100;
+    const PGA_4_096V: u16 = 0b001 << 9;
+    const MODE_SINGLE_SHOT: u16 = 1 << 8;
+    const DATA_RATE_860SPS: u16 = 0b111 << 5;
+    const COMPARATOR_DISABLED: u16 = 0b11;
+
+    /// One sample at 860 samples per second, rounded up
+    const CONVERSION_TIME: Duration = Duration::from_micros(1200);
+
+    fn read_register(&mut self, register: u8) -> anyhow::Result<u16> {
+        let mut buffer = [0, 0];
+        self.i2c
+            .write_read(&[register], &mut buffer)
+            .context("Read register")?;
+
+        Ok(u16::from_be_bytes(buffer))
+    }
+}
+
+/// Full scale is ±4.096V
+fn raw_to_volts(raw: i16) -> Volts {
+    Volts(raw as f64 * 4.096 / 32768.0)
+}
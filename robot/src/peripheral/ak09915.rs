@@ -0,0 +1,105 @@
+use std::{thread, time::Duration};
+
+use anyhow::{bail, Context};
+use common::types::{Gauss, MagFrame};
+use rppal::i2c::I2c;
+
+/// The navigator's second compass, next to the MMC5983
+pub struct Ak09915 {
+    i2c: I2c,
+}
+
+impl Ak09915 {
+    pub const I2C_BUS: u8 = 1;
+    pub const I2C_ADDRESS: u8 = 0x0C;
+
+    pub fn new(bus: u8, address: u8) -> anyhow::Result<Self> {
+        let mut i2c = I2c::with_bus(bus).context("Open i2c")?;
+
+        i2c.set_slave_address(address as u16)
+            .context("Set addres for AK09915")?;
+
+        let mut this = Self { i2c };
+        this.initialize().context("Init AK09915")?;
+
+        Ok(this)
+    }
+
+    /// Reads the newest measurement, `None` if there has not been one since the last read
+    pub fn read_frame(&mut self) -> anyhow::Result<Option<MagFrame>> {
+        // ST1, the six data bytes, a dummy and ST2. Reading ST2 ends the read and unlocks the
+        // data registers
+        let mut raw = [0; 9];
+        self.i2c
+            .write_read(&[Self::REG_ST1], &mut raw)
+            .context("Read raw frame")?;
+
+        if raw[0] & Self::ST1_DRDY == 0 {
+            return Ok(None);
+        }
+        if raw[8] & Self::ST2_HOFL != 0 {
+            bail!("Magnetic sensor overflow");
+        }
+
+        let raw_mag_native_x = i16::from_le_bytes([raw[1], raw[2]]);
+        let raw_mag_native_y = i16::from_le_bytes([raw[3], raw[4]]);
+        let raw_mag_native_z = i16::from_le_bytes([raw[5], raw[6]]);
+
+        let mag_native_x = raw_mag_native_x as f64 * Self::GAUSS_PER_LSB;
+        let mag_native_y = raw_mag_native_y as f64 * Self::GAUSS_PER_LSB;
+        let mag_native_z = raw_mag_native_z as f64 * Self::GAUSS_PER_LSB;
+
+        // Sits in the same orientation as the ICM20602. AK09915 axes are the InvenSense ones
+        // with x and y swapped and z pointing into the board, which puts them in the same frame
+        // as the MMC5983 like this
+        let mag_x = -mag_native_x;
+        let mag_y = -mag_native_y;
+        let mag_z = mag_native_z;
+
+        Ok(Some(MagFrame {
+            mag_x: Gauss(mag_x),
+            mag_y: Gauss(mag_y),
+            mag_z: Gauss(mag_z),
+        }))
+    }
+}
+
+impl Ak09915 {
+    const REG_WIA1: u8 = 0x00;
+    const REG_ST1: u8 = 0x10;
+    const REG_CNTL2: u8 = 0x31;
+    const REG_CNTL3: u8 = 0x32;
+
+    const COMPANY_ID: u8 = 0x48;
+    const DEVICE_ID: u8 = 0x10;
+
+    const ST1_DRDY: u8 = 0x01;
+    const ST2_HOFL: u8 = 0x08;
+
+    const MODE_CONTINUOUS_100HZ: u8 = 0x08;
+    const SOFT_RESET: u8 = 0x01;
+
+    /// 0.15 µT per count
+    const GAUSS_PER_LSB: f64 = 0.0015;
+
+    fn initialize(&mut self) -> anyhow::Result<()> {
+        self.i2c
+            .write(&[Self::REG_CNTL3, Self::SOFT_RESET])
+            .context("Reset AK09915")?;
+        thread::sleep(Duration::from_millis(1));
+
+        let mut id = [0, 0];
+        self.i2c
+            .write_read(&[Self::REG_WIA1], &mut id)
+            .context("Read id")?;
+        if id != [Self::COMPANY_ID, Self::DEVICE_ID] {
+            bail!("Not an AK09915, got id {:#04x} {:#04x}", id[0], id[1]);
+        }
+
+        self.i2c
+            .write(&[Self::REG_CNTL2, Self::MODE_CONTINUOUS_100HZ])
+            .context("Continous mode")?;
+
+        Ok(())
+    }
+}
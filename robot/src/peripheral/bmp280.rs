@@ -0,0 +1,202 @@
+use std::{thread, time::Duration};
+
+use anyhow::{bail, Context};
+use common::types::{Celsius, EnclosureFrame, Mbar};
+use rppal::i2c::I2c;
+
+/// The navigator's onboard barometer, measures the inside of the enclosure
+pub struct Bmp280 {
+    i2c: I2c,
+    calibration: Calibration,
+}
+
+/// Trimming parameters burned into each chip
+#[derive(Debug, Clone, Copy, Default)]
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+}
+
+impl Bmp280 {
+    pub const I2C_BUS: u8 = 1;
+    pub const I2C_ADDRESS: u8 = 0x76;
+
+    pub fn new(bus: u8, address: u8) -> anyhow::Result<Self> {
+        let mut i2c = I2c::with_bus(bus).context("Open i2c")?;
+
+        i2c.set_slave_address(address as u16)
+            .context("Set addres for BMP280")?;
+
+        let mut this = Self {
+            i2c,
+            calibration: Default::default(),
+        };
+        this.initialize().context("Init BMP280")?;
+
+        Ok(this)
+    }
+
+    /// Reads the newest measurement, the chip measures continuously in the background
+    pub fn read_frame(&mut self) -> anyhow::Result<EnclosureFrame> {
+        // Pressure then temperature, read in one burst so both come from the same measurement
+        let mut raw = [0; 6];
+        self.i2c
+            .write_read(&[Self::REG_PRESS_MSB], &mut raw)
+            .context("Read raw frame")?;
+
+        let raw_pressure = (raw[0] as i32) << 12 | (raw[1] as i32) << 4 | (raw[2] as i32) >> 4;
+        let raw_temperature = (raw[3] as i32) << 12 | (raw[4] as i32) << 4 | (raw[5] as i32) >> 4;
+
+        let (pressure, temperature) = compensate(raw_pressure, raw_temperature, &self.calibration);
+
+        Ok(EnclosureFrame {
+            pressure,
+            temperature,
+        })
+    }
+}
+
+// Implementation based on the BMP280 datasheet
+impl Bmp280 {
+    const REG_CALIBRATION: u8 = 0x88;
+    const REG_ID: u8 = 0xD0;
+    const REG_RESET: u8 = 0xE0;
+    const REG_CTRL_MEAS: u8 = 0xF4;
+    const REG_CONFIG: u8 = 0xF5;
+    const REG_PRESS_MSB: u8 = 0xF7;
+
+    const CHIP_ID: u8 = 0x58;
+    const SOFT_RESET: u8 = 0xB6;
+
+    /// Temperature oversampling x2, pressure oversampling x16, normal mode
+    const CTRL_MEAS: u8 = 0x57;
+    /// 125ms standby, iir filter coefficient 16
+    const CONFIG: u8 = 0x50;
+
+    fn initialize(&mut self) -> anyhow::Result<()> {
+        self.i2c
+            .write(&[Self::REG_RESET, Self::SOFT_RESET])
+            .context("Reset BMP280")?;
+        thread::sleep(Duration::from_millis(5));
+
+        let mut id = [0];
+        self.i2c
+            .write_read(&[Self::REG_ID], &mut id)
+            .context("Read id")?;
+        if id[0] != Self::CHIP_ID {
+            bail!("Not a BMP280, got id {:#04x}", id[0]);
+        }
+
+        let mut raw = [0; 24];
+        self.i2c
+            .write_read(&[Self::REG_CALIBRATION], &mut raw)
+            .context("Read calibration")?;
+
+        let unsigned = |idx: usize| u16::from_le_bytes([raw[idx], raw[idx + 1]]);
+        let signed = |idx: usize| i16::from_le_bytes([raw[idx], raw[idx + 1]]);
+        self.calibration = Calibration {
+            t1: unsigned(0),
+            t2: signed(2),
+            t3: signed(4),
+            p1: unsigned(6),
+            p2: signed(8),
+            p3: signed(10),
+            p4: signed(12),
+            p5: signed(14),
+            p6: signed(16),
+            p7: signed(18),
+            p8: signed(20),
+            p9: signed(22),
+        };
+
+        // Config is only writable while the chip sleeps, which it does after a reset
+        self.i2c
+            .write(&[Self::REG_CONFIG, Self::CONFIG])
+            .context("Configure filter")?;
+        self.i2c
+            .write(&[Self::REG_CTRL_MEAS, Self::CTRL_MEAS])
+            .context("Normal mode")?;
+
+        Ok(())
+    }
+}
+
+/// Integer compensation from the datasheet, the pressure uses the 64 bit variant
+fn compensate(
+    raw_pressure: i32,
+    raw_temperature: i32,
+    calibration: &Calibration,
+) -> (Mbar, Celsius) {
+    let c = calibration;
+
+    let var1 = (((raw_temperature >> 3) - ((c.t1 as i32) << 1)) * c.t2 as i32) >> 11;
+    let var2 =
+        (((((raw_temperature >> 4) - c.t1 as i32) * ((raw_temperature >> 4) - c.t1 as i32)) >> 12)
+            * c.t3 as i32)
+            >> 14;
+    let t_fine = var1 + var2;
+    let temperature = (t_fine * 5 + 128) >> 8;
+
+    let var1 = t_fine as i64 - 128000;
+    let var2 = var1 * var1 * c.p6 as i64;
+    let var2 = var2 + ((var1 * c.p5 as i64) << 17);
+    let var2 = var2 + ((c.p4 as i64) << 35);
+    let var1 = ((var1 * var1 * c.p3 as i64) >> 8) + ((var1 * c.p2 as i64) << 12);
+    let var1 = (((1i64 << 47) + var1) * c.p1 as i64) >> 33;
+
+    // Only happens with a blank calibration
+    let pressure = if var1 == 0 {
+        0
+    } else {
+        let p = 1048576 - raw_pressure as i64;
+        let p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = (c.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = (c.p8 as i64 * p) >> 19;
+
+        ((p + var1 + var2) >> 8) + ((c.p7 as i64) << 4)
+    };
+
+    // Pressure is in 1/256 Pa and temperature in 1/100 °C
+    (
+        Mbar(pressure as f64 / 256.0 / 100.0),
+        Celsius(temperature as f64 / 100.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_datasheet_example() {
+        let calibration = Calibration {
+            t1: 27504,
+            t2: 26435,
+            t3: -1000,
+            p1: 36477,
+            p2: -10685,
+            p3: 3024,
+            p4: 2855,
+            p5: 140,
+            p6: -7,
+            p7: 15500,
+            p8: -14600,
+            p9: 6000,
+        };
+
+        let (pressure, temperature) = compensate(415148, 519888, &calibration);
+
+        assert!((temperature.0 - 25.08).abs() < 0.005, "{temperature}");
+        assert!((pressure.0 - 1006.53).abs() < 0.01, "{pressure}");
+    }
+}
@@ -1,87 +1,251 @@
 use std::{thread, time::Duration};
 
 use anyhow::Context;
-use common::types::{Celsius, Dps, GForce, InertialFrame};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use common::types::{
+    AccelFilter, AccelRange, Celsius, Dps, GForce, GyroFilter, GyroRange, ImuSampleRate,
+    ImuSettings, InertialFrame,
+};
+use rppal::{
+    gpio::{Gpio, InputPin, Trigger},
+    spi::{Bus, Mode, SlaveSelect, Spi},
+};
 
 pub struct Icm20602 {
     spi: Spi,
+    data_ready: InputPin,
+    settings: ImuSettings,
+}
+
+/// Frames drained from the fifo, oldest first
+#[derive(Debug, Default)]
+pub struct FifoRead {
+    pub frames: Vec<InertialFrame>,
+    /// The fifo filled up and was reset, an unknown number of samples were lost
+    pub overflowed: bool,
 }
 
 impl Icm20602 {
     pub const SPI_BUS: Bus = Bus::Spi1;
     pub const SPI_SELECT: SlaveSelect = SlaveSelect::Ss2;
     pub const SPI_CLOCK: u32 = 10_000_000;
-
-    pub fn new(bus: Bus, slave_select: SlaveSelect, clock_speed: u32) -> anyhow::Result<Self> {
+    pub const DATA_READY_PIN: u8 = 22;
+
+    pub fn new(
+        bus: Bus,
+        slave_select: SlaveSelect,
+        clock_speed: u32,
+        data_ready_pin: u8,
+        settings: ImuSettings,
+    ) -> anyhow::Result<Self> {
         let spi = Spi::new(bus, slave_select, clock_speed, Mode::Mode0).context("Open spi")?;
 
-        let mut this = Self { spi };
+        let gpio = Gpio::new().context("Open gpio")?;
+        let mut data_ready = gpio
+            .get(data_ready_pin)
+            .context("Open data ready pin")?
+            .into_input_pulldown();
+        data_ready
+            .set_interrupt(Trigger::RisingEdge)
+            .context("Set data ready interrupt")?;
+
+        let mut this = Self {
+            spi,
+            data_ready,
+            settings,
+        };
         this.initialize().context("Initialize")?;
 
         Ok(this)
     }
 
+    pub fn settings(&self) -> ImuSettings {
+        self.settings
+    }
+
+    /// Time between samples written to the fifo
+    pub fn sample_period(&self) -> Duration {
+        self.settings.sample_rate.period()
+    }
+
+    /// Reconfigures the sensor, samples still in the fifo are discarded
+    pub fn apply_settings(&mut self, settings: ImuSettings) -> anyhow::Result<()> {
+        self.write_settings(settings).context("Write settings")?;
+        self.settings = settings;
+
+        // Samples taken with the old settings would be scaled wrong
+        self.reset_fifo().context("Reset fifo")?;
+
+        Ok(())
+    }
+
+    /// Blocks until the next sample is written to the fifo, returns false on timeout
+    pub fn wait_data_ready(&mut self, timeout: Duration) -> anyhow::Result<bool> {
+        let level = self
+            .data_ready
+            .poll_interrupt(false, Some(timeout))
+            .context("Poll data ready")?;
+
+        Ok(level.is_some())
+    }
+
+    /// Reads the current sample directly from the output registers
     pub fn read_frame(&mut self) -> anyhow::Result<InertialFrame> {
         let raw = self.read_raw_frame().context("Read raw frame")?;
 
         // The first byte is junk
-        let raw = &raw[1..];
+        let raw = raw[1..].try_into().expect("Frame size");
+
+        Ok(parse_frame(raw, &self.settings))
+    }
+
+    /// Drains every complete sample from the fifo
+    pub fn read_fifo(&mut self) -> anyhow::Result<FifoRead> {
+        let status = self
+            .read_register(Self::REG_INT_STATUS)
+            .context("Read status")?;
+        if status & Self::INT_FIFO_OFLOW != 0 {
+            // Packets can be misaligned after an overflow, nothing in the fifo can be trusted
+            self.reset_fifo().context("Reset fifo")?;
+
+            return Ok(FifoRead {
+                frames: Vec::new(),
+                overflowed: true,
+            });
+        }
+
+        let count_high = self
+            .read_register(Self::REG_FIFO_COUNTH)
+            .context("Read fifo count")?;
+        let count_low = self
+            .read_register(Self::REG_FIFO_COUNTL)
+            .context("Read fifo count")?;
+        let count = (count_high as usize) << 8 | count_low as usize;
+
+        let samples =
+            (count / Self::FIFO_PACKET_SIZE).min(Self::FIFO_SIZE / Self::FIFO_PACKET_SIZE);
+        if samples == 0 {
+            return Ok(FifoRead::default());
+        }
+
+        let mut output = vec![0; 1 + samples * Self::FIFO_PACKET_SIZE];
+        let mut input = vec![0; output.len()];
+
+        // Reads of the fifo register do not advance the address
+        output[0] = Self::REG_FIFO_R_W | Self::READ;
+
+        self.spi
+            .transfer(&mut input, &output)
+            .context("Read fifo")?;
+
+        // The first byte is junk
+        let frames = input[1..]
+            .chunks_exact(Self::FIFO_PACKET_SIZE)
+            .map(|packet| parse_frame(packet.try_into().expect("Packet size"), &self.settings))
+            .collect();
+
+        Ok(FifoRead {
+            frames,
+            overflowed: false,
+        })
+    }
+}
 
-        let raw_accel_native_x = (raw[0] as u16) << 8 | raw[1] as u16;
-        let raw_accel_native_y = (raw[2] as u16) << 8 | raw[3] as u16;
-        let raw_accel_native_z = (raw[4] as u16) << 8 | raw[5] as u16;
+/// Parses accel, temperature and gyro data, the layout of both the output registers and fifo
+/// packets
+fn parse_frame(raw: &[u8; 14], settings: &ImuSettings) -> InertialFrame {
+    let accel_scale = accel_lsb_per_g(settings.accel_range);
+    let gyro_scale = gyro_lsb_per_dps(settings.gyro_range);
 
-        let raw_tempature = (raw[6] as u16) << 8 | raw[7] as u16;
+    let raw_accel_native_x = (raw[0] as u16) << 8 | raw[1] as u16;
+    let raw_accel_native_y = (raw[2] as u16) << 8 | raw[3] as u16;
+    let raw_accel_native_z = (raw[4] as u16) << 8 | raw[5] as u16;
 
-        let raw_gyro_native_x = (raw[8] as u16) << 8 | raw[9] as u16;
-        let raw_gyro_native_y = (raw[10] as u16) << 8 | raw[11] as u16;
-        let raw_gyro_native_z = (raw[12] as u16) << 8 | raw[13] as u16;
+    let raw_tempature = (raw[6] as u16) << 8 | raw[7] as u16;
 
-        let accel_native_x = raw_accel_native_x as i16 as f64 / 16384.0;
-        let accel_native_y = raw_accel_native_y as i16 as f64 / 16384.0;
-        let accel_native_z = raw_accel_native_z as i16 as f64 / 16384.0;
+    let raw_gyro_native_x = (raw[8] as u16) << 8 | raw[9] as u16;
+    let raw_gyro_native_y = (raw[10] as u16) << 8 | raw[11] as u16;
+    let raw_gyro_native_z = (raw[12] as u16) << 8 | raw[13] as u16;
 
-        let tempature = raw_tempature as i16 as f64 / 326.8 + 25.0;
+    let accel_native_x = raw_accel_native_x as i16 as f64 / accel_scale;
+    let accel_native_y = raw_accel_native_y as i16 as f64 / accel_scale;
+    let accel_native_z = raw_accel_native_z as i16 as f64 / accel_scale;
 
-        let gyro_native_x = raw_gyro_native_x as i16 as f64 / 65.5;
-        let gyro_native_y = raw_gyro_native_y as i16 as f64 / 65.5;
-        let gyro_native_z = raw_gyro_native_z as i16 as f64 / 65.5;
+    let tempature = raw_tempature as i16 as f64 / 326.8 + 25.0;
 
-        let accel_x = -accel_native_y;
-        let accel_y = -accel_native_x;
-        let accel_z = -accel_native_z;
+    let gyro_native_x = raw_gyro_native_x as i16 as f64 / gyro_scale;
+    let gyro_native_y = raw_gyro_native_y as i16 as f64 / gyro_scale;
+    let gyro_native_z = raw_gyro_native_z as i16 as f64 / gyro_scale;
 
-        let gyro_x = -gyro_native_y;
-        let gyro_y = -gyro_native_x;
-        let gyro_z = -gyro_native_z;
+    let accel_x = -accel_native_y;
+    let accel_y = -accel_native_x;
+    let accel_z = -accel_native_z;
 
-        Ok(InertialFrame {
-            gyro_x: Dps(gyro_x),
-            gyro_y: Dps(gyro_y),
-            gyro_z: Dps(gyro_z),
-            accel_x: GForce(accel_x),
-            accel_y: GForce(accel_y),
-            accel_z: GForce(accel_z),
-            tempature: Celsius(tempature),
-        })
+    let gyro_x = -gyro_native_y;
+    let gyro_y = -gyro_native_x;
+    let gyro_z = -gyro_native_z;
+
+    InertialFrame {
+        gyro_x: Dps(gyro_x),
+        gyro_y: Dps(gyro_y),
+        gyro_z: Dps(gyro_z),
+        accel_x: GForce(accel_x),
+        accel_y: GForce(accel_y),
+        accel_z: GForce(accel_z),
+        tempature: Celsius(tempature),
+    }
+}
+
+fn accel_lsb_per_g(range: AccelRange) -> f64 {
+    match range {
+        AccelRange::G2 => 16384.0,
+        AccelRange::G4 => 8192.0,
+        AccelRange::G8 => 4096.0,
+        AccelRange::G16 => 2048.0,
+    }
+}
+
+fn gyro_lsb_per_dps(range: GyroRange) -> f64 {
+    match range {
+        GyroRange::Dps250 => 131.0,
+        GyroRange::Dps500 => 65.5,
+        GyroRange::Dps1000 => 32.8,
+        GyroRange::Dps2000 => 16.4,
     }
 }
 
 // Implementation based on https://github.com/bluerobotics/icm20602-python
 impl Icm20602 {
     const REG_I2C_IF: u8 = 0x70;
+    const REG_SMPLRT_DIV: u8 = 0x19;
     const REG_CONFIG: u8 = 0x1A;
     const REG_GYRO_CONFIG: u8 = 0x1B;
     const REG_ACCEL_CONFIG: u8 = 0x1C;
     const REG_ACCEL_CONFIG_2: u8 = 0x1D;
+    const REG_FIFO_EN: u8 = 0x23;
+    const REG_INT_PIN_CFG: u8 = 0x37;
+    const REG_INT_ENABLE: u8 = 0x38;
+    const REG_INT_STATUS: u8 = 0x3A;
     const REG_ACCEL_INTEL_CTRL: u8 = 0x69;
+    const REG_USER_CTRL: u8 = 0x6A;
     const REG_PWR_MGMT_1: u8 = 0x6B;
+    const REG_FIFO_COUNTH: u8 = 0x72;
+    const REG_FIFO_COUNTL: u8 = 0x73;
+    const REG_FIFO_R_W: u8 = 0x74;
     const REG_WHO_AM_I: u8 = 0x75;
     const REG_ACCEL_XOUT_H: u8 = 0x3B;
 
     const READ: u8 = 0x80;
 
+    const INT_DATA_RDY: u8 = 0x01;
+    const INT_FIFO_OFLOW: u8 = 0x10;
+
+    const USER_CTRL_FIFO_EN: u8 = 0x40;
+    const USER_CTRL_FIFO_RST: u8 = 0x04;
+
+    /// Accel, temperature and gyro, the temperature is always included
+    const FIFO_PACKET_SIZE: usize = 14;
+    const FIFO_SIZE: usize = 1008;
+
     fn initialize(&mut self) -> anyhow::Result<()> {
         let mut id = [0, 0];
         self.spi
@@ -93,25 +257,8 @@ impl Icm20602 {
             .write(&[Self::REG_I2C_IF, 0x40])
             .context("Disable i2c")?;
 
-        // 1Hz sample rate
-        self.spi
-            .write(&[Self::REG_CONFIG, 0x1])
-            .context("Setup lowpass filter")?;
-
-        // 500 deg range, lowpass filter
-        self.spi
-            .write(&[Self::REG_GYRO_CONFIG, 0x0 | 0b01 << 3])
-            .context("Setup gyro")?;
-
-        // 2g range
-        self.spi
-            .write(&[Self::REG_ACCEL_CONFIG, 0x0])
-            .context("Setup accel")?;
-
-        // lowpass filter
-        self.spi
-            .write(&[Self::REG_ACCEL_CONFIG_2, 0x0])
-            .context("Setup accel")?;
+        self.write_settings(self.settings)
+            .context("Write settings")?;
 
         // Disable output limit
         self.spi
@@ -126,9 +273,109 @@ impl Icm20602 {
         // Delay to allow sensors to start up and stabilize
         thread::sleep(Duration::from_millis(100));
 
+        // Pulse the interrupt pin on every sample, active high push pull
+        self.spi
+            .write(&[Self::REG_INT_PIN_CFG, 0x0])
+            .context("Setup interrupt pin")?;
+        self.spi
+            .write(&[
+                Self::REG_INT_ENABLE,
+                Self::INT_DATA_RDY | Self::INT_FIFO_OFLOW,
+            ])
+            .context("Enable interrupts")?;
+
+        self.spi
+            .write(&[Self::REG_FIFO_EN, 0b0001_1000])
+            .context("Select fifo sensors")?;
+        self.reset_fifo().context("Enable fifo")?;
+
         Ok(())
     }
 
+    fn write_settings(&mut self, settings: ImuSettings) -> anyhow::Result<()> {
+        // Every filter setting keeps the internal sample rate at 1kHz, which the divider then
+        // slows down. The fifo overwrites the oldest samples when full so the newest are always
+        // available.
+        let gyro_filter = match settings.gyro_filter {
+            GyroFilter::Hz176 => 1,
+            GyroFilter::Hz92 => 2,
+            GyroFilter::Hz41 => 3,
+            GyroFilter::Hz20 => 4,
+            GyroFilter::Hz10 => 5,
+            GyroFilter::Hz5 => 6,
+        };
+        self.spi
+            .write(&[Self::REG_CONFIG, gyro_filter])
+            .context("Setup lowpass filter")?;
+
+        let divider = match settings.sample_rate {
+            ImuSampleRate::Hz1000 => 0,
+            ImuSampleRate::Hz500 => 1,
+            ImuSampleRate::Hz250 => 3,
+            ImuSampleRate::Hz200 => 4,
+            ImuSampleRate::Hz100 => 9,
+            ImuSampleRate::Hz50 => 19,
+        };
+        self.spi
+            .write(&[Self::REG_SMPLRT_DIV, divider])
+            .context("Setup sample rate")?;
+
+        let gyro_range: u8 = match settings.gyro_range {
+            GyroRange::Dps250 => 0b00,
+            GyroRange::Dps500 => 0b01,
+            GyroRange::Dps1000 => 0b10,
+            GyroRange::Dps2000 => 0b11,
+        };
+        self.spi
+            .write(&[Self::REG_GYRO_CONFIG, gyro_range << 3])
+            .context("Setup gyro")?;
+
+        let accel_range: u8 = match settings.accel_range {
+            AccelRange::G2 => 0b00,
+            AccelRange::G4 => 0b01,
+            AccelRange::G8 => 0b10,
+            AccelRange::G16 => 0b11,
+        };
+        self.spi
+            .write(&[Self::REG_ACCEL_CONFIG, accel_range << 3])
+            .context("Setup accel")?;
+
+        let accel_filter = match settings.accel_filter {
+            AccelFilter::Hz218 => 0,
+            AccelFilter::Hz99 => 2,
+            AccelFilter::Hz45 => 3,
+            AccelFilter::Hz21 => 4,
+            AccelFilter::Hz10 => 5,
+            AccelFilter::Hz5 => 6,
+        };
+        self.spi
+            .write(&[Self::REG_ACCEL_CONFIG_2, accel_filter])
+            .context("Setup accel filter")?;
+
+        Ok(())
+    }
+
+    fn reset_fifo(&mut self) -> anyhow::Result<()> {
+        self.spi
+            .write(&[Self::REG_USER_CTRL, Self::USER_CTRL_FIFO_RST])
+            .context("Reset fifo")?;
+        self.spi
+            .write(&[Self::REG_USER_CTRL, Self::USER_CTRL_FIFO_EN])
+            .context("Start fifo")?;
+
+        Ok(())
+    }
+
+    fn read_register(&mut self, register: u8) -> anyhow::Result<u8> {
+        let mut input = [0, 0];
+
+        self.spi
+            .transfer(&mut input, &[register | Self::READ, 0])
+            .context("Read register")?;
+
+        Ok(input[1])
+    }
+
     fn read_raw_frame(&mut self) -> anyhow::Result<[u8; 15]> {
         let mut output = [0; 15];
         let mut input = [0; 15];
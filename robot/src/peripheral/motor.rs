@@ -1,4 +1,4 @@
-use common::types::{MotorId, Percent};
+use common::types::{EscCalibration, MotorId, Percent};
 use std::fmt::Debug;
 use std::time::Duration;
 
@@ -9,6 +9,7 @@ const DEFAULT_MOTOR_CW: Motor = Motor {
     reverse: Duration::from_micros(1100),
     forward: Duration::from_micros(1900),
     center: Duration::from_micros(1500),
+    deadband: Duration::from_micros(50),
 };
 const DEFAULT_MOTOR_CCW: Motor = Motor {
     channel: 255,
@@ -17,6 +18,7 @@ const DEFAULT_MOTOR_CCW: Motor = Motor {
     reverse: Duration::from_micros(1100),
     forward: Duration::from_micros(1900),
     center: Duration::from_micros(1500),
+    deadband: Duration::from_micros(50),
 };
 
 const DEFAULT_SERVO: Motor = Motor {
@@ -26,8 +28,28 @@ const DEFAULT_SERVO: Motor = Motor {
     reverse: Duration::from_micros(1100),
     forward: Duration::from_micros(1900),
     center: Duration::from_micros(1500),
+    deadband: Duration::ZERO,
 };
 
+pub const MOTOR_IDS: [MotorId; 16] = [
+    MotorId::FrontLeftBottom,
+    MotorId::FrontLeftTop,
+    MotorId::FrontRightBottom,
+    MotorId::FrontRightTop,
+    MotorId::BackLeftBottom,
+    MotorId::BackLeftTop,
+    MotorId::BackRightBottom,
+    MotorId::BackRightTop,
+    MotorId::Camera1,
+    MotorId::Camera2,
+    MotorId::Camera3,
+    MotorId::Camera4,
+    MotorId::Aux1,
+    MotorId::Aux2,
+    MotorId::Aux3,
+    MotorId::Aux4,
+];
+
 // ---------- Thrusters ----------
 //7
 pub const MOTOR_FLB: Motor = Motor {
@@ -118,26 +140,74 @@ pub struct Motor {
     pub reverse: Duration,
     pub forward: Duration,
     pub center: Duration,
+    /// Width of the band around center that the esc ignores
+    pub deadband: Duration,
 }
 
 impl Motor {
     #[must_use]
     pub fn value_to_pwm(&self, speed: Percent) -> Duration {
         let speed = speed.get() * self.max_value.get();
+        if speed == 0.0 {
+            return self.center;
+        }
+
+        self.pulse_past_deadband(speed > 0.0, speed.abs())
+    }
 
-        let upper = if speed >= 0.0 {
-            self.forward.as_micros()
+    /// Pulse `travel` of the way from the edge of the deadband to the endpoint, in the esc's
+    /// own direction. Starting at the edge means small commands still produce thrust
+    #[must_use]
+    pub fn pulse_past_deadband(&self, forward: bool, travel: f64) -> Duration {
+        let center = self.center.as_secs_f64() * 1_000_000.0;
+        let half_deadband = self.deadband.as_secs_f64() * 1_000_000.0 / 2.0;
+        let (lower, upper) = if forward {
+            (
+                center + half_deadband,
+                self.forward.as_secs_f64() * 1_000_000.0,
+            )
         } else {
-            self.reverse.as_micros()
+            (
+                center - half_deadband,
+                self.reverse.as_secs_f64() * 1_000_000.0,
+            )
         };
-        let lower = self.center.as_micros();
 
-        let scaled_speed = speed.abs() * 1000.0;
-        let pulse = (upper as i64 * scaled_speed as i64
-            + lower as i64 * (1000 - scaled_speed as i64))
-            / 1000;
+        let travel = travel.clamp(0.0, 1.0);
+        let pulse = lower + (upper - lower) * travel;
+
+        Duration::from_micros(pulse.round() as u64)
+    }
+
+    /// Current pulse widths of this channel
+    pub fn calibration(&self) -> EscCalibration {
+        EscCalibration {
+            min: self.reverse,
+            center: self.center,
+            max: self.forward,
+            deadband: self.deadband,
+            reverse: self.max_value.get() < 0.0,
+        }
+    }
+
+    /// Replaces the pulse widths and direction of this channel
+    #[must_use]
+    pub fn calibrated(self, calibration: &EscCalibration) -> Self {
+        let max_value = self.max_value.get().abs();
+        let max_value = if calibration.reverse {
+            -max_value
+        } else {
+            max_value
+        };
 
-        Duration::from_micros(pulse as u64)
+        Self {
+            max_value: Percent::new(max_value),
+            reverse: calibration.min,
+            forward: calibration.max,
+            center: calibration.center,
+            deadband: calibration.deadband,
+            ..self
+        }
     }
 }
 
@@ -165,3 +235,68 @@ impl From<MotorId> for Motor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOTOR: Motor = Motor {
+        channel: 0,
+        max_value: Percent::new(1.0),
+        reverse: Duration::from_micros(1000),
+        forward: Duration::from_micros(2000),
+        center: Duration::from_micros(1520),
+        deadband: Duration::from_micros(80),
+    };
+
+    #[test]
+    fn zero_is_center() {
+        assert_eq!(
+            MOTOR.value_to_pwm(Percent::ZERO),
+            Duration::from_micros(1520)
+        );
+    }
+
+    #[test]
+    fn small_commands_start_at_deadband_edge() {
+        assert_eq!(
+            MOTOR.value_to_pwm(Percent::new(1e-6)),
+            Duration::from_micros(1560)
+        );
+        assert_eq!(
+            MOTOR.value_to_pwm(Percent::new(-1e-6)),
+            Duration::from_micros(1480)
+        );
+    }
+
+    #[test]
+    fn full_commands_reach_endpoints() {
+        assert_eq!(
+            MOTOR.value_to_pwm(Percent::new(1.0)),
+            Duration::from_micros(2000)
+        );
+        assert_eq!(
+            MOTOR.value_to_pwm(Percent::new(-1.0)),
+            Duration::from_micros(1000)
+        );
+    }
+
+    #[test]
+    fn reverse_flag_flips_direction() {
+        let calibration = EscCalibration {
+            reverse: true,
+            ..MOTOR.calibration()
+        };
+        let motor = MOTOR.calibrated(&calibration);
+
+        assert_eq!(
+            motor.value_to_pwm(Percent::new(1.0)),
+            Duration::from_micros(1000)
+        );
+        assert_eq!(
+            motor.value_to_pwm(Percent::new(-1.0)),
+            Duration::from_micros(2000)
+        );
+        assert!(motor.calibration().reverse);
+    }
+}
@@ -1,20 +1,54 @@
-use std::{thread, time::Duration};
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::{bail, Context};
-use common::types::{Celsius, DepthFrame, Mbar, Meters};
+use common::types::{
+    Celsius, DepthCalibration, DepthFrame, DepthSensorSettings, Mbar, Meters, Oversampling,
+    WaterType,
+};
 use rppal::i2c::I2c;
+use tracing::info;
 
 pub struct Ms5837 {
     i2c: I2c,
+    model: Ms5837Model,
     calibration: [u16; 8],
-    fluid_density: f64,
+    settings: DepthSensorSettings,
+    surface_pressure: Mbar,
+    water_type: WaterType,
+}
+
+/// Variants differ in range and in how readings are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ms5837Model {
+    /// 30 bar, 0.2 mbar resolution
+    Ms5837_30Ba,
+    /// 2 bar, 0.016 mbar resolution
+    Ms5837_02Ba,
+}
+
+impl Ms5837Model {
+    /// Identifies the part from the version field in the first PROM word
+    pub fn from_prom(prom: u16) -> anyhow::Result<Self> {
+        let version = (prom >> 5) & 0x7F;
+
+        match version {
+            0x1A => Ok(Self::Ms5837_30Ba),
+            0x00 | 0x15 => Ok(Self::Ms5837_02Ba),
+            version => {
+                bail!("Unsupported MS5837 version {version:#04x}, expected the 30BA or 02BA")
+            }
+        }
+    }
 }
 
 impl Ms5837 {
     pub const I2C_BUS: u8 = 6;
     pub const I2C_ADDRESS: u8 = 0x76;
 
-    pub fn new(bus: u8, address: u8) -> anyhow::Result<Self> {
+    pub fn new(bus: u8, address: u8, settings: DepthSensorSettings) -> anyhow::Result<Self> {
         let mut i2c = I2c::with_bus(bus).context("Open i2c")?;
 
         i2c.set_slave_address(address as u16)
@@ -22,9 +56,12 @@ impl Ms5837 {
 
         let mut this = Self {
             i2c,
+            // Replaced once the PROM is read
+            model: Ms5837Model::Ms5837_30Ba,
             calibration: [0; 8],
-            // TODO tweak
-            fluid_density: 1000.0,
+            settings,
+            surface_pressure: DepthCalibration::default().surface_pressure,
+            water_type: WaterType::default(),
         };
 
         this.initialize().context("Init MS5837")?;
@@ -35,28 +72,51 @@ impl Ms5837 {
     pub fn read_frame(&mut self) -> anyhow::Result<DepthFrame> {
         let raw = self.read_raw().context("Read raw frame")?;
 
-        let (pressure, temperature) = calculate_pressure_and_temperature(raw, &self.calibration);
+        let (pressure, temperature) =
+            calculate_pressure_and_temperature(self.model, raw, &self.calibration);
         let altitude = pressure_to_altitude(pressure);
-        let depth = pressure_to_depth(pressure, self.fluid_density);
+        let fluid_density = fluid_density(self.water_type, temperature);
+        let depth = pressure_to_depth(pressure, self.surface_pressure, fluid_density);
 
         Ok(DepthFrame {
+            sampled: SystemTime::now(),
             depth,
             altitude,
             pressure,
             temperature,
+            surface_pressure: self.surface_pressure,
+            fluid_density,
+            water_type: self.water_type,
         })
     }
 
-    pub fn set_fluid_density(&mut self, density: f64) {
-        self.fluid_density = density;
+    pub fn model(&self) -> Ms5837Model {
+        self.model
+    }
+
+    pub fn set_surface_pressure(&mut self, pressure: Mbar) {
+        self.surface_pressure = pressure;
+    }
+
+    pub fn set_water_type(&mut self, water_type: WaterType) {
+        self.water_type = water_type;
+    }
+
+    pub fn settings(&self) -> DepthSensorSettings {
+        self.settings
+    }
+
+    /// Takes effect from the next reading
+    pub fn set_settings(&mut self, settings: DepthSensorSettings) {
+        self.settings = settings;
     }
 }
 
 impl Ms5837 {
     const CMD_RESET: u8 = 0x1e;
     const CMD_READ_PROM: u8 = 0xA0;
-    const CMD_CONVERT_D1_OSR1024: u8 = 0x44;
-    const CMD_CONVERT_D2_OSR1024: u8 = 0x54;
+    const CMD_CONVERT_D1: u8 = 0x40;
+    const CMD_CONVERT_D2: u8 = 0x50;
     const CMD_READ_ADC: u8 = 0x00;
 
     fn initialize(&mut self) -> anyhow::Result<()> {
@@ -79,10 +139,8 @@ impl Ms5837 {
             bail!("Got bad crc");
         }
 
-        // let version = (self.calibration[0] & 0x0FE0) >> 5;
-        // if version != 0x1A {
-        //     bail!("Got bad version, {version}, {}", self.calibration[0]);
-        // }
+        self.model = Ms5837Model::from_prom(self.calibration[0])?;
+        info!("Found {:?}", self.model);
 
         Ok(())
     }
@@ -90,83 +148,150 @@ impl Ms5837 {
     fn read_raw(&mut self) -> anyhow::Result<(u32, u32)> {
         let mut buffer = [0, 0, 0];
 
+        let oversampling = self.settings.oversampling;
+        let osr = oversampling_command(oversampling);
+        let conversion_time = oversampling.conversion_time();
+
         self.i2c
-            .write(&[Self::CMD_CONVERT_D1_OSR1024])
+            .write(&[Self::CMD_CONVERT_D1 | osr])
             .context("Begin d1 convert")?;
-        thread::sleep(Duration::from_millis(3));
+        thread::sleep(conversion_time);
 
         self.i2c
             .write(&[Self::CMD_READ_ADC])
             .context("Begin d1 read")?;
         self.i2c.read(&mut buffer).context("D1 read")?;
 
-        let d1 = (buffer[0] as u32) << 16 | (buffer[1] as u32) << 8 | buffer[0] as u32;
+        let d1 = (buffer[0] as u32) << 16 | (buffer[1] as u32) << 8 | buffer[2] as u32;
 
         self.i2c
-            .write(&[Self::CMD_CONVERT_D2_OSR1024])
+            .write(&[Self::CMD_CONVERT_D2 | osr])
             .context("Begin d2 convert")?;
-        thread::sleep(Duration::from_millis(3));
+        thread::sleep(conversion_time);
 
         self.i2c
             .write(&[Self::CMD_READ_ADC])
             .context("Begin d2 read")?;
         self.i2c.read(&mut buffer).context("D2 read")?;
 
-        let d2 = (buffer[0] as u32) << 16 | (buffer[1] as u32) << 8 | buffer[0] as u32;
+        let d2 = (buffer[0] as u32) << 16 | (buffer[1] as u32) << 8 | buffer[2] as u32;
 
         Ok((d1, d2))
     }
 }
 
-fn calculate_pressure_and_temperature(raw: (u32, u32), calibration: &[u16; 8]) -> (Mbar, Celsius) {
+/// Offset of the ratio from the base convert commands
+fn oversampling_command(oversampling: Oversampling) -> u8 {
+    match oversampling {
+        Oversampling::Osr256 => 0x0,
+        Oversampling::Osr512 => 0x2,
+        Oversampling::Osr1024 => 0x4,
+        Oversampling::Osr2048 => 0x6,
+        Oversampling::Osr4096 => 0x8,
+        Oversampling::Osr8192 => 0xA,
+    }
+}
+
+/// Converts raw `(D1, D2)` readings with the datasheet's first and second order compensation
+fn calculate_pressure_and_temperature(
+    model: Ms5837Model,
+    raw: (u32, u32),
+    calibration: &[u16; 8],
+) -> (Mbar, Celsius) {
+    let c = calibration.map(|it| it as i64);
+    let (d1, d2) = (raw.0 as i64, raw.1 as i64);
+
     // Calculate temperature
-    let dt = raw.1 as i32 - calibration[5] as i32 * 256;
-    let temp = 2000 + dt * calibration[6] as i32 / 8388608;
+    let dt = d2 - c[5] * 256;
+    let temp = 2000 + dt * c[6] / 8388608;
 
     // Calculate actual offset and sensitivity
-    let off = calibration[2] as i64 * 65536 + (calibration[4] as i64 * dt as i64) / 128;
-    let sens = calibration[1] as i64 * 32768 + (calibration[3] as i64 * dt as i64) / 256;
-
-    let t_i;
-    let mut off_i;
-    let mut sens_i;
+    let (off, sens) = match model {
+        Ms5837Model::Ms5837_30Ba => (
+            c[2] * 65536 + (c[4] * dt) / 128,
+            c[1] * 32768 + (c[3] * dt) / 256,
+        ),
+        Ms5837Model::Ms5837_02Ba => (
+            c[2] * 131072 + (c[4] * dt) / 64,
+            c[1] * 65536 + (c[3] * dt) / 128,
+        ),
+    };
 
     // Second order compensation
-    if temp / 100 < 20 {
-        // Low temp
-        t_i = 3 * dt as i64 * dt as i64 / 8589934592;
-        off_i = 3 * (temp as i64 - 2000) * (temp as i64 - 2000) / 2;
-        sens_i = 5 * (temp as i64 - 2000) * (temp as i64 - 2000) / 8;
-
-        if temp / 100 < -15 {
-            // Very low temp
-            off_i += 7 * (temp as i64 + 1500) * (temp as i64 + 1500);
-            sens_i += 4 * (temp as i64 + 1500) * (temp as i64 + 1500);
+    let low_temp = (temp - 2000) * (temp - 2000);
+    let (t_i, off_i, sens_i) = match model {
+        Ms5837Model::Ms5837_30Ba if temp < 2000 => {
+            let mut off_i = 3 * low_temp / 2;
+            let mut sens_i = 5 * low_temp / 8;
+
+            if temp < -1500 {
+                // Very low temp
+                off_i += 7 * (temp + 1500) * (temp + 1500);
+                sens_i += 4 * (temp + 1500) * (temp + 1500);
+            }
+
+            (3 * dt * dt / 8589934592, off_i, sens_i)
         }
-    } else {
-        // High temp
-        t_i = 2 * dt as i64 * dt as i64 / 137438953472;
-        off_i = (temp as i64 - 2000) * (temp as i64 - 2000) / 16;
-        sens_i = 0;
-    }
+        Ms5837Model::Ms5837_30Ba => (2 * dt * dt / 137438953472, low_temp / 16, 0),
+        Ms5837Model::Ms5837_02Ba if temp < 2000 => (
+            11 * dt * dt / 34359738368,
+            31 * low_temp / 8,
+            63 * low_temp / 32,
+        ),
+        Ms5837Model::Ms5837_02Ba => (0, 0, 0),
+    };
 
     // Calculate corrected offset and sensitivity
     let off = off - off_i;
     let sens = sens - sens_i;
 
-    // Calculate pressure and temperature
-    let pressure_raw = ((raw.0 as i64 * sens / 2097152) - off) / 8192;
-    let temperature_raw = temp - t_i as i32;
+    // Calculate pressure and temperature, the 30BA reports in 0.1 mbar and the 02BA in 0.01 mbar
+    let (pressure_divisor, pressure_scale) = match model {
+        Ms5837Model::Ms5837_30Ba => (8192, 10.0),
+        Ms5837Model::Ms5837_02Ba => (32768, 100.0),
+    };
+    let pressure_raw = (d1 * sens / 2097152 - off) / pressure_divisor;
+    let temperature_raw = temp - t_i;
 
     // Wrap in newtypes
-    let pressure = Mbar(pressure_raw as f64 / 10.0);
+    let pressure = Mbar(pressure_raw as f64 / pressure_scale);
     let temperature = Celsius(temperature_raw as f64 / 100.0);
 
     (pressure, temperature)
 }
 
-fn pressure_to_depth(pressure: Mbar, density: f64) -> Meters {
-    Meters((pressure.0 * 100.0 - 101300.0) / (density * 9.80665))
+pub fn pressure_to_depth(pressure: Mbar, surface_pressure: Mbar, density: f64) -> Meters {
+    Meters((pressure.0 - surface_pressure.0) * 100.0 / (density * 9.80665))
+}
+
+/// Practical salinity of typical seawater
+const SEAWATER_SALINITY: f64 = 35.0;
+
+/// Density in kg/m³ of water at the surface, where the sensor's temperature is the water's
+fn fluid_density(water_type: WaterType, temperature: Celsius) -> f64 {
+    match water_type {
+        WaterType::Fresh => water_density(temperature, 0.0),
+        WaterType::Salt => water_density(temperature, SEAWATER_SALINITY),
+        WaterType::Custom(density) => density,
+    }
+}
+
+/// The UNESCO 1981 equation of state for seawater at one atmosphere, valid from 0 to 40 °C and 0
+/// to 42 PSU
+fn water_density(temperature: Celsius, salinity: f64) -> f64 {
+    let t = temperature.0;
+    let s = salinity;
+
+    let pure = 999.842594 + 6.793952e-2 * t - 9.095290e-3 * t.powi(2) + 1.001685e-4 * t.powi(3)
+        - 1.120083e-6 * t.powi(4)
+        + 6.536332e-9 * t.powi(5);
+
+    let a = 8.24493e-1 - 4.0899e-3 * t + 7.6438e-5 * t.powi(2) - 8.2467e-7 * t.powi(3)
+        + 5.3875e-9 * t.powi(4);
+    let b = -5.72466e-3 + 1.0227e-4 * t - 1.6546e-6 * t.powi(2);
+    let c = 4.8314e-4;
+
+    pure + a * s + b * s.powf(1.5) + c * s.powi(2)
 }
 
 fn pressure_to_altitude(pressure: Mbar) -> Meters {
@@ -197,3 +322,77 @@ fn crc4(mut data: [u16; 8]) -> u8 {
 
     (n_rem >> 12) as u8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example coefficients and readings from the datasheets, the unused first and last words
+    // are left at zero
+    const PROM_30BA: [u16; 8] = [0, 34982, 36352, 20328, 22354, 26646, 26146, 0];
+    const RAW_30BA: (u32, u32) = (4958179, 6815414);
+    const PROM_02BA: [u16; 8] = [0, 46372, 43981, 29059, 27842, 31553, 28165, 0];
+    const RAW_02BA: (u32, u32) = (6465444, 8077636);
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn converts_30ba_reference_values() {
+        let (pressure, temperature) =
+            calculate_pressure_and_temperature(Ms5837Model::Ms5837_30Ba, RAW_30BA, &PROM_30BA);
+
+        // The datasheet gives 3999.8 mbar and 19.81 °C, its example rounds dT * C6 down where
+        // integer division truncates towards zero
+        assert_close(pressure.0, 3999.8, 0.05);
+        assert_close(temperature.0, 19.81, 0.015);
+    }
+
+    #[test]
+    fn converts_02ba_reference_values() {
+        let (pressure, temperature) =
+            calculate_pressure_and_temperature(Ms5837Model::Ms5837_02Ba, RAW_02BA, &PROM_02BA);
+
+        assert_close(pressure.0, 1100.02, 0.005);
+        assert_close(temperature.0, 20.00, 0.005);
+    }
+
+    #[test]
+    fn second_order_compensation_applies_below_20c() {
+        // Cooler reading of the 30BA example, the second order terms lower the temperature
+        let raw = (RAW_30BA.0, RAW_30BA.1 - 200_000);
+
+        let dt = raw.1 as i64 - PROM_30BA[5] as i64 * 256;
+        let first_order = (2000 + dt * PROM_30BA[6] as i64 / 8388608) as f64 / 100.0;
+
+        let (_, temperature) =
+            calculate_pressure_and_temperature(Ms5837Model::Ms5837_30Ba, raw, &PROM_30BA);
+        assert!(temperature.0 < first_order);
+    }
+
+    #[test]
+    fn detects_model_from_prom_version() {
+        assert_eq!(
+            Ms5837Model::from_prom(0x1A << 5).unwrap(),
+            Ms5837Model::Ms5837_30Ba
+        );
+        assert_eq!(
+            Ms5837Model::from_prom(0x00 << 5).unwrap(),
+            Ms5837Model::Ms5837_02Ba
+        );
+        assert_eq!(
+            Ms5837Model::from_prom(0x15 << 5 | 0xF000).unwrap(),
+            Ms5837Model::Ms5837_02Ba
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let err = Ms5837Model::from_prom(0x07 << 5).unwrap_err();
+        assert!(err.to_string().contains("0x07"), "{err}");
+    }
+}
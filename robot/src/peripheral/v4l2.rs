@@ -0,0 +1,113 @@
+use std::{fs::File, os::fd::AsRawFd, path::Path};
+
+use anyhow::Context;
+use nix::{errno::Errno, ioctl_read, ioctl_readwrite};
+
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x0000_0001;
+/// Set when `device_caps` is filled in, otherwise `capabilities` describes the node
+const V4L2_CAP_DEVICE_CAPS: u32 = 0x8000_0000;
+const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
+
+pub const PIX_FMT_H264: FourCC = FourCC(*b"H264");
+
+#[repr(C)]
+#[derive(Default)]
+struct V4l2Capability {
+    driver: [u8; 16],
+    card: [u8; 32],
+    bus_info: [u8; 32],
+    version: u32,
+    capabilities: u32,
+    device_caps: u32,
+    reserved: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct V4l2FmtDesc {
+    index: u32,
+    kind: u32,
+    flags: u32,
+    description: [u8; 32],
+    pixelformat: u32,
+    mbus_code: u32,
+    reserved: [u32; 3],
+}
+
+ioctl_read!(vidioc_querycap, b'V', 0, V4l2Capability);
+ioctl_readwrite!(vidioc_enum_fmt, b'V', 2, V4l2FmtDesc);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCC(pub [u8; 4]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub driver: String,
+    pub card: String,
+    pub bus_info: String,
+    pub video_capture: bool,
+}
+
+/// Handle to a V4L2 device node such as `/dev/video0`
+pub struct V4l2Device {
+    file: File,
+}
+
+impl V4l2Device {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Open {}", path.display()))?;
+
+        Ok(Self { file })
+    }
+
+    pub fn capabilities(&self) -> anyhow::Result<Capabilities> {
+        let mut raw = V4l2Capability::default();
+        // Safety: `raw` matches the layout of `struct v4l2_capability`
+        unsafe { vidioc_querycap(self.file.as_raw_fd(), &mut raw) }.context("Query caps")?;
+
+        let caps = if raw.capabilities & V4L2_CAP_DEVICE_CAPS != 0 {
+            raw.device_caps
+        } else {
+            raw.capabilities
+        };
+
+        Ok(Capabilities {
+            driver: c_string(&raw.driver),
+            card: c_string(&raw.card),
+            bus_info: c_string(&raw.bus_info),
+            video_capture: caps & V4L2_CAP_VIDEO_CAPTURE != 0,
+        })
+    }
+
+    /// Pixel formats the node can capture in
+    pub fn capture_formats(&self) -> anyhow::Result<Vec<FourCC>> {
+        let mut formats = Vec::new();
+
+        for index in 0.. {
+            let mut raw = V4l2FmtDesc {
+                index,
+                kind: V4L2_BUF_TYPE_VIDEO_CAPTURE,
+                ..Default::default()
+            };
+
+            // Safety: `raw` matches the layout of `struct v4l2_fmtdesc`
+            match unsafe { vidioc_enum_fmt(self.file.as_raw_fd(), &mut raw) } {
+                Ok(_) => formats.push(FourCC(raw.pixelformat.to_le_bytes())),
+                // Past the last format
+                Err(Errno::EINVAL) => break,
+                Err(err) => return Err(err).context("Enumerate formats"),
+            }
+        }
+
+        Ok(formats)
+    }
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|it| *it == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
@@ -1,19 +1,33 @@
+pub mod arming;
+pub mod blackbox;
 pub mod cameras;
+pub mod clock;
+pub mod debounce;
 pub mod depth;
 pub mod depth_control;
+pub mod enclosure;
 pub mod error;
+pub mod esc_calibration;
+pub mod estop;
 pub mod hw_stat;
 pub mod indicators;
 pub mod inertial;
 pub mod leak;
 pub mod leveling;
+pub mod log_forward;
 pub mod logging;
 pub mod motor;
 pub mod networking;
 pub mod orientation;
+pub mod power;
 pub mod robot;
+pub mod scheduler;
+pub mod seal_test;
+pub mod sensor_health;
+pub mod servo;
 pub mod status;
 pub mod stop;
+pub mod thruster_test;
 
 use std::{
     any,
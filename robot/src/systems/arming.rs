@@ -0,0 +1,659 @@
+use std::{
+    sync::Arc,
+    thread::Scope,
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::{
+    protocol::Protocol,
+    store::{tokens, Store, UpdateCallback},
+    types::{
+        Armed, ArmingConfig, ArmingState, Dps, GForce, LeakResponse, LogLevel, Mbar, Meters,
+        Percent, PreArmFailure, SealTestState,
+    },
+};
+use crossbeam::channel::select;
+use tracing::{info, span, warn, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{
+        clock, estop,
+        scheduler::{self, Tick},
+    },
+    SystemId,
+};
+
+use super::{motor, System};
+
+pub const ARMING_CONFIG: ArmingConfig = ArmingConfig {
+    check_leak: true,
+    check_sensor_age: true,
+    check_sensor_range: true,
+    check_latency: true,
+    check_pilot_input: true,
+    check_seal: true,
+
+    max_sensor_age: Duration::from_millis(250),
+    max_latency: Duration::from_millis(200),
+    max_pilot_input: Percent::new(0.05),
+
+    accel_range: (GForce(0.8), GForce(1.2)),
+    max_gyro: Dps(20.0),
+    depth_range: (Meters(-1.0), Meters(30.0)),
+    pressure_range: (Mbar(800.0), Mbar(4000.0)),
+};
+const PERIOD: Duration = Duration::from_millis(100);
+const PING_DIVISOR: usize = 5;
+const MAX_LATENCY_AGE: Duration = Duration::from_secs(2);
+
+/// Gates arm requests from the surface behind the pre-arm checklist
+pub struct ArmingSystem;
+
+impl System for ArmingSystem {
+    const ID: SystemId = SystemId::Arming;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listner = events.take_listner().unwrap();
+
+        let ticks = scheduler::schedule("Arming", PERIOD);
+        let clock = clock::clock();
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Arming thread");
+
+            let mut store = {
+                let mut events = events.clone();
+                Store::with_clock(
+                    move |update| {
+                        events.send(Event::Store(update));
+                    },
+                    clock.clone(),
+                )
+            };
+
+            let mut latency: Option<(Duration, Instant)> = None;
+            let mut arm_requested = false;
+            let mut tick_counter = 0;
+
+            store.insert(&tokens::ARMING_STATE, ArmingState::Disarmed);
+
+            loop {
+                let event = select! {
+                    recv(listner) -> event => event.map(ArmingEvent::Event),
+                    recv(ticks) -> tick => tick.map(ArmingEvent::Tick),
+                };
+                let Ok(event) = event else {
+                    return;
+                };
+
+                match event {
+                    ArmingEvent::Event(event) => match &*event {
+                        Event::SyncStore => {
+                            store.refresh();
+                        }
+                        Event::ResetForignStore => {
+                            store.reset_shared();
+                        }
+                        Event::Store(update) => {
+                            store.handle_update_shared(update);
+                        }
+                        Event::PacketRx(Protocol::Pong(ping, _)) => {
+                            if let Ok(round_trip) = SystemTime::now().duration_since(*ping) {
+                                latency = Some((round_trip, clock.now()));
+                            }
+                        }
+                        Event::Exit => {
+                            return;
+                        }
+                        _ => {}
+                    },
+                    ArmingEvent::Tick(_tick) => {
+                        if tick_counter % PING_DIVISOR == 0 {
+                            events.send(Event::PacketTx(Protocol::Ping(SystemTime::now())));
+                        }
+                        tick_counter += 1;
+
+                        let emergency_stopped = estop::emergency_stopped();
+                        let published = store.get(&tokens::EMERGENCY_STOP).map(|it| *it);
+                        if published != Some(emergency_stopped) {
+                            store.insert(&tokens::EMERGENCY_STOP, emergency_stopped);
+                        }
+
+                        // The arm request has to be made again once the latch is reset
+                        if emergency_stopped {
+                            let armed = store
+                                .get(&tokens::ARMING_STATE)
+                                .map(|it| it.is_armed())
+                                .unwrap_or(false);
+                            if armed {
+                                warn!("Disarmed by emergency stop");
+                                store.insert(&tokens::ARMING_STATE, ArmingState::Disarmed);
+                            }
+                        }
+
+                        let armed = store
+                            .get(&tokens::ARMING_STATE)
+                            .map(|it| it.is_armed())
+                            .unwrap_or(false);
+                        let leak_response = store
+                            .get(&tokens::LEAK_RESPONSE)
+                            .map(|it| *it)
+                            .unwrap_or_default();
+                        let leaking = store.get(&tokens::LEAK).map(|it| *it).unwrap_or(false);
+                        if armed && leaking && leak_response == LeakResponse::Disarm {
+                            warn!("Disarmed by leak");
+                            store.insert(&tokens::ARMING_STATE, ArmingState::Disarmed);
+                        }
+
+                        let requested = wants_armed(&store, armed);
+
+                        if !requested {
+                            // A refusal stays visible until the next arm request
+                            let armed = store
+                                .get(&tokens::ARMING_STATE)
+                                .map(|it| it.is_armed())
+                                .unwrap_or(false);
+                            if armed {
+                                info!("Disarmed");
+                                store.insert(&tokens::ARMING_STATE, ArmingState::Disarmed);
+                            }
+
+                            arm_requested = false;
+                            continue;
+                        }
+
+                        // Only evaluate the checklist on the rising edge of an arm request
+                        if arm_requested {
+                            continue;
+                        }
+                        arm_requested = true;
+
+                        let config = store
+                            .get(&tokens::ARMING_CONFIG_OVERRIDE)
+                            .map(|it| *it)
+                            .unwrap_or(ARMING_CONFIG);
+                        let force = store
+                            .get(&tokens::ARMING_FORCE)
+                            .map(|it| *it)
+                            .unwrap_or(false);
+                        let latency = latency
+                            .filter(|(_, received)| {
+                                clock.now().saturating_duration_since(*received) < MAX_LATENCY_AGE
+                            })
+                            .map(|(latency, _)| latency);
+
+                        let failures = run_checklist(&store, &config, latency);
+                        let state = arming_decision(failures, force);
+
+                        match &state {
+                            ArmingState::Armed => {
+                                info!("Armed, all pre-arm checks passed");
+                            }
+                            ArmingState::ForceArmed(overridden) => {
+                                let message = format!(
+                                    "Force armed, overriding: {}",
+                                    PreArmFailure::describe_all(overridden)
+                                );
+                                warn!("{message}");
+                                events
+                                    .send(Event::PacketTx(Protocol::Log(LogLevel::Warn, message)));
+                            }
+                            ArmingState::Refused(failed) => {
+                                warn!("Arming refused: {}", PreArmFailure::describe_all(failed));
+                            }
+                            ArmingState::Disarmed => unreachable!(),
+                        }
+
+                        store.insert(&tokens::ARMING_STATE, state);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+enum ArmingEvent {
+    Event(Arc<Event>),
+    Tick(Tick),
+}
+
+/// Whether the surface wants the robot armed
+/// A robot that is already armed stays armed through link loss while it ascends from a leak
+pub fn wants_armed<C: UpdateCallback>(store: &Store<C>, armed: bool) -> bool {
+    match store.get_alive(&tokens::ARMED, motor::MAX_UPDATE_AGE) {
+        Some(requested) => *requested == Armed::Armed,
+        None => {
+            armed
+                && store
+                    .get_alive(&tokens::MOVEMENT_LEAK, motor::MAX_UPDATE_AGE)
+                    .is_some()
+        }
+    }
+}
+
+/// Runs every enabled pre-arm check, returns the checks that failed
+pub fn run_checklist<C: UpdateCallback>(
+    store: &Store<C>,
+    config: &ArmingConfig,
+    latency: Option<Duration>,
+) -> Vec<PreArmFailure> {
+    let mut failures = Vec::new();
+
+    // Not configurable, the latch has to be reset explicitly
+    if store
+        .get(&tokens::EMERGENCY_STOP)
+        .map(|it| *it)
+        .unwrap_or(false)
+    {
+        failures.push(PreArmFailure::EmergencyStopped);
+    }
+
+    if config.check_leak {
+        match store.get(&tokens::LEAK) {
+            Some(leak) if *leak => failures.push(PreArmFailure::LeakDetected),
+            Some(_) => {}
+            None => failures.push(PreArmFailure::NoLeakData),
+        }
+    }
+
+    let inertial = store.get_alive(&tokens::RAW_INERTIAL, config.max_sensor_age);
+    let depth = store.get_alive(&tokens::RAW_DEPTH, config.max_sensor_age);
+
+    if config.check_sensor_age {
+        if inertial.is_none() {
+            failures.push(PreArmFailure::StaleInertial);
+        }
+        if depth.is_none() {
+            failures.push(PreArmFailure::StaleDepth);
+        }
+    }
+
+    if config.check_sensor_range {
+        if let Some(ref inertial) = inertial {
+            let accel = GForce(
+                (inertial.accel_x.0.powi(2)
+                    + inertial.accel_y.0.powi(2)
+                    + inertial.accel_z.0.powi(2))
+                .sqrt(),
+            );
+            let gyro = Dps(inertial
+                .gyro_x
+                .0
+                .abs()
+                .max(inertial.gyro_y.0.abs())
+                .max(inertial.gyro_z.0.abs()));
+
+            let (min_accel, max_accel) = config.accel_range;
+            if accel < min_accel || accel > max_accel || gyro > config.max_gyro {
+                failures.push(PreArmFailure::InertialOutOfRange { accel, gyro });
+            }
+        }
+
+        if let Some(ref depth) = depth {
+            let (min_depth, max_depth) = config.depth_range;
+            let (min_pressure, max_pressure) = config.pressure_range;
+            if depth.depth < min_depth
+                || depth.depth > max_depth
+                || depth.pressure < min_pressure
+                || depth.pressure > max_pressure
+            {
+                failures.push(PreArmFailure::DepthOutOfRange {
+                    depth: depth.depth,
+                    pressure: depth.pressure,
+                });
+            }
+        }
+    }
+
+    if config.check_latency {
+        match latency {
+            Some(latency) if latency > config.max_latency => {
+                failures.push(PreArmFailure::LatencyTooHigh(latency))
+            }
+            Some(_) => {}
+            None => failures.push(PreArmFailure::NoLatencyData),
+        }
+    }
+
+    if config.check_pilot_input {
+        // Motors ignore a joystick that has gone stale, so it cannot move the robot once armed
+        if let Some(joystick) = store.get_alive(&tokens::MOVEMENT_JOYSTICK, motor::MAX_UPDATE_AGE) {
+            let max_input = [
+                joystick.x,
+                joystick.y,
+                joystick.z,
+                joystick.x_rot,
+                joystick.y_rot,
+                joystick.z_rot,
+            ]
+            .into_iter()
+            .map(|it| it.get().abs())
+            .max_by(f64::total_cmp)
+            .unwrap_or(0.0);
+
+            if max_input > config.max_pilot_input.get() {
+                failures.push(PreArmFailure::PilotInput(Percent::new(max_input)));
+            }
+        }
+    }
+
+    if config.check_seal {
+        if let Some(report) = store.get(&tokens::SEAL_TEST_REPORT) {
+            match report.state {
+                SealTestState::Settling | SealTestState::Measuring => {
+                    failures.push(PreArmFailure::SealTestRunning)
+                }
+                SealTestState::Failed => {
+                    failures.push(PreArmFailure::SealTestFailed(report.rise_rate))
+                }
+                SealTestState::Passed | SealTestState::Aborted(_) => {}
+            }
+        }
+    }
+
+    failures
+}
+
+/// Decides the arming state from the failed checks and the force flag
+pub fn arming_decision(failures: Vec<PreArmFailure>, force: bool) -> ArmingState {
+    if failures.is_empty() {
+        ArmingState::Armed
+    } else if force && failures.iter().all(PreArmFailure::overridable) {
+        ArmingState::ForceArmed(failures)
+    } else {
+        ArmingState::Refused(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::{
+        clock::ManualClock,
+        store::{create_update, Update},
+        types::{Celsius, DepthFrame, InertialFrame, Movement, SealTestReport, WaterType},
+    };
+
+    use super::*;
+
+    const LATENCY: Option<Duration> = Some(Duration::from_millis(50));
+
+    fn inertial(accel_z: f64, gyro_x: f64) -> Update {
+        create_update(
+            &tokens::RAW_INERTIAL,
+            InertialFrame {
+                gyro_x: Dps(gyro_x),
+                gyro_y: Dps(0.0),
+                gyro_z: Dps(0.0),
+                accel_x: GForce(0.0),
+                accel_y: GForce(0.0),
+                accel_z: GForce(accel_z),
+                tempature: Celsius(25.0),
+            },
+        )
+    }
+
+    fn depth(depth: f64) -> Update {
+        create_update(
+            &tokens::RAW_DEPTH,
+            DepthFrame {
+                sampled: SystemTime::now(),
+                depth: Meters(depth),
+                altitude: Meters(-depth),
+                pressure: Mbar(1013.25 + depth * 97.8),
+                temperature: Celsius(15.0),
+                surface_pressure: Mbar(1013.25),
+                fluid_density: 997.0,
+                water_type: WaterType::Fresh,
+            },
+        )
+    }
+
+    fn seal_test(state: SealTestState) -> Update {
+        create_update(
+            &tokens::SEAL_TEST_REPORT,
+            SealTestReport {
+                state,
+                start_pressure: Mbar(900.0),
+                pressure: Mbar(905.0),
+                rise_rate: Mbar(2.5),
+                remaining: Duration::ZERO,
+            },
+        )
+    }
+
+    fn joystick(z: f64) -> Update {
+        create_update(
+            &tokens::MOVEMENT_JOYSTICK,
+            Movement {
+                z: Percent::new(z),
+                ..Movement::default()
+            },
+        )
+    }
+
+    /// A store every check passes on
+    fn ready(clock: &Arc<ManualClock>) -> Store<()> {
+        let mut store = Store::with_clock((), clock.clone());
+
+        store.handle_update_shared(&create_update(&tokens::EMERGENCY_STOP, false));
+        store.handle_update_shared(&create_update(&tokens::LEAK, false));
+        store.handle_update_shared(&inertial(1.0, 0.0));
+        store.handle_update_shared(&depth(1.0));
+        store.handle_update_shared(&joystick(0.0));
+        store.handle_update_shared(&seal_test(SealTestState::Passed));
+
+        store
+    }
+
+    /// The failures with `update` applied to a ready robot
+    fn failures_with(update: Update, latency: Option<Duration>) -> Vec<PreArmFailure> {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = ready(&clock);
+        store.handle_update_shared(&update);
+
+        run_checklist(&store, &ARMING_CONFIG, latency)
+    }
+
+    #[test]
+    fn ready_robot_arms() {
+        let clock = Arc::new(ManualClock::new());
+        let store = ready(&clock);
+
+        let failures = run_checklist(&store, &ARMING_CONFIG, LATENCY);
+        assert_eq!(failures, vec![]);
+        assert_eq!(arming_decision(failures, false), ArmingState::Armed);
+    }
+
+    #[test]
+    fn each_check_reports_its_failure() {
+        let cases = [
+            (
+                create_update(&tokens::EMERGENCY_STOP, true),
+                PreArmFailure::EmergencyStopped,
+            ),
+            (
+                create_update(&tokens::LEAK, true),
+                PreArmFailure::LeakDetected,
+            ),
+            ((tokens::LEAK.0, None), PreArmFailure::NoLeakData),
+            ((tokens::RAW_INERTIAL.0, None), PreArmFailure::StaleInertial),
+            ((tokens::RAW_DEPTH.0, None), PreArmFailure::StaleDepth),
+            (
+                inertial(1.5, 0.0),
+                PreArmFailure::InertialOutOfRange {
+                    accel: GForce(1.5),
+                    gyro: Dps(0.0),
+                },
+            ),
+            (
+                inertial(1.0, -45.0),
+                PreArmFailure::InertialOutOfRange {
+                    accel: GForce(1.0),
+                    gyro: Dps(45.0),
+                },
+            ),
+            (
+                depth(40.0),
+                PreArmFailure::DepthOutOfRange {
+                    depth: Meters(40.0),
+                    pressure: Mbar(1013.25 + 40.0 * 97.8),
+                },
+            ),
+            (joystick(-0.5), PreArmFailure::PilotInput(Percent::new(0.5))),
+            (
+                seal_test(SealTestState::Measuring),
+                PreArmFailure::SealTestRunning,
+            ),
+            (
+                seal_test(SealTestState::Failed),
+                PreArmFailure::SealTestFailed(Mbar(2.5)),
+            ),
+        ];
+
+        for (update, expected) in cases {
+            let key = update.0.clone();
+            assert_eq!(
+                failures_with(update, LATENCY),
+                vec![expected],
+                "update to {key:?}"
+            );
+        }
+
+        let clock = Arc::new(ManualClock::new());
+        let store = ready(&clock);
+        assert_eq!(
+            run_checklist(&store, &ARMING_CONFIG, None),
+            vec![PreArmFailure::NoLatencyData]
+        );
+        assert_eq!(
+            run_checklist(&store, &ARMING_CONFIG, Some(Duration::from_millis(500))),
+            vec![PreArmFailure::LatencyTooHigh(Duration::from_millis(500))]
+        );
+    }
+
+    #[test]
+    fn disabled_checks_are_skipped() {
+        let config = ArmingConfig {
+            check_leak: false,
+            check_sensor_age: false,
+            check_sensor_range: false,
+            check_latency: false,
+            check_pilot_input: false,
+            check_seal: false,
+            ..ARMING_CONFIG
+        };
+
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+        store.handle_update_shared(&create_update(&tokens::LEAK, true));
+        store.handle_update_shared(&inertial(3.0, 90.0));
+        store.handle_update_shared(&joystick(1.0));
+        store.handle_update_shared(&seal_test(SealTestState::Failed));
+        assert_eq!(run_checklist(&store, &config, None), vec![]);
+
+        // The emergency stop cannot be turned off
+        store.handle_update_shared(&create_update(&tokens::EMERGENCY_STOP, true));
+        assert_eq!(
+            run_checklist(&store, &config, None),
+            vec![PreArmFailure::EmergencyStopped]
+        );
+    }
+
+    #[test]
+    fn stale_pilot_input_is_ignored() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = ready(&clock);
+        store.handle_update_shared(&joystick(0.5));
+
+        clock.advance(motor::MAX_UPDATE_AGE);
+        store.handle_update_shared(&inertial(1.0, 0.0));
+        store.handle_update_shared(&depth(1.0));
+
+        assert_eq!(run_checklist(&store, &ARMING_CONFIG, LATENCY), vec![]);
+    }
+
+    #[test]
+    fn force_overrides_only_overridable_failures() {
+        let overridable = [
+            (tokens::LEAK.0, None),
+            (tokens::RAW_INERTIAL.0, None),
+            depth(40.0),
+            seal_test(SealTestState::Failed),
+        ];
+        for update in overridable {
+            let failures = failures_with(update, None);
+            assert!(matches!(
+                arming_decision(failures.clone(), true),
+                ArmingState::ForceArmed(overridden) if overridden == failures
+            ));
+            assert!(matches!(
+                arming_decision(failures, false),
+                ArmingState::Refused(_)
+            ));
+        }
+
+        let blocking = [
+            (
+                create_update(&tokens::LEAK, true),
+                PreArmFailure::LeakDetected,
+            ),
+            (joystick(0.5), PreArmFailure::PilotInput(Percent::new(0.5))),
+            (
+                create_update(&tokens::EMERGENCY_STOP, true),
+                PreArmFailure::EmergencyStopped,
+            ),
+        ];
+        for (update, blocker) in blocking {
+            // Alongside failures that could be overridden on their own
+            let failures = failures_with(update, None);
+            assert!(failures.contains(&blocker));
+            assert!(failures.contains(&PreArmFailure::NoLatencyData));
+
+            assert_eq!(
+                arming_decision(failures.clone(), true),
+                ArmingState::Refused(failures)
+            );
+        }
+    }
+
+    #[test]
+    fn arming_follows_the_surface() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        assert!(!wants_armed(&store, false));
+
+        store.handle_update_shared(&create_update(&tokens::ARMED, Armed::Armed));
+        assert!(wants_armed(&store, false));
+
+        clock.advance(motor::MAX_UPDATE_AGE * 2);
+        assert!(!wants_armed(&store, true));
+    }
+
+    #[test]
+    fn leak_ascent_holds_arming_through_link_loss() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        store.handle_update_shared(&create_update(&tokens::ARMED, Armed::Armed));
+        clock.advance(motor::MAX_UPDATE_AGE * 2);
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_LEAK, Movement::default()));
+
+        assert!(wants_armed(&store, true));
+        // A leak never arms the robot by itself
+        assert!(!wants_armed(&store, false));
+
+        // The surface can still disarm
+        store.handle_update_shared(&create_update(&tokens::ARMED, Armed::Disarmed));
+        assert!(!wants_armed(&store, true));
+    }
+}
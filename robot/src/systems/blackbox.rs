@@ -0,0 +1,178 @@
+use std::{
+    path::Path,
+    thread::Scope,
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::{
+    blackbox::{BlackBoxWriter, Entry, Record, WriterConfig},
+    error::LogErrorExt,
+    protocol::Protocol,
+    store::tokens,
+};
+use crossbeam::channel::{bounded, RecvTimeoutError};
+use tracing::{span, warn, Level};
+
+use crate::{
+    event::{Event, Sample},
+    events::EventHandle,
+    systems::{clock, stop, System},
+    SystemId,
+};
+
+pub const BLACKBOX_DIR: &str = "/home/pi/mate/blackbox";
+/// Buffered records are handed to the os this often
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// And forced to disk this often
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+const BUFFER: usize = 2000;
+
+/// Records store updates, sensor frames, network traffic and errors to disk
+///
+/// Recordings can be fed back through the control systems with the `replay` binary
+pub struct BlackBoxSystem;
+
+impl System for BlackBoxSystem {
+    const ID: SystemId = SystemId::BlackBox;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listner = events.take_listner().unwrap();
+
+        let mut writer = BlackBoxWriter::open(Path::new(BLACKBOX_DIR), WriterConfig::default())?;
+
+        // Writing happens on its own thread so disk stalls cant back up the event channel
+        let (tx, rx) = bounded(BUFFER);
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Black box event filterer");
+
+            let adapters = tokens::generate_adaptors();
+            let clock = clock::clock();
+            let mut dropped = 0;
+
+            for event in listner {
+                let entry = match &*event {
+                    Event::Store((key, data)) => {
+                        let data = match data {
+                            Some(data) => {
+                                let Some(data) =
+                                    adapters.get(key).and_then(|it| it.serialize(&**data))
+                                else {
+                                    warn!("Could not serialize {key:?} for the black box");
+                                    continue;
+                                };
+
+                                Some(data)
+                            }
+                            None => None,
+                        };
+
+                        Entry::Store(key.to_string(), data)
+                    }
+                    Event::SensorFrame(batch) => {
+                        let now = clock.now();
+
+                        Entry::Sensor {
+                            inertial: ages(&batch.inertial, now),
+                            mag: ages(&batch.mag, now),
+                            secondary_mag: ages(&batch.secondary_mag, now),
+                        }
+                    }
+                    // Store traffic is recorded as `Event::Store` on both sides
+                    Event::PacketTx(Protocol::Store(..)) | Event::PacketRx(Protocol::Store(..)) => {
+                        continue;
+                    }
+                    // Forwarded logs would record everything twice
+                    Event::PacketTx(Protocol::LogRecord(_)) => continue,
+                    Event::PacketTx(packet) => Entry::PacketTx(packet.clone()),
+                    Event::PacketRx(packet) => Entry::PacketRx(packet.clone()),
+                    Event::Error(err) => Entry::Error(format!("{err:?}")),
+                    Event::Exit => return,
+                    _ => continue,
+                };
+
+                let record = Record {
+                    time: SystemTime::now(),
+                    entry,
+                };
+
+                if tx.try_send(record).is_err() {
+                    dropped += 1;
+                } else if dropped > 0 {
+                    let record = Record {
+                        time: SystemTime::now(),
+                        entry: Entry::Error(format!("Black box dropped {dropped} records")),
+                    };
+                    if tx.try_send(record).is_ok() {
+                        dropped = 0;
+                    }
+                }
+            }
+        });
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Black box writer");
+
+            let mut last_flush = Instant::now();
+            let mut last_sync = Instant::now();
+            let mut failed = false;
+
+            loop {
+                match rx.recv_timeout(FLUSH_INTERVAL) {
+                    Ok(record) => {
+                        let errored = matches!(record.entry, Entry::Error(_));
+
+                        match writer.write(&record) {
+                            Ok(()) => failed = false,
+                            Err(err) => {
+                                // Reported once so a full disk does not flood the error channel
+                                if !failed {
+                                    events.send(Event::Error(err.context("Write black box")));
+                                    failed = true;
+                                }
+                            }
+                        }
+
+                        // Errors are often followed by a crash
+                        if errored {
+                            writer.sync().log_error("Sync black box");
+                            last_sync = Instant::now();
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => {
+                        writer.sync().log_error("Sync black box");
+                        return;
+                    }
+                }
+
+                if last_sync.elapsed() >= SYNC_INTERVAL {
+                    writer.sync().log_error("Sync black box");
+                    last_sync = Instant::now();
+                    last_flush = last_sync;
+                } else if last_flush.elapsed() >= FLUSH_INTERVAL {
+                    writer.flush().log_error("Flush black box");
+                    last_flush = Instant::now();
+                }
+
+                if stop::world_stopped() {
+                    writer.sync().log_error("Sync black box");
+                    return;
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Converts sample times to how long before the record they were taken
+fn ages<T: Copy>(samples: &[Sample<T>], now: Instant) -> Vec<(Duration, T)> {
+    samples
+        .iter()
+        .map(|sample| (now.saturating_duration_since(sample.time), sample.frame))
+        .collect()
+}
@@ -1,22 +1,27 @@
-use core::str;
 use std::{
-    borrow::ToOwned,
-    io,
-    net::{IpAddr, SocketAddr},
-    process::{Child, Command},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    sync::Arc,
     thread::{self, Scope},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, bail, Context, Error};
+use anyhow::{anyhow, Context, Error};
 use common::{
     error::LogErrorExt,
     store::{self, tokens},
-    types::Camera,
+    types::{Camera, CameraHealth, CameraSettings, CameraStreamState, Recording},
 };
-use crossbeam::channel::bounded;
+use crossbeam::channel::{bounded, Sender};
 use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
-use tracing::{error, info, span, Level};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, span, warn, Level};
 
 use crate::{
     event::Event,
@@ -25,6 +30,33 @@ use crate::{
     SystemId,
 };
 
+use self::{
+    discovery::{CameraDevice, V4l2Probe, DEV, SYSFS_VIDEO4LINUX},
+    pipeline::{Destination, Pipeline},
+    recording::{MIN_FREE_SPACE, RECORDING_DIR},
+};
+
+pub mod discovery;
+pub mod pipeline;
+pub mod recording;
+
+/// Longest the hotplug watcher waits for device changes before checking if the robot is stopping
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// Time for udev to finish setting up a new device node before it is probed
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+/// Camera ports are picked from `FIRST_PORT..FIRST_PORT + PORT_COUNT`
+const FIRST_PORT: u16 = 5600;
+const PORT_COUNT: u16 = 400;
+const PORTS_FILE: &str = "camera_ports.csv";
+/// Shared by all multicast streams, they are told apart by port
+const MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 56, 0);
+/// How often running pipelines are checked on
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(1);
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// A pipeline that ran this long before failing is restarted without waiting long
+const STABLE_TIME: Duration = Duration::from_secs(60);
+
 /// Handles camera detection, starting and stopping gstreamer, and notifying the suface about
 /// available cameras
 pub struct CameraSystem;
@@ -40,158 +72,161 @@ impl System for CameraSystem {
 
         let (tx, rx) = bounded(30);
 
-        spawner.spawn(move || {
-            span!(Level::INFO, "Event filterer");
+        {
+            let tx = tx.clone();
+            spawner.spawn(move || {
+                span!(Level::INFO, "Event filterer");
 
-            for event in listner {
-                if stop::world_stopped() | matches!(&*event, Event::Exit) {
-                    tx.try_send(Event::Exit.into())
-                        .log_error("Forward exit to camera manager");
+                for event in listner {
+                    if stop::world_stopped() | matches!(&*event, Event::Exit) {
+                        tx.try_send(CameraEvent::Exit)
+                            .log_error("Forward exit to camera manager");
 
-                    return;
-                }
+                        return;
+                    }
 
-                match &*event {
-                    Event::PeerConnected(_) | Event::SyncStore => {
-                        tx.try_send(event)
-                            .log_error("Forward event to camera manager");
+                    match &*event {
+                        Event::CameraSubscriptions(..) | Event::PeerDisconnected(_) => {
+                            tx.try_send(CameraEvent::Event(event))
+                                .log_error("Forward event to camera manager");
+                        }
+                        Event::Store(update)
+                            if update.0 == tokens::CAMERA_SETTINGS.0
+                                || update.0 == tokens::CAMERA_RECORD.0 =>
+                        {
+                            tx.try_send(CameraEvent::Event(event))
+                                .log_error("Forward event to camera manager");
+                        }
+                        _ => {}
                     }
-                    _ => {}
                 }
-            }
-        });
+            });
+        }
+
+        {
+            let tx = tx.clone();
+            spawner.spawn(move || {
+                span!(Level::INFO, "Camera supervise thread");
+
+                while !stop::world_stopped() {
+                    tx.try_send(CameraEvent::Supervise)
+                        .log_error("Send supervise");
+                    thread::sleep(SUPERVISE_INTERVAL);
+                }
+            });
+        }
+
+        {
+            let mut events = events.clone();
+            spawner.spawn(move || {
+                span!(Level::INFO, "Camera hotplug watcher");
+
+                if let Err(err) = watch_cameras(tx) {
+                    events.send(Event::Error(err.context("Watch for cameras")));
+                }
+            });
+        }
 
         spawner.spawn(move || {
             span!(Level::INFO, "Camera manager");
 
-            let mut last_cameras: HashSet<String> = HashSet::default();
-            let mut cameras: HashMap<String, (Child, SocketAddr)> = HashMap::default();
-            let mut target_ip = None;
-            let mut port = 1024u16;
+            let mut state = CameraState::default();
+            match read_camera_ports(Path::new(PORTS_FILE)) {
+                Ok(ports) => state.ports = ports,
+                Err(err) => events.send(Event::Error(err.context("Read camera ports"))),
+            }
 
             for event in rx {
-                match &*event {
-                    // Respawns all instances of gstreamer and points the new ones towards the new peer
-                    Event::PeerConnected(addrs) => {
-                        target_ip = Some(addrs.ip());
-
-                        for (camera, (mut child, _)) in cameras.drain() {
-                            let rst = child.kill();
-
-                            if let Err(err) = rst {
-                                events.send(Event::Error(
-                                    Error::new(err).context(format!("Kill gstreamer for {camera}")),
-                                ));
+                match event {
+                    CameraEvent::Event(event) => match &*event {
+                        // Restarts the affected pipelines with the peer added or removed
+                        Event::CameraSubscriptions(addrs, cameras) => {
+                            info!("{addrs} subscribed to {cameras:?}");
+
+                            state
+                                .subscribers
+                                .insert(*addrs, cameras.iter().cloned().collect());
+                            state.reconcile(&mut events);
+                        }
+                        Event::PeerDisconnected(Some(addrs)) => {
+                            if state.subscribers.remove(addrs).is_some() {
+                                state.reconcile(&mut events);
                             }
                         }
+                        // Restarts only the pipelines whose settings changed, recordings are
+                        // started and stopped without touching the stream
+                        Event::Store(update) => {
+                            if let Some(settings) =
+                                store::handle_update(&tokens::CAMERA_SETTINGS, update)
+                            {
+                                state.settings = (*settings).clone();
+                            } else if update.0 == tokens::CAMERA_SETTINGS.0 {
+                                state.settings.clear();
+                            } else if let Some(record) =
+                                store::handle_update(&tokens::CAMERA_RECORD, update)
+                            {
+                                state.record = (*record).clone();
+                                // Checked again on the next supervise tick
+                                state.disk_full = false;
+                            } else if update.0 == tokens::CAMERA_RECORD.0 {
+                                state.record.clear();
+                            } else {
+                                continue;
+                            }
 
-                        thread::sleep(Duration::from_millis(500));
-
-                        for camera in &last_cameras {
-                            let rst = add_camera(camera, addrs.ip(), &mut cameras, &mut port);
-
-                            if let Err(err) = rst {
-                                events.send(Event::Error(
-                                    err.context(format!("Start gstreamer for {camera}")),
-                                ));
+                            // New settings might fix a failing camera so retry right away
+                            for supervision in state.supervision.values_mut() {
+                                supervision.failures = 0;
+                                if supervision.retry_at.is_some() {
+                                    supervision.retry_at = Some(Instant::now());
+                                }
                             }
-                        }
 
-                        let camera_list = camera_list(&cameras);
-                        let update = store::create_update(&tokens::CAMERAS, camera_list);
-                        events.send(Event::Store(update));
-                    }
-                    // Reruns detect cameras script and start or kill instances of gstreamer as needed
-                    Event::SyncStore => {
+                            state.reconcile(&mut events);
+                        }
+                        _ => {}
+                    },
+                    // Enumerates cameras and start or kill instances of gstreamer as needed
+                    CameraEvent::Rescan => {
                         info!("Checking for new cameras");
 
-                        let camera_detect =
-                            Command::new("/home/pi/mate/detect_cameras.sh").output();
-
-                        match camera_detect {
-                            Ok(output) => {
-                                if !output.status.success() {
-                                    events.send(Event::Error(anyhow!(
-                                        "Collect cameras: {}",
-                                        output.status
-                                    )));
-                                    continue;
-                                }
-
-                                match str::from_utf8(&output.stdout) {
-                                    Ok(data) => {
-                                        let next_cameras: HashSet<String> =
-                                            data.lines().map(ToOwned::to_owned).collect();
-
-                                        for old_camera in last_cameras.difference(&next_cameras) {
-                                            if let Some(mut child) = cameras.remove(old_camera) {
-                                                let rst = child.0.kill();
-
-                                                if let Err(err) = rst {
-                                                    events.send(Event::Error(
-                                                        Error::new(err).context(format!(
-                                                            "Kill gstreamer for {old_camera}"
-                                                        )),
-                                                    ));
-                                                }
-                                            } else {
-                                                error!("Attempted to remove a nonexistant camera");
-                                            }
-                                        }
-
-                                        for new_camera in next_cameras.difference(&last_cameras) {
-                                            if let Some(ip) = target_ip {
-                                                let rst = add_camera(
-                                                    new_camera,
-                                                    ip,
-                                                    &mut cameras,
-                                                    &mut port,
-                                                );
-
-                                                if let Err(err) = rst {
-                                                    events.send(Event::Error(err.context(
-                                                        format!("Start gstreamer for {new_camera}"),
-                                                    )));
-                                                }
-                                            } else {
-                                                error!("Tried to update cameras without a peer");
-                                            }
-                                        }
-
-                                        last_cameras = next_cameras;
-
-                                        let camera_list = camera_list(&cameras);
-                                        let update =
-                                            store::create_update(&tokens::CAMERAS, camera_list);
-                                        events.send(Event::Store(update));
-                                    }
-                                    Err(err) => {
-                                        events.send(Event::Error(
-                                            Error::new(err).context("Collect cameras"),
-                                        ));
+                        let discovered = discovery::discover(
+                            Path::new(SYSFS_VIDEO4LINUX),
+                            Path::new(DEV),
+                            &V4l2Probe,
+                        );
+                        match discovered {
+                            Ok(discovered) => {
+                                state.devices = discovered
+                                    .into_iter()
+                                    .map(|camera| (camera.id.clone(), camera))
+                                    .collect();
+
+                                if state.assign_ports() {
+                                    let written =
+                                        write_camera_ports(Path::new(PORTS_FILE), &state.ports);
+                                    if let Err(err) = written {
+                                        events
+                                            .send(Event::Error(err.context("Write camera ports")));
                                     }
                                 }
                             }
                             Err(err) => {
-                                events
-                                    .send(Event::Error(Error::new(err).context("Collect cameras")));
+                                events.send(Event::Error(err.context("Collect cameras")));
+                                continue;
                             }
                         }
+
+                        state.reconcile(&mut events);
                     }
-                    Event::Exit => {
-                        for (camera, (mut child, _)) in cameras.drain() {
-                            let rst = child.kill();
-
-                            if let Err(err) = rst {
-                                events.send(Event::Error(
-                                    Error::new(err).context(format!("Kill gstreamer for {camera}")),
-                                ));
-                            }
-                        }
+                    CameraEvent::Supervise => {
+                        state.supervise(&mut events);
+                    }
+                    CameraEvent::Exit => {
+                        state.stop_all(&mut events);
 
                         return;
                     }
-                    _ => {}
                 }
             }
         });
@@ -200,59 +235,615 @@ impl System for CameraSystem {
     }
 }
 
-/// Spawns a gstreamer with the args necessary
-fn start_gstreamer(camera: &str, addrs: SocketAddr) -> io::Result<Child> {
-    Command::new("gst-launch-1.0")
-        .arg("v4l2src")
-        .arg(format!("device={camera}"))
-        .arg("!")
-        .arg("video/x-h264,width=1920,height=1080,framerate=30/1")
-        .arg("!")
-        .arg("rtph264pay")
-        .arg("!")
-        .arg("udpsink")
-        .arg(format!("host={}", addrs.ip()))
-        .arg(format!("port={}", addrs.port()))
-        .spawn()
+#[derive(Default)]
+struct CameraState {
+    devices: HashMap<String, CameraDevice>,
+    settings: HashMap<String, CameraSettings>,
+    pipelines: HashMap<String, Pipeline>,
+    /// Saved to `PORTS_FILE` so a camera keeps its port whichever other cameras are plugged in
+    ports: HashMap<String, u16>,
+    /// The cameras each peer wants to receive
+    subscribers: HashMap<SocketAddr, HashSet<String>>,
+    /// The cameras the surface wants recorded
+    record: HashMap<String, bool>,
+    /// Set when deleting old recordings did not free enough space, stops all recordings
+    disk_full: bool,
+    /// Last published list of recordings
+    recordings: Vec<Recording>,
+    supervision: HashMap<String, Supervision>,
+}
+
+/// Restart bookkeeping for one camera
+#[derive(Default)]
+struct Supervision {
+    restarts: u32,
+    /// Failures since the pipeline last ran stably, sets the backoff
+    failures: u32,
+    last_error: Option<String>,
+    /// Set while waiting to restart a failed pipeline
+    retry_at: Option<Instant>,
+}
+
+impl Supervision {
+    fn failed(&mut self, error: String, now: Instant) {
+        self.failures += 1;
+        let backoff = BACKOFF_MIN
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(BACKOFF_MAX);
+
+        self.last_error = Some(error);
+        self.retry_at = Some(now + backoff);
+    }
+
+    fn waiting(&self, now: Instant) -> bool {
+        self.retry_at.map(|it| it > now).unwrap_or(false)
+    }
+}
+
+impl CameraState {
+    fn settings(&self, camera: &str) -> CameraSettings {
+        self.settings.get(camera).copied().unwrap_or_default()
+    }
+
+    /// Where a camera should be streaming to, if anywhere
+    fn destination(&self, camera: &str) -> Option<Destination> {
+        if self.settings(camera).multicast {
+            return Some(Destination::Multicast(MULTICAST_GROUP));
+        }
+
+        let mut peers: Vec<IpAddr> = self
+            .subscribers
+            .iter()
+            .filter(|(_, cameras)| cameras.contains(camera))
+            .map(|(addrs, _)| addrs.ip())
+            .collect();
+        peers.sort();
+        peers.dedup();
+
+        if peers.is_empty() {
+            None
+        } else {
+            Some(Destination::Unicast(peers))
+        }
+    }
+
+    fn recording(&self, camera: &str) -> bool {
+        !self.disk_full && self.record.get(camera).copied().unwrap_or(false)
+    }
+
+    /// Gives newly discovered cameras a port derived from their id, returns if any were given
+    /// one and the ports need saving
+    fn assign_ports(&mut self) -> bool {
+        let mut ids: Vec<&String> = self.devices.keys().collect();
+        ids.sort();
+
+        let mut assigned = false;
+        for id in ids {
+            if self.ports.contains_key(id) {
+                continue;
+            }
+
+            let taken: HashSet<u16> = self.ports.values().copied().collect();
+            let start = port_hash(id);
+            let port = (0..PORT_COUNT)
+                .map(|offset| FIRST_PORT + (start + offset) % PORT_COUNT)
+                .find(|port| !taken.contains(port));
+
+            if let Some(port) = port {
+                self.ports.insert(id.clone(), port);
+                assigned = true;
+            } else {
+                warn!("No free port for camera {id}");
+            }
+        }
+
+        assigned
+    }
+
+    /// Stops pipelines that no longer match their camera, starts any that are missing and
+    /// attaches or detaches their recorders
+    fn reconcile(&mut self, events: &mut EventHandle) {
+        let stale: Vec<String> = self
+            .pipelines
+            .iter()
+            .filter(|(id, pipeline)| {
+                self.devices.get(*id) != Some(&pipeline.device)
+                    || self.settings(id) != pipeline.settings
+                    || self.destination(id) != pipeline.destination
+                    || self.ports.get(*id) != Some(&pipeline.port)
+                    || (self.destination(id).is_none() && !self.recording(id))
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in stale {
+            if let Some(pipeline) = self.pipelines.remove(&id) {
+                kill_pipeline(&id, pipeline, events);
+            }
+        }
+
+        // Nothing to restart for cameras that are gone, turned off or not used
+        let idle: Vec<String> = self
+            .supervision
+            .keys()
+            .filter(|id| {
+                !self.devices.contains_key(*id)
+                    || !self.settings(id).enabled
+                    || (self.destination(id).is_none() && !self.recording(id))
+            })
+            .cloned()
+            .collect();
+        for id in idle {
+            if let Some(supervision) = self.supervision.get_mut(&id) {
+                supervision.retry_at = None;
+            }
+        }
+
+        let now = Instant::now();
+        let mut missing: Vec<(&CameraDevice, u16, Option<Destination>)> = self
+            .devices
+            .values()
+            .filter(|camera| !self.pipelines.contains_key(&camera.id))
+            .filter(|camera| self.settings(&camera.id).enabled)
+            .filter(|camera| {
+                !self
+                    .supervision
+                    .get(&camera.id)
+                    .map(|it| it.waiting(now))
+                    .unwrap_or(false)
+            })
+            .filter_map(|camera| {
+                let port = *self.ports.get(&camera.id)?;
+                let destination = self.destination(&camera.id);
+
+                if destination.is_none() && !self.recording(&camera.id) {
+                    return None;
+                }
+
+                Some((camera, port, destination))
+            })
+            .collect();
+        missing.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+
+        let mut started = Vec::new();
+
+        for (camera, port, destination) in missing {
+            let settings = self.settings(&camera.id);
+
+            info!(
+                "Starting {} as {} at {} to {destination:?} on port {port}",
+                camera.name,
+                camera.id,
+                camera.node.display()
+            );
+
+            let supervision = self.supervision.entry(camera.id.clone()).or_default();
+            if supervision.retry_at.take().is_some() {
+                supervision.restarts += 1;
+            }
+
+            match Pipeline::start(camera, settings, port, destination) {
+                Ok(pipeline) => started.push(pipeline),
+                Err(err) => {
+                    supervision.failed(format!("{err:#}"), now);
+                    events.send(Event::Error(
+                        err.context(format!("Start gstreamer for {}", camera.id)),
+                    ));
+                }
+            }
+        }
+
+        for pipeline in started {
+            self.pipelines.insert(pipeline.device.id.clone(), pipeline);
+        }
+
+        let mut ids: Vec<String> = self.pipelines.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let recording = self.recording(&id);
+            let Some(pipeline) = self.pipelines.get_mut(&id) else {
+                continue;
+            };
+
+            if recording && pipeline.recording.is_none() {
+                info!("Recording {id}");
+
+                let started = recording::segment_pattern(Path::new(RECORDING_DIR), &id)
+                    .context("Prepare recording")
+                    .and_then(|pattern| pipeline.start_recording(pattern));
+                if let Err(err) = started {
+                    events.send(Event::Error(err.context(format!("Record {id}"))));
+                }
+            } else if !recording && pipeline.recording.is_some() {
+                info!("Stopped recording {id}");
+
+                if let Err(err) = pipeline.stop_recording() {
+                    events.send(Event::Error(
+                        Error::new(err).context(format!("Stop recording {id}")),
+                    ));
+                }
+            }
+        }
+
+        let update = store::create_update(&tokens::CAMERAS, self.camera_list());
+        events.send(Event::Store(update));
+        self.publish_health(events);
+    }
+
+    /// Notices pipelines that died and restarts them once their backoff runs out
+    fn supervise(&mut self, events: &mut EventHandle) {
+        let now = Instant::now();
+
+        let mut exited = Vec::new();
+        for (id, pipeline) in &mut self.pipelines {
+            match pipeline.exited() {
+                Ok(Some(status)) => exited.push((id.clone(), format!("gstreamer {status}"))),
+                Ok(None) => {}
+                Err(err) => exited.push((id.clone(), format!("Check on gstreamer: {err}"))),
+            }
+
+            // Restarted with its pipeline so it goes through the same backoff
+            match pipeline.recorder_exited() {
+                Ok(Some(status)) => exited.push((id.clone(), format!("Recorder {status}"))),
+                Ok(None) => {}
+                Err(err) => exited.push((id.clone(), format!("Check on recorder: {err}"))),
+            }
+        }
+
+        for (id, error) in &exited {
+            let Some(pipeline) = self.pipelines.remove(id) else {
+                continue;
+            };
+
+            let stderr = pipeline.stderr();
+            let error = if stderr.is_empty() {
+                error.clone()
+            } else {
+                format!("{error}\n{stderr}")
+            };
+            warn!("Camera {id} failed: {error}");
+
+            let supervision = self.supervision.entry(id.clone()).or_default();
+            if pipeline.uptime() >= STABLE_TIME {
+                supervision.failures = 0;
+            }
+            supervision.failed(error.clone(), now);
+
+            // Already exited in the usual case, this only matters if `try_wait` failed
+            let _ = pipeline.kill();
+
+            events.send(Event::Error(anyhow!("Camera {id} failed: {error}")));
+        }
+
+        let retry_due = self
+            .supervision
+            .values()
+            .any(|it| it.retry_at.is_some() && !it.waiting(now));
+
+        let disk_filled = self.check_free_space(events);
+        self.publish_recordings(events);
+
+        if !exited.is_empty() || retry_due || disk_filled {
+            self.reconcile(events);
+        } else {
+            self.publish_health(events);
+        }
+    }
+
+    /// Deletes the oldest recordings when the disk gets too full and stops recording if that
+    /// is not enough, returns if it just stopped
+    fn check_free_space(&mut self, events: &mut EventHandle) -> bool {
+        let active: Vec<PathBuf> = self
+            .pipelines
+            .values()
+            .filter_map(|it| it.recording.as_ref())
+            .map(|it| it.pattern.clone())
+            .collect();
+        if self.disk_full || active.is_empty() {
+            return false;
+        }
+
+        let dir = Path::new(RECORDING_DIR);
+        match recording::prune(dir, &active, || recording::free_space(dir)) {
+            Ok(deleted) if !deleted.is_empty() => {
+                let freed: u64 = deleted.iter().map(|it| it.size).sum();
+                warn!(
+                    "Deleted {} old recordings to free {} MiB",
+                    deleted.len(),
+                    freed / 1024 / 1024
+                );
+            }
+            Ok(_) => {}
+            Err(err) => {
+                events.send(Event::Error(err.context("Delete old recordings")));
+            }
+        }
+
+        match recording::free_space(dir) {
+            Ok(free) if free < MIN_FREE_SPACE => {
+                self.disk_full = true;
+                events.send(Event::Error(anyhow!(
+                    "Stopped recording, only {} MiB free",
+                    free / 1024 / 1024
+                )));
+
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                events.send(Event::Error(err.context("Check free space")));
+
+                false
+            }
+        }
+    }
+
+    fn publish_recordings(&mut self, events: &mut EventHandle) {
+        match recording::list_recordings(Path::new(RECORDING_DIR)) {
+            Ok(recordings) => {
+                if recordings != self.recordings {
+                    self.recordings = recordings.clone();
+
+                    let update = store::create_update(&tokens::RECORDINGS, recordings);
+                    events.send(Event::Store(update));
+                }
+            }
+            Err(err) => {
+                events.send(Event::Error(err.context("List recordings")));
+            }
+        }
+    }
+
+    fn stop_all(&mut self, events: &mut EventHandle) {
+        for (camera, pipeline) in self.pipelines.drain() {
+            kill_pipeline(&camera, pipeline, events);
+        }
+    }
+
+    fn publish_health(&self, events: &mut EventHandle) {
+        let health: HashMap<String, CameraHealth> = self
+            .devices
+            .keys()
+            .map(|id| {
+                let supervision = self.supervision.get(id);
+                let pipeline = self.pipelines.get(id);
+
+                let state = if pipeline.is_some() {
+                    CameraStreamState::Running
+                } else if supervision.map(|it| it.retry_at.is_some()).unwrap_or(false) {
+                    CameraStreamState::Restarting
+                } else {
+                    CameraStreamState::Stopped
+                };
+
+                let health = CameraHealth {
+                    state,
+                    restarts: supervision.map(|it| it.restarts).unwrap_or(0),
+                    last_error: supervision.and_then(|it| it.last_error.clone()),
+                    uptime: pipeline.map(Pipeline::uptime).unwrap_or_default(),
+                    recording: pipeline.map(|it| it.recording.is_some()).unwrap_or(false),
+                };
+
+                (id.clone(), health)
+            })
+            .collect();
+
+        let update = store::create_update(&tokens::CAMERA_HEALTH, health);
+        events.send(Event::Store(update));
+    }
+
+    /// Converts internal repersentation of cameras to what the protocol calls for
+    fn camera_list(&self) -> Vec<Camera> {
+        let mut list = Vec::new();
+
+        for name in self.devices.keys() {
+            let settings = self.settings(name);
+            let Some(&port) = self.ports.get(name) else {
+                continue;
+            };
+            if !settings.enabled {
+                continue;
+            }
+
+            let ip = if settings.multicast {
+                IpAddr::V4(MULTICAST_GROUP)
+            } else {
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+            };
+
+            list.push(Camera {
+                name: name.clone(),
+                location: (ip, port).into(),
+                settings,
+            });
+        }
+
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+
+        list
+    }
+}
+
+/// FNV-1a of the camera id, stable across builds unlike the std hasher
+fn port_hash(id: &str) -> u16 {
+    let hash = id.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+
+    (hash % PORT_COUNT as u32) as u16
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PortRecord {
+    camera: String,
+    port: u16,
 }
 
-/// Starts a gstreamer and updates state
-fn add_camera(
-    camera: &str,
-    ip: IpAddr,
-    cameras: &mut HashMap<String, (Child, SocketAddr)>,
-    port: &mut u16,
-) -> anyhow::Result<()> {
-    let setup_exit = Command::new("/home/pi/mate/setup_camera.sh")
-        .arg(camera)
-        .spawn()
-        .context("Setup cameras")?
-        .wait()
-        .context("wait on setup")?;
-    if !setup_exit.success() {
-        bail!("Could not setup cameras");
+/// Reads the ports cameras were given before, none if nothing was saved yet
+/// Columns are `camera,port` with the camera's id
+fn read_camera_ports(path: &Path) -> anyhow::Result<HashMap<String, u16>> {
+    if !path.exists() {
+        info!("No camera ports saved, assigning new ones");
+        return Ok(HashMap::default());
     }
 
-    let bind = (ip, *port).into();
-    let child =
-        start_gstreamer(camera, bind).with_context(|| format!("Spawn gstreamer for {camera}"))?;
-    *port += 1;
+    let reader = csv::Reader::from_path(path).context("Open camera ports")?;
+    reader
+        .into_deserialize()
+        .map(|result| {
+            let record: PortRecord = result.context("Parse camera port")?;
+            Ok((record.camera, record.port))
+        })
+        .collect()
+}
 
-    cameras.insert((*camera).to_owned(), (child, bind));
+fn write_camera_ports(path: &Path, ports: &HashMap<String, u16>) -> anyhow::Result<()> {
+    let mut records: Vec<PortRecord> = ports
+        .iter()
+        .map(|(camera, port)| PortRecord {
+            camera: camera.clone(),
+            port: *port,
+        })
+        .collect();
+    records.sort_by(|a, b| a.camera.cmp(&b.camera));
+
+    let mut writer = csv::Writer::from_path(path).context("Open camera ports")?;
+    for record in records {
+        writer.serialize(record).context("Write camera port")?;
+    }
+    writer.flush().context("Flush camera ports")?;
 
     Ok(())
 }
 
-/// Converts internal repersentation of cameras to what the protocol calls for
-fn camera_list(cameras: &HashMap<String, (Child, SocketAddr)>) -> Vec<Camera> {
-    let mut list = Vec::new();
+enum CameraEvent {
+    Event(Arc<Event>),
+    Rescan,
+    Supervise,
+    Exit,
+}
 
-    for (name, (_, location)) in cameras {
-        list.push(Camera {
-            name: name.clone(),
-            location: *location,
-        });
+/// Requests a rescan at startup and whenever a video node is added to or removed from `/dev`
+fn watch_cameras(tx: Sender<CameraEvent>) -> anyhow::Result<()> {
+    let inotify = Inotify::init(InitFlags::IN_NONBLOCK).context("Init inotify")?;
+    inotify
+        .add_watch(DEV, AddWatchFlags::IN_CREATE | AddWatchFlags::IN_DELETE)
+        .context("Watch /dev")?;
+
+    tx.try_send(CameraEvent::Rescan).log_error("Request rescan");
+
+    while !stop::world_stopped() {
+        if !wait_for_events(&inotify, WATCH_INTERVAL)? {
+            continue;
+        }
+
+        if read_video_events(&inotify)? {
+            // Collect the rest of a burst of node changes into one rescan
+            thread::sleep(SETTLE_TIME);
+            read_video_events(&inotify)?;
+
+            tx.try_send(CameraEvent::Rescan).log_error("Request rescan");
+        }
+    }
+
+    Ok(())
+}
+
+/// Blocks until inotify has events or `timeout` passes, returns if there are events
+fn wait_for_events(inotify: &Inotify, timeout: Duration) -> anyhow::Result<bool> {
+    let mut fds = [PollFd::new(inotify.as_raw_fd(), PollFlags::POLLIN)];
+
+    match poll(&mut fds, timeout.as_millis() as i32) {
+        Ok(ready) => Ok(ready > 0),
+        Err(Errno::EINTR) => Ok(false),
+        Err(err) => Err(err).context("Poll inotify"),
+    }
+}
+
+/// Drains pending inotify events, returns if any were for a video node
+fn read_video_events(inotify: &Inotify) -> anyhow::Result<bool> {
+    let mut changed = false;
+
+    loop {
+        match inotify.read_events() {
+            Ok(events) => {
+                changed |= events.iter().any(|event| {
+                    event
+                        .name
+                        .as_ref()
+                        .map(|it| it.to_string_lossy().starts_with("video"))
+                        .unwrap_or(false)
+                });
+            }
+            Err(Errno::EAGAIN) => return Ok(changed),
+            Err(err) => return Err(err).context("Read inotify events"),
+        }
+    }
+}
+
+fn kill_pipeline(camera: &str, pipeline: Pipeline, events: &mut EventHandle) {
+    if let Err(err) = pipeline.kill() {
+        events.send(Event::Error(
+            Error::new(err).context(format!("Kill gstreamer for {camera}")),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    fn plug_in(state: &mut CameraState, ids: &[&str]) {
+        state.devices = ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| {
+                let device = CameraDevice {
+                    id: id.to_string(),
+                    node: PathBuf::from(format!("/dev/video{}", idx * 2)),
+                    name: id.to_string(),
+                };
+
+                (id.to_string(), device)
+            })
+            .collect();
     }
 
-    list
+    #[test]
+    fn saved_ports_do_not_depend_on_other_cameras() {
+        let path = std::env::temp_dir().join(format!("camera-ports-{}.csv", process::id()));
+        let _ = fs::remove_file(&path);
+
+        // Ends up on the port `front` hashes to when it is plugged in first
+        let front = "platform-usb-1.1-video-index0";
+        let taken = FIRST_PORT + port_hash(front);
+
+        let mut state = CameraState::default();
+        state.ports.insert("bottom".to_owned(), taken);
+        plug_in(&mut state, &[front]);
+        assert!(state.assign_ports());
+        assert_eq!(state.ports[front], taken + 1);
+        write_camera_ports(&path, &state.ports).unwrap();
+
+        // Without `bottom` around `front` would get a different port if it were assigned again
+        let mut state = CameraState {
+            ports: read_camera_ports(&path).unwrap(),
+            ..Default::default()
+        };
+        plug_in(&mut state, &[front]);
+        assert!(!state.assign_ports());
+        assert_eq!(state.ports[front], taken + 1);
+        assert_eq!(state.ports["bottom"], taken);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_ports_file_is_empty() {
+        let path = std::env::temp_dir().join("camera-ports-missing.csv");
+
+        assert!(read_camera_ports(&path).unwrap().is_empty());
+    }
 }
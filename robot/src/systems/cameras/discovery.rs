@@ -0,0 +1,217 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use fxhash::FxHashMap as HashMap;
+use tracing::warn;
+
+use crate::peripheral::v4l2::{V4l2Device, PIX_FMT_H264};
+
+pub const SYSFS_VIDEO4LINUX: &str = "/sys/class/video4linux";
+pub const DEV: &str = "/dev";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraDevice {
+    /// Stays the same across reconnects as long as the camera is in the same port
+    pub id: String,
+    /// Device node such as `/dev/video0`, can change whenever the camera reconnects
+    pub node: PathBuf,
+    pub name: String,
+}
+
+/// Checks what a device node is able to stream
+pub trait DeviceProbe {
+    fn supports_h264(&self, node: &Path) -> anyhow::Result<bool>;
+}
+
+/// Asks the driver through V4L2 ioctls
+pub struct V4l2Probe;
+
+impl DeviceProbe for V4l2Probe {
+    fn supports_h264(&self, node: &Path) -> anyhow::Result<bool> {
+        let device = V4l2Device::open(node)?;
+
+        if !device.capabilities()?.video_capture {
+            return Ok(false);
+        }
+
+        Ok(device.capture_formats()?.contains(&PIX_FMT_H264))
+    }
+}
+
+/// Lists the H.264 capable capture devices under `sysfs`, with their nodes in `dev`
+pub fn discover(
+    sysfs: &Path,
+    dev: &Path,
+    probe: &impl DeviceProbe,
+) -> anyhow::Result<Vec<CameraDevice>> {
+    let by_id = stable_links(&dev.join("v4l/by-id"));
+    let mut cameras = Vec::new();
+
+    for entry in fs::read_dir(sysfs).context("Read video4linux")? {
+        let entry = entry.context("Read video4linux entry")?;
+        let node_name = entry.file_name().to_string_lossy().into_owned();
+
+        if !node_name.starts_with("video") {
+            continue;
+        }
+
+        let node = dev.join(&node_name);
+        match probe.supports_h264(&node) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(err) => {
+                warn!("Could not probe {}: {err:?}", node.display());
+                continue;
+            }
+        }
+
+        let path = entry.path();
+        let name = read_attribute(&path.join("name")).unwrap_or_else(|| node_name.clone());
+        let index = read_attribute(&path.join("index")).unwrap_or_else(|| "0".to_owned());
+
+        // The usb interface the node belongs to, eg. `1-1.2:1.0`
+        let usb_path = fs::read_link(path.join("device"))
+            .ok()
+            .and_then(|it| it.file_name().map(|it| it.to_string_lossy().into_owned()));
+
+        let id = match (usb_path, by_id.get(&node_name)) {
+            (Some(usb_path), _) => format!("usb-{usb_path}-video-index{index}"),
+            (None, Some(link)) => link.clone(),
+            (None, None) => node_name,
+        };
+
+        cameras.push(CameraDevice { id, node, name });
+    }
+
+    cameras.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(cameras)
+}
+
+/// Maps node names to the udev symlinks pointing at them
+fn stable_links(dir: &Path) -> HashMap<String, String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::default();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let target = fs::read_link(entry.path()).ok()?;
+            let node_name = target.file_name()?.to_string_lossy().into_owned();
+
+            Some((node_name, entry.file_name().to_string_lossy().into_owned()))
+        })
+        .collect()
+}
+
+fn read_attribute(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|it| it.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::symlink, process};
+
+    use fxhash::FxHashSet as HashSet;
+
+    use super::*;
+
+    struct FakeProbe(HashSet<&'static str>);
+
+    impl DeviceProbe for FakeProbe {
+        fn supports_h264(&self, node: &Path) -> anyhow::Result<bool> {
+            let node_name = node.file_name().unwrap().to_str().unwrap();
+
+            Ok(self.0.contains(node_name))
+        }
+    }
+
+    fn add_node(root: &Path, node: &str, name: &str, index: u32, usb_path: Option<&str>) {
+        let sysfs = root.join("sys/class/video4linux").join(node);
+        fs::create_dir_all(&sysfs).unwrap();
+        fs::write(sysfs.join("name"), format!("{name}\n")).unwrap();
+        fs::write(sysfs.join("index"), format!("{index}\n")).unwrap();
+
+        if let Some(usb_path) = usb_path {
+            let interface = root.join("sys/devices/usb1").join(usb_path);
+            fs::create_dir_all(&interface).unwrap();
+            symlink(interface, sysfs.join("device")).unwrap();
+        }
+
+        fs::write(root.join("dev").join(node), "").unwrap();
+    }
+
+    #[test]
+    fn discover_fake_sysfs() {
+        let root = std::env::temp_dir().join(format!("v4l2-discovery-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("dev/v4l/by-id")).unwrap();
+        fs::create_dir_all(root.join("sys/class/video4linux")).unwrap();
+
+        // H.264 camera, its second node only carries metadata
+        add_node(&root, "video0", "H264 USB Camera", 0, Some("1-1.2:1.0"));
+        add_node(&root, "video1", "H264 USB Camera", 1, Some("1-1.2:1.0"));
+        // MJPEG only camera
+        add_node(&root, "video2", "USB Camera", 0, Some("1-1.3:1.0"));
+        // H.264 device that is not on usb
+        add_node(&root, "video4", "Platform Camera", 0, None);
+        symlink(
+            "../../video4",
+            root.join("dev/v4l/by-id/platform-camera-video-index0"),
+        )
+        .unwrap();
+        // Codec nodes without a capture device are not cameras
+        fs::create_dir_all(root.join("sys/class/video4linux/v4l-subdev0")).unwrap();
+
+        let probe = FakeProbe(["video0", "video4"].into_iter().collect());
+        let cameras = discover(
+            &root.join("sys/class/video4linux"),
+            &root.join("dev"),
+            &probe,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cameras,
+            vec![
+                CameraDevice {
+                    id: "platform-camera-video-index0".to_owned(),
+                    node: root.join("dev/video4"),
+                    name: "Platform Camera".to_owned(),
+                },
+                CameraDevice {
+                    id: "usb-1-1.2:1.0-video-index0".to_owned(),
+                    node: root.join("dev/video0"),
+                    name: "H264 USB Camera".to_owned(),
+                },
+            ]
+        );
+
+        // Reconnecting in the same port keeps the id even though the node changes
+        fs::remove_dir_all(root.join("sys/class/video4linux/video0")).unwrap();
+        add_node(&root, "video6", "H264 USB Camera", 0, Some("1-1.2:1.0"));
+
+        let probe = FakeProbe(["video6"].into_iter().collect());
+        let cameras = discover(
+            &root.join("sys/class/video4linux"),
+            &root.join("dev"),
+            &probe,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cameras,
+            vec![CameraDevice {
+                id: "usb-1-1.2:1.0-video-index0".to_owned(),
+                node: root.join("dev/video6"),
+                name: "H264 USB Camera".to_owned(),
+            }]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
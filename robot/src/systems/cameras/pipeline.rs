@@ -0,0 +1,259 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use common::types::CameraSettings;
+use tracing::debug;
+
+use super::{discovery::CameraDevice, recording::SEGMENT_TIME};
+
+/// Lines of gstreamer's stderr kept for error reports
+const STDERR_LINES: usize = 10;
+/// Room for a few keyframes at the highest bitrate
+const SHM_SIZE: usize = 8 * 1024 * 1024;
+
+/// Where a pipeline sends its stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// A copy of the stream for each subscribed surface
+    Unicast(Vec<IpAddr>),
+    Multicast(Ipv4Addr),
+}
+
+/// A running gstreamer streaming one camera
+pub struct Pipeline {
+    child: Child,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    pub started: Instant,
+
+    pub device: CameraDevice,
+    pub port: u16,
+    pub destination: Option<Destination>,
+    pub recording: Option<Recorder>,
+    pub settings: CameraSettings,
+}
+
+/// A gstreamer writing a pipeline's stream to disk, attached to the pipeline's tee through
+/// shared memory so it can come and go without touching the stream
+pub struct Recorder {
+    child: Child,
+    /// Segment pattern of the files being recorded to
+    pub pattern: PathBuf,
+}
+
+impl Pipeline {
+    /// Sets up the camera and starts a gstreamer streaming to `port` at `destination`
+    pub fn start(
+        camera: &CameraDevice,
+        settings: CameraSettings,
+        port: u16,
+        destination: Option<Destination>,
+    ) -> anyhow::Result<Self> {
+        let node = camera.node.to_string_lossy();
+
+        let setup_exit = Command::new("/home/pi/mate/setup_camera.sh")
+            .arg(&*node)
+            .spawn()
+            .context("Setup cameras")?
+            .wait()
+            .context("wait on setup")?;
+        if !setup_exit.success() {
+            bail!("Could not setup cameras");
+        }
+
+        // Left behind if the last gstreamer on this port was killed
+        let socket = socket_path(port);
+        if socket.exists() {
+            fs::remove_file(&socket).context("Remove stale recording socket")?;
+        }
+
+        let mut child = start_gstreamer(&node, settings, port, &destination, &socket)
+            .with_context(|| format!("Spawn gstreamer for {}", camera.id))?;
+
+        let stderr = Arc::new(Mutex::new(VecDeque::new()));
+        if let Some(pipe) = child.stderr.take() {
+            let stderr = stderr.clone();
+            let camera = camera.id.clone();
+
+            // Ends by itself when gstreamer exits and closes the pipe
+            thread::spawn(move || {
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    debug!("gstreamer {camera}: {line}");
+
+                    let mut stderr = stderr.lock().expect("Lock stderr");
+                    if stderr.len() == STDERR_LINES {
+                        stderr.pop_front();
+                    }
+                    stderr.push_back(line);
+                }
+            });
+        }
+
+        Ok(Self {
+            child,
+            stderr,
+            started: Instant::now(),
+            device: camera.clone(),
+            port,
+            destination,
+            recording: None,
+            settings,
+        })
+    }
+
+    /// Starts recording the stream to the segment pattern `pattern`
+    pub fn start_recording(&mut self, pattern: PathBuf) -> anyhow::Result<()> {
+        self.stop_recording().context("Stop last recording")?;
+
+        let child = start_recorder(&socket_path(self.port), &pattern)
+            .with_context(|| format!("Spawn recorder for {}", self.device.id))?;
+        self.recording = Some(Recorder { child, pattern });
+
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        if let Some(mut recorder) = self.recording.take() {
+            recorder.child.kill()?;
+            recorder.child.wait()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the exit status once gstreamer has stopped
+    pub fn exited(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Returns the exit status once the recorder has stopped
+    pub fn recorder_exited(&mut self) -> io::Result<Option<ExitStatus>> {
+        match &mut self.recording {
+            Some(recorder) => recorder.child.try_wait(),
+            None => Ok(None),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The last lines gstreamer wrote to stderr
+    pub fn stderr(&self) -> String {
+        let stderr = self.stderr.lock().expect("Lock stderr");
+
+        stderr.iter().cloned().collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn kill(mut self) -> io::Result<()> {
+        let recording = self.stop_recording();
+
+        self.child.kill()?;
+        self.child.wait()?;
+
+        recording
+    }
+}
+
+/// Where a pipeline on `port` offers its stream to a recorder
+fn socket_path(port: u16) -> PathBuf {
+    PathBuf::from(format!("/tmp/mate_camera_{port}"))
+}
+
+/// Spawns a gstreamer with the args necessary
+fn start_gstreamer(
+    camera: &str,
+    settings: CameraSettings,
+    port: u16,
+    destination: &Option<Destination>,
+    socket: &Path,
+) -> io::Result<Child> {
+    let CameraSettings {
+        width,
+        height,
+        framerate,
+        bitrate,
+        ..
+    } = settings;
+
+    let mut command = Command::new("gst-launch-1.0");
+    command
+        .arg("v4l2src")
+        .arg(format!("device={camera}"))
+        .arg(format!("extra-controls=c,video_bitrate={}", bitrate * 1000))
+        .arg("!")
+        .arg(format!(
+            "video/x-h264,width={width},height={height},framerate={framerate}/1"
+        ))
+        .arg("!")
+        // Repeats the stream headers with every keyframe so a recorder can join at any time
+        .arg("h264parse")
+        .arg("config-interval=-1")
+        .arg("!")
+        .arg("tee")
+        .arg("name=t");
+
+    match destination {
+        Some(Destination::Unicast(peers)) => {
+            let clients = peers
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, port).to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            command
+                .args(["t.", "!", "queue", "!", "rtph264pay", "!"])
+                .arg("multiudpsink")
+                .arg(format!("clients={clients}"));
+        }
+        Some(Destination::Multicast(group)) => {
+            command
+                .args(["t.", "!", "queue", "!", "rtph264pay", "!"])
+                .arg("udpsink")
+                .arg(format!("host={group}"))
+                .arg(format!("port={port}"))
+                .arg("auto-multicast=true");
+        }
+        None => {}
+    }
+
+    // Drops frames until a recorder connects
+    command
+        .args(["t.", "!", "queue", "leaky=downstream", "!"])
+        .arg("shmsink")
+        .arg(format!("socket-path={}", socket.display()))
+        .arg(format!("shm-size={SHM_SIZE}"))
+        .arg("wait-for-connection=false")
+        .arg("sync=false");
+
+    command.stderr(Stdio::piped()).spawn()
+}
+
+/// Spawns a gstreamer recording the stream offered on `socket`
+fn start_recorder(socket: &Path, pattern: &Path) -> io::Result<Child> {
+    // Matroska stays readable if gstreamer is killed mid segment
+    Command::new("gst-launch-1.0")
+        .arg("shmsrc")
+        .arg(format!("socket-path={}", socket.display()))
+        .arg("is-live=true")
+        .arg("do-timestamp=true")
+        .arg("!")
+        .arg("video/x-h264,stream-format=byte-stream,alignment=au")
+        .arg("!")
+        .arg("h264parse")
+        .arg("!")
+        .arg("splitmuxsink")
+        .arg(format!("location={}", pattern.display()))
+        .arg(format!("max-size-time={}", SEGMENT_TIME.as_nanos()))
+        .arg("muxer-factory=matroskamux")
+        .spawn()
+}
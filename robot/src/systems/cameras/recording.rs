@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use common::types::Recording;
+use nix::sys::statvfs::statvfs;
+
+pub const RECORDING_DIR: &str = "/home/pi/mate/recordings";
+/// Length of each file a recording is split into
+pub const SEGMENT_TIME: Duration = Duration::from_secs(5 * 60);
+/// Old recordings are deleted to keep this many bytes free, recording stops if that is not
+/// enough
+pub const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+const EXTENSION: &str = "mkv";
+
+/// Bytes available to unprivileged users on the filesystem containing `dir`
+pub fn free_space(dir: &Path) -> anyhow::Result<u64> {
+    let stat = statvfs(dir).with_context(|| format!("Stat {}", dir.display()))?;
+
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+/// The `splitmuxsink` location for a new recording of `camera`, segments are numbered in
+/// place of the `%03d`
+pub fn segment_pattern(dir: &Path, camera: &str) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(dir).context("Create recording dir")?;
+
+    let started = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .context("Time before epoch")?
+        .as_secs();
+
+    Ok(dir.join(format!("{camera}_{started}_%03d.{EXTENSION}")))
+}
+
+/// Lists the recorded segments in `dir`, oldest first
+pub fn list_recordings(dir: &Path) -> anyhow::Result<Vec<Recording>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        // Nothing has been recorded yet
+        return Ok(Vec::new());
+    };
+
+    let mut recordings = Vec::new();
+    for entry in entries {
+        let entry = entry.context("Read recording dir entry")?;
+        let path = entry.path();
+
+        if path.extension().and_then(|it| it.to_str()) != Some(EXTENSION) {
+            continue;
+        }
+
+        // Camera ids can contain `_` so split from the right
+        let Some(stem) = path.file_stem().and_then(|it| it.to_str()) else {
+            continue;
+        };
+        let Some(camera) = stem.rsplitn(3, '_').nth(2) else {
+            continue;
+        };
+
+        let metadata = entry.metadata().context("Read recording metadata")?;
+        recordings.push(Recording {
+            camera: camera.to_owned(),
+            path: path.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified: metadata.modified().context("Read modified time")?,
+        });
+    }
+
+    recordings.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.path.cmp(&b.path)));
+
+    Ok(recordings)
+}
+
+/// Deletes the oldest segments in `dir` until `free_space` reports `MIN_FREE_SPACE`, returns
+/// the segments deleted
+///
+/// The newest segment of each pattern in `active` is still being written and is kept
+pub fn prune(
+    dir: &Path,
+    active: &[PathBuf],
+    mut free_space: impl FnMut() -> anyhow::Result<u64>,
+) -> anyhow::Result<Vec<Recording>> {
+    let mut deleted = Vec::new();
+    if free_space()? >= MIN_FREE_SPACE {
+        return Ok(deleted);
+    }
+
+    let recordings = list_recordings(dir)?;
+    let writing: Vec<&Recording> = active
+        .iter()
+        .filter_map(|pattern| {
+            recordings
+                .iter()
+                .rev()
+                .find(|recording| segment_of(pattern, recording))
+        })
+        .collect();
+
+    for recording in &recordings {
+        if writing.contains(&recording) {
+            continue;
+        }
+
+        fs::remove_file(&recording.path)
+            .with_context(|| format!("Delete recording {}", recording.path))?;
+        deleted.push(recording.clone());
+
+        if free_space()? >= MIN_FREE_SPACE {
+            break;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// If `recording` is one of the segments written for `pattern`
+fn segment_of(pattern: &Path, recording: &Recording) -> bool {
+    let pattern = pattern.to_string_lossy();
+    let prefix = pattern.split("%03d").next().unwrap_or(&pattern);
+
+    recording.path.starts_with(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, process, thread};
+
+    use super::*;
+
+    const SEGMENT_SIZE: u64 = 512 * 1024 * 1024;
+
+    #[test]
+    fn prune_deletes_oldest_segments_first() {
+        let dir = std::env::temp_dir().join(format!("recording-prune-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let segments = [
+            "front_100_000.mkv",
+            "front_100_001.mkv",
+            "back_200_000.mkv",
+            "back_200_001.mkv",
+        ];
+        for segment in segments {
+            fs::write(dir.join(segment), []).unwrap();
+            // Modified times have to differ for the order to be known
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        // Only back is still recording, each segment frees half a gigabyte
+        let free = Cell::new(0);
+        let active = [dir.join("back_200_%03d.mkv")];
+        let free_space = || {
+            let remaining = fs::read_dir(&dir).unwrap().count() as u64;
+            free.set((segments.len() as u64 - remaining) * SEGMENT_SIZE);
+            Ok(free.get())
+        };
+
+        let deleted = prune(&dir, &active, free_space).unwrap();
+        let deleted: Vec<&str> = deleted.iter().map(|it| it.camera.as_str()).collect();
+        assert_eq!(deleted, ["front", "front"]);
+        assert!(free.get() >= MIN_FREE_SPACE);
+
+        // Nothing left to delete but the segment being written
+        fs::remove_file(dir.join("back_200_000.mkv")).unwrap();
+        let deleted = prune(&dir, &active, || Ok(0)).unwrap();
+        assert!(deleted.is_empty());
+        assert!(dir.join("back_200_001.mkv").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+//! The clock the robot's control loops and stores run on
+
+use std::sync::RwLock;
+
+use common::clock::{self, SharedClock};
+use tracing::error;
+
+static CLOCK: RwLock<Option<SharedClock>> = RwLock::new(None);
+
+/// The clock set with `set_clock`, or the system clock
+pub fn clock() -> SharedClock {
+    match CLOCK.read() {
+        Ok(clock) => clock.clone().unwrap_or_else(clock::system_clock),
+        Err(_) => clock::system_clock(),
+    }
+}
+
+/// Replaces the clock systems pick up when they start, must be called before `SystemManager::start`
+pub fn set_clock(clock: SharedClock) {
+    match CLOCK.write() {
+        Ok(mut current) => *current = Some(clock),
+        Err(_) => error!("Could not set clock"),
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+/// Only lets a condition change state once it has held for a while
+#[derive(Debug, Clone, Copy)]
+pub struct Debounce {
+    hold: Duration,
+    active: bool,
+    /// When the condition started disagreeing with `active`
+    since: Option<Instant>,
+}
+
+impl Debounce {
+    pub const fn new(hold: Duration) -> Self {
+        Self {
+            hold,
+            active: false,
+            since: None,
+        }
+    }
+
+    pub const fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns the new state if it changed
+    pub fn update(&mut self, condition: bool, now: Instant) -> Option<bool> {
+        if condition == self.active {
+            self.since = None;
+            return None;
+        }
+
+        let since = *self.since.get_or_insert(now);
+        if now.saturating_duration_since(since) < self.hold {
+            return None;
+        }
+
+        self.active = condition;
+        self.since = None;
+
+        Some(condition)
+    }
+}
@@ -1,17 +1,33 @@
 use std::{
-    thread::{self, Scope},
-    time::{Duration, Instant},
+    collections::VecDeque,
+    path::Path,
+    thread::Scope,
+    time::{Duration, SystemTime},
 };
 
-use common::store::{self, tokens};
-use tracing::{span, Level};
+use anyhow::{bail, Context};
+use common::{
+    store::{self, tokens},
+    types::{DepthCalibration, DepthSensorSettings, Mbar, WaterType},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, span, warn, Level};
 
 use crate::{
-    event::Event, events::EventHandle, peripheral::ms5937::Ms5837, systems::stop, SystemId,
+    event::Event, events::EventHandle, peripheral::ms5937::Ms5837, systems::scheduler, SystemId,
 };
 
 use super::System;
 
+/// Fastest rate the sensor is read at, slower oversampling stretches it
+const PERIOD: Duration = Duration::from_millis(10);
+/// Time each reading needs on top of its two conversions for the i2c transfers
+const READ_OVERHEAD: Duration = Duration::from_millis(2);
+const TASK_NAME: &str = "Depth sensor";
+const CALIBRATION_FILE: &str = "depth_calibration.csv";
+/// Readings averaged into the surface pressure when taring
+const TARE_SAMPLES: usize = 10;
+
 pub struct DepthSystem;
 
 impl System for DepthSystem {
@@ -21,12 +37,18 @@ impl System for DepthSystem {
         mut events: EventHandle,
         spawner: &'scope Scope<'scope, '_>,
     ) -> anyhow::Result<()> {
-        let _ = events.take_listner();
+        let listener = events.take_listner().unwrap();
+        let mut current_period = period(&DepthSensorSettings::default());
+        let mut ticks = scheduler::schedule(TASK_NAME, current_period);
 
         spawner.spawn(move || {
             span!(Level::INFO, "Depth sensor monitor thread");
 
-            let depth = Ms5837::new(Ms5837::I2C_BUS, Ms5837::I2C_ADDRESS);
+            let depth = Ms5837::new(
+                Ms5837::I2C_BUS,
+                Ms5837::I2C_ADDRESS,
+                DepthSensorSettings::default(),
+            );
             let mut depth = match depth {
                 Ok(depth) => depth,
                 Err(err) => {
@@ -35,16 +57,108 @@ impl System for DepthSystem {
                 }
             };
 
-            let interval = Duration::from_secs_f64(1.0 / 100.0);
+            let mut calibration = match read_depth_calibration() {
+                Ok(calibration) => calibration,
+                Err(err) => {
+                    events.send(Event::Error(
+                        err.context("Could not load depth calibration"),
+                    ));
+                    DepthCalibration::default()
+                }
+            };
+            depth.set_surface_pressure(calibration.surface_pressure);
+            depth.set_water_type(calibration.water_type);
+
+            let update = store::create_update(&tokens::DEPTH_CALIBRATION, calibration);
+            events.send(Event::Store(update));
+
+            let mut recent_pressures = VecDeque::with_capacity(TARE_SAMPLES);
+
+            while let Ok(_tick) = ticks.recv() {
+                let mut changed = false;
+
+                for event in listener.try_iter() {
+                    match &*event {
+                        Event::Store(update) => {
+                            if update.0 == tokens::DEPTH_SENSOR_SETTINGS.0 {
+                                // Removing the settings restores the defaults
+                                let settings =
+                                    store::handle_update(&tokens::DEPTH_SENSOR_SETTINGS, update)
+                                        .map(|it| *it)
+                                        .unwrap_or_default();
+
+                                info!("Applying depth sensor settings: {settings:?}");
+                                depth.set_settings(settings);
+
+                                // Replacing the receiver unschedules the old period
+                                let period = period(&settings);
+                                if period != current_period {
+                                    info!("Reading depth every {period:?}");
+                                    ticks = scheduler::schedule(TASK_NAME, period);
+                                    current_period = period;
+                                }
+                            } else if let Some(request) =
+                                store::handle_update(&tokens::DEPTH_TARE, update)
+                            {
+                                // Requests are resent when the store syncs
+                                if calibration.tare_request == Some(*request) {
+                                    continue;
+                                }
+
+                                if recent_pressures.is_empty() {
+                                    warn!("Cannot tare without pressure readings");
+                                    continue;
+                                }
 
-            let mut deadline = Instant::now();
-            while !stop::world_stopped() {
-                deadline += interval;
+                                let surface_pressure = recent_pressures.iter().sum::<f64>()
+                                    / recent_pressures.len() as f64;
+
+                                info!("Tared depth sensor at {}", Mbar(surface_pressure));
+                                calibration.surface_pressure = Mbar(surface_pressure);
+                                calibration.tare_request = Some(*request);
+                                changed = true;
+                            } else if let Some(water_type) =
+                                store::handle_update(&tokens::DEPTH_WATER_TYPE, update)
+                            {
+                                if calibration.water_type != *water_type {
+                                    info!("Switching to {water_type:?} water");
+                                    calibration.water_type = *water_type;
+                                    changed = true;
+                                }
+                            }
+                        }
+                        Event::SyncStore => {
+                            let update =
+                                store::create_update(&tokens::DEPTH_CALIBRATION, calibration);
+                            events.send(Event::Store(update));
+                        }
+                        _ => {}
+                    }
+                }
+
+                if changed {
+                    depth.set_surface_pressure(calibration.surface_pressure);
+                    depth.set_water_type(calibration.water_type);
+
+                    let update = store::create_update(&tokens::DEPTH_CALIBRATION, calibration);
+                    events.send(Event::Store(update));
+
+                    if let Err(err) = write_depth_calibration(&calibration) {
+                        events.send(Event::Error(
+                            err.context("Could not save depth calibration"),
+                        ));
+                    }
+                }
 
                 let rst = depth.read_frame();
 
                 match rst {
                     Ok(frame) => {
+                        if recent_pressures.len() == TARE_SAMPLES {
+                            recent_pressures.pop_front();
+                        }
+                        recent_pressures.push_back(frame.pressure.0);
+
                         let update = store::create_update(&tokens::RAW_DEPTH, frame);
                         events.send(Event::Store(update));
                     }
@@ -52,12 +166,117 @@ impl System for DepthSystem {
                         events.send(Event::Error(err.context("Could not read depth")));
                     }
                 }
-
-                let remaining = deadline - Instant::now();
-                thread::sleep(remaining);
             }
         });
 
         Ok(())
     }
 }
+
+/// How often the sensor can be read with `settings`, a reading waits for two conversions
+fn period(settings: &DepthSensorSettings) -> Duration {
+    PERIOD.max(settings.oversampling.conversion_time() * 2 + READ_OVERHEAD)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Water {
+    Fresh,
+    Salt,
+    Custom,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CalibrationRecord {
+    surface_pressure: f64,
+    water: Water,
+    /// Only set for custom water
+    density: Option<f64>,
+    /// Nanoseconds since the unix epoch, exact so requests can be matched after a restart
+    tare_request: Option<u64>,
+}
+
+/// Reads the persisted calibration, defaults if there is none
+/// Columns are `surface_pressure,water,density,tare_request` with the pressure in mbar and the
+/// density in kg/m³
+fn read_depth_calibration() -> anyhow::Result<DepthCalibration> {
+    if !Path::new(CALIBRATION_FILE).exists() {
+        info!("No depth calibration found, using defaults");
+        return Ok(DepthCalibration::default());
+    }
+
+    let reader = csv::Reader::from_path(CALIBRATION_FILE).context("Read depth calibration")?;
+    let record: CalibrationRecord = reader
+        .into_deserialize()
+        .next()
+        .context("Empty depth calibration")?
+        .context("Parse depth calibration")?;
+
+    let water_type = match (record.water, record.density) {
+        (Water::Fresh, _) => WaterType::Fresh,
+        (Water::Salt, _) => WaterType::Salt,
+        (Water::Custom, Some(density)) => WaterType::Custom(density),
+        (Water::Custom, None) => bail!("Custom water without a density"),
+    };
+
+    Ok(DepthCalibration {
+        surface_pressure: Mbar(record.surface_pressure),
+        water_type,
+        tare_request: record
+            .tare_request
+            .map(|nanos| SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos)),
+    })
+}
+
+fn write_depth_calibration(calibration: &DepthCalibration) -> anyhow::Result<()> {
+    let (water, density) = match calibration.water_type {
+        WaterType::Fresh => (Water::Fresh, None),
+        WaterType::Salt => (Water::Salt, None),
+        WaterType::Custom(density) => (Water::Custom, Some(density)),
+    };
+
+    let record = CalibrationRecord {
+        surface_pressure: calibration.surface_pressure.0,
+        water,
+        density,
+        tare_request: calibration.tare_request.map(|time| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64
+        }),
+    };
+
+    let mut writer = csv::Writer::from_path(CALIBRATION_FILE).context("Open depth calibration")?;
+    writer
+        .serialize(record)
+        .context("Write depth calibration")?;
+    writer.flush().context("Flush depth calibration")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::Oversampling;
+
+    use super::*;
+
+    #[test]
+    fn period_fits_conversions() {
+        assert_eq!(period(&DepthSensorSettings::default()), PERIOD);
+
+        for oversampling in [
+            Oversampling::Osr256,
+            Oversampling::Osr512,
+            Oversampling::Osr1024,
+            Oversampling::Osr2048,
+            Oversampling::Osr4096,
+            Oversampling::Osr8192,
+        ] {
+            let period = period(&DepthSensorSettings { oversampling });
+
+            assert!(period >= PERIOD);
+            assert!(period >= oversampling.conversion_time() * 2 + READ_OVERHEAD);
+        }
+    }
+}
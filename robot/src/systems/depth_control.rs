@@ -1,19 +1,29 @@
 use std::{
     sync::Arc,
-    thread::{self, Scope},
+    thread::Scope,
     time::{Duration, Instant},
 };
 
 use common::{
-    error::LogErrorExt,
-    store::{tokens, Store},
-    types::{DepthControlMode, DepthCorrection, Movement, Percent, PidConfig, PidController},
+    store::{tokens, Store, UpdateCallback},
+    types::{
+        DepthControlMode, DepthCorrection, Movement, Percent, PidConfig, PidController, SensorId,
+    },
 };
-use crossbeam::channel::bounded;
+use crossbeam::channel::select;
 use glam::{Quat, Vec3};
-use tracing::{span, warn, Level};
+use tracing::{span, Level};
 
-use crate::{event::Event, events::EventHandle, systems::stop, SystemId};
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{
+        clock,
+        scheduler::{self, Tick},
+        sensor_health::FaultLatch,
+    },
+    SystemId,
+};
 
 use super::System;
 
@@ -36,154 +46,143 @@ impl System for DepthControlSystem {
     ) -> anyhow::Result<()> {
         let listner = events.take_listner().unwrap();
 
-        let (tx, rx) = bounded(30);
+        let ticks = scheduler::schedule("Depth control", PERIOD);
+        let clock = clock::clock();
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Depth control thread");
+
+            let mut store = {
+                let mut events = events.clone();
+                Store::with_clock(
+                    move |update| {
+                        events.send(Event::Store(update));
+                    },
+                    clock.clone(),
+                )
+            };
 
-        {
-            let tx = tx.clone();
-            spawner.spawn(move || {
-                span!(Level::INFO, "Depth control watcher thread");
+            let mut controller = DepthController::default();
+
+            loop {
+                let event = select! {
+                    recv(listner) -> event => event.map(DepthControlEvent::Event),
+                    recv(ticks) -> tick => tick.map(DepthControlEvent::Tick),
+                };
+                let Ok(event) = event else {
+                    return;
+                };
 
-                for event in listner {
-                    match &*event {
-                        Event::Store(_) | Event::SyncStore | Event::ResetForignStore => {
-                            tx.try_send(DepthControlEvent::Event(event))
-                                .log_error("Send Event");
+                match event {
+                    DepthControlEvent::Event(event) => match &*event {
+                        Event::SyncStore => {
+                            store.refresh();
+                        }
+                        Event::ResetForignStore => {
+                            store.reset_shared();
+                        }
+                        Event::Store(update) => {
+                            store.handle_update_shared(update);
                         }
                         Event::Exit => {
-                            tx.try_send(DepthControlEvent::Exit).log_error("Send Exit");
                             return;
                         }
                         _ => {}
+                    },
+                    DepthControlEvent::Tick(tick) => {
+                        controller.tick(&mut store, tick.scheduled());
                     }
                 }
-            });
-        }
+            }
+        });
 
-        {
-            let tx = tx;
-            spawner.spawn(move || {
-                span!(Level::INFO, "Depth control tick thread");
+        Ok(())
+    }
+}
 
-                let mut deadline = Instant::now() + PERIOD;
+enum DepthControlEvent {
+    Event(Arc<Event>),
+    Tick(Tick),
+}
 
-                while !stop::world_stopped() {
-                    tx.try_send(DepthControlEvent::Tick).log_error("Send tick");
+struct DepthController {
+    pid: PidController,
+    fault_latch: FaultLatch<DepthControlMode>,
+}
 
-                    let remaining = deadline - Instant::now();
-                    if !remaining.is_zero() {
-                        thread::sleep(remaining);
-                    } else {
-                        warn!("Behind schedual");
-                    }
-                    deadline += PERIOD;
-                }
-            });
+impl Default for DepthController {
+    fn default() -> Self {
+        Self {
+            pid: PidController::new(PERIOD),
+            // Compass faults do not matter, only the vertical comes from orientation
+            fault_latch: FaultLatch::new(
+                "Depth hold",
+                &[SensorId::Depth, SensorId::Inertial],
+                tokens::DEPTH_CONTROL_DISENGAGED,
+            ),
         }
+    }
+}
 
-        {
-            let rx = rx;
-            spawner.spawn(move || {
-                span!(Level::INFO, "Depth control fusion thread");
+impl DepthController {
+    fn tick<C: UpdateCallback>(&mut self, store: &mut Store<C>, scheduled: Instant) {
+        let mode = store.get(&tokens::DEPTH_CONTROL_MODE).map(|it| *it);
+        let engaged = matches!(mode, Some(DepthControlMode::Enabled(_)));
+        // A latched fault stands in for the surface disabling the hold
+        let mode = if self.fault_latch.disengaged(store, mode, engaged) {
+            Some(DepthControlMode::Disabled)
+        } else {
+            mode
+        };
 
-                let mut store = {
-                    let mut events = events.clone();
-                    Store::new(move |update| {
-                        events.send(Event::Store(update));
-                    })
-                };
+        if let (
+            Some(DepthControlMode::Enabled(depth_target)),
+            Some(depth_observed),
+            Some(orientation),
+        ) = (
+            mode,
+            store.get(&tokens::RAW_DEPTH),
+            store.get(&tokens::ORIENTATION),
+        ) {
+            let depth_error = depth_target.0 - depth_observed.depth.0;
 
-                let mut depth_controller = PidController::new(PERIOD);
-
-                for event in rx {
-                    match event {
-                        DepthControlEvent::Event(event) => match &*event {
-                            Event::SyncStore => {
-                                store.refresh();
-                            }
-                            Event::ResetForignStore => {
-                                store.reset_shared();
-                            }
-                            Event::Store(update) => {
-                                store.handle_update_shared(update);
-                            }
-                            _ => unreachable!(),
-                        },
-                        DepthControlEvent::Tick => {
-                            if let (Some(mode), Some(depth_observed), Some(orientation)) = (
-                                store.get(&tokens::DEPTH_CONTROL_MODE),
-                                store.get(&tokens::RAW_DEPTH),
-                                store.get(&tokens::ORIENTATION),
-                            ) {
-                                if let DepthControlMode::Enabled(depth_target) = *mode {
-                                    let depth_error = depth_target.0 - depth_observed.depth.0;
-
-                                    let config = store
-                                        .get(&tokens::DEPTH_CONTROL_PID_OVERRIDE)
-                                        .map(|it| *it)
-                                        .unwrap_or(PID_CONFIG);
-                                    let depth_pid_result =
-                                        depth_controller.update(depth_error, config);
-
-                                    let max_correction = 1.0;
-                                    let depth_corection = depth_pid_result
-                                        .correction()
-                                        .clamp(-max_correction, max_correction);
-
-                                    store.insert(&tokens::DEPTH_CONTROL_RESULT, depth_pid_result);
-                                    store.insert(
-                                        &tokens::DEPTH_CONTROL_CORRECTION,
-                                        DepthCorrection {
-                                            depth: depth_pid_result.correction(),
-                                        },
-                                    );
-
-                                    let orientation: Quat = orientation.0.into();
-                                    let correction_vec = orientation.inverse()
-                                        * Vec3::new(0.0, 0.0, -depth_corection as f32);
-
-                                    store.insert(
-                                        &tokens::MOVEMENT_DEPTH,
-                                        Movement {
-                                            x: Percent::new(high_pass(
-                                                correction_vec.x as f64,
-                                                0.05,
-                                            )),
-                                            y: Percent::new(high_pass(
-                                                correction_vec.y as f64,
-                                                0.05,
-                                            )),
-                                            z: Percent::new(high_pass(
-                                                correction_vec.z as f64,
-                                                0.05,
-                                            )),
-                                            ..Movement::default()
-                                        },
-                                    );
-                                } else {
-                                    depth_controller = PidController::new(PERIOD);
-                                    store.remove(&tokens::MOVEMENT_DEPTH);
-                                }
-                            } else {
-                                depth_controller = PidController::new(PERIOD);
-                                store.remove(&tokens::MOVEMENT_DEPTH);
-                            }
-                        }
-                        DepthControlEvent::Exit => {
-                            return;
-                        }
-                    }
-                }
-            });
-        }
+            let config = store
+                .get(&tokens::DEPTH_CONTROL_PID_OVERRIDE)
+                .map(|it| *it)
+                .unwrap_or(PID_CONFIG);
+            let depth_pid_result = self.pid.update_at(depth_error, config, scheduled);
 
-        Ok(())
-    }
-}
+            let max_correction = 1.0;
+            let depth_corection = depth_pid_result
+                .correction()
+                .clamp(-max_correction, max_correction);
 
-enum DepthControlEvent {
-    Event(Arc<Event>),
-    Tick,
-    Exit,
+            store.insert(&tokens::DEPTH_CONTROL_RESULT, depth_pid_result);
+            store.insert(
+                &tokens::DEPTH_CONTROL_CORRECTION,
+                DepthCorrection {
+                    depth: depth_pid_result.correction(),
+                },
+            );
+
+            let orientation: Quat = orientation.0.into();
+            let correction_vec =
+                orientation.inverse() * Vec3::new(0.0, 0.0, -depth_corection as f32);
+
+            store.insert(
+                &tokens::MOVEMENT_DEPTH,
+                Movement {
+                    x: Percent::new(high_pass(correction_vec.x as f64, 0.05)),
+                    y: Percent::new(high_pass(correction_vec.y as f64, 0.05)),
+                    z: Percent::new(high_pass(correction_vec.z as f64, 0.05)),
+                    ..Movement::default()
+                },
+            );
+        } else {
+            self.pid = PidController::new(PERIOD);
+            store.remove(&tokens::MOVEMENT_DEPTH);
+        }
+    }
 }
 
 fn high_pass(value: f64, threshold: f64) -> f64 {
@@ -193,3 +192,94 @@ fn high_pass(value: f64, threshold: f64) -> f64 {
         0.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use common::{
+        clock::{Clock, ManualClock},
+        store::create_update,
+        types::{
+            Celsius, DepthFrame, HoldDisengaged, Mbar, Meters, Orientation, SensorFault, WaterType,
+        },
+    };
+    use fxhash::FxHashMap as HashMap;
+
+    use super::*;
+
+    /// At the surface
+    fn depth() -> DepthFrame {
+        DepthFrame {
+            sampled: SystemTime::now(),
+            depth: Meters(0.0),
+            altitude: Meters(0.0),
+            pressure: Mbar(1013.25),
+            temperature: Celsius(15.0),
+            surface_pressure: Mbar(1013.25),
+            fluid_density: 997.0,
+            water_type: WaterType::Fresh,
+        }
+    }
+
+    fn health(depth: Vec<SensorFault>) -> HashMap<SensorId, Vec<SensorFault>> {
+        let mut health = HashMap::default();
+        health.insert(SensorId::Inertial, vec![]);
+        health.insert(SensorId::Depth, depth);
+        health.insert(SensorId::Magnetometer, vec![]);
+
+        health
+    }
+
+    #[test]
+    fn sensor_fault_stops_output_until_mode_changes() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+        let mut controller = DepthController::default();
+
+        store.handle_update_shared(&create_update(&tokens::RAW_DEPTH, depth()));
+        store.handle_update_shared(&create_update(&tokens::ORIENTATION, Orientation::default()));
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health(vec![])));
+        let hold = DepthControlMode::Enabled(Meters(2.0));
+        store.handle_update_shared(&create_update(&tokens::DEPTH_CONTROL_MODE, hold));
+
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_DEPTH).is_some());
+
+        store.handle_update_shared(&create_update(
+            &tokens::SENSOR_HEALTH,
+            health(vec![SensorFault::Stale]),
+        ));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_DEPTH).is_none());
+        assert_eq!(
+            store.get(&tokens::DEPTH_CONTROL_DISENGAGED).as_deref(),
+            Some(&HoldDisengaged {
+                sensor: SensorId::Depth,
+                fault: SensorFault::Stale,
+            })
+        );
+
+        // Stays off after the fault clears, the surface still has the old hold set
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health(vec![])));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_DEPTH).is_none());
+
+        // The surface drops the hold
+        store.handle_update_shared(&create_update(
+            &tokens::DEPTH_CONTROL_MODE,
+            DepthControlMode::Disabled,
+        ));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::DEPTH_CONTROL_DISENGAGED).is_none());
+        assert!(store.get(&tokens::MOVEMENT_DEPTH).is_none());
+
+        store.handle_update_shared(&create_update(&tokens::DEPTH_CONTROL_MODE, hold));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_DEPTH).is_some());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{thread::Scope, time::Duration};
+
+use common::{
+    store::{self, tokens},
+    types::{SealTestReport, SealTestState},
+};
+use tracing::{info, span, warn, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    peripheral::bmp280::Bmp280,
+    systems::{clock, scheduler, seal_test::SealTest},
+    SystemId,
+};
+
+use super::System;
+
+const PERIOD: Duration = Duration::from_millis(100);
+/// Only publish every nth report while a seal test runs to keep network traffic down
+const REPORT_DIVISOR: usize = 10;
+
+/// Monitors the inside of the electronics enclosure and runs vacuum seal tests
+pub struct EnclosureSystem;
+
+impl System for EnclosureSystem {
+    const ID: SystemId = SystemId::Enclosure;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listener = events.take_listner().unwrap();
+        let ticks = scheduler::schedule("Enclosure", PERIOD);
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Enclosure monitor thread");
+
+            let barometer = Bmp280::new(Bmp280::I2C_BUS, Bmp280::I2C_ADDRESS);
+            let mut barometer = match barometer {
+                Ok(barometer) => barometer,
+                Err(err) => {
+                    events.send(Event::Error(err.context("BMP280")));
+                    return;
+                }
+            };
+
+            let clock = clock::clock();
+
+            let mut seal_test: Option<SealTest> = None;
+            let mut last_request = None;
+            let mut last_report: Option<SealTestReport> = None;
+            let mut tick_counter = 0;
+
+            for _tick in ticks {
+                for event in listener.try_iter() {
+                    match &*event {
+                        Event::Store(update) => {
+                            if let Some(request) = store::handle_update(&tokens::SEAL_TEST, update)
+                            {
+                                // Requests are resent when the store syncs
+                                if last_request == Some(request.requested) {
+                                    continue;
+                                }
+                                last_request = Some(request.requested);
+
+                                info!("Starting seal test");
+                                let test = SealTest::new(request.config, clock.now());
+
+                                let report = test.progress();
+                                let update =
+                                    store::create_update(&tokens::SEAL_TEST_REPORT, report.clone());
+                                events.send(Event::Store(update));
+
+                                seal_test = Some(test);
+                                last_report = Some(report);
+                            } else if update.0 == tokens::SEAL_TEST.0 && update.1.is_none() {
+                                if let Some(test) = seal_test.take() {
+                                    info!("Seal test cancelled");
+
+                                    let report = test.abort("Cancelled");
+                                    let update = store::create_update(
+                                        &tokens::SEAL_TEST_REPORT,
+                                        report.clone(),
+                                    );
+                                    events.send(Event::Store(update));
+
+                                    last_report = Some(report);
+                                }
+                            }
+                        }
+                        Event::SyncStore => {
+                            if let Some(report) = &last_report {
+                                let update =
+                                    store::create_update(&tokens::SEAL_TEST_REPORT, report.clone());
+                                events.send(Event::Store(update));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
+                let frame = match barometer.read_frame() {
+                    Ok(frame) => frame,
+                    Err(err) => {
+                        events.send(Event::Error(err.context("Could not read enclosure")));
+                        continue;
+                    }
+                };
+
+                let update = store::create_update(&tokens::ENCLOSURE, frame);
+                events.send(Event::Store(update));
+
+                let Some(test) = &mut seal_test else {
+                    continue;
+                };
+
+                let report = test.update(clock.now(), &frame);
+                let changed = last_report
+                    .as_ref()
+                    .map(|last| last.state != report.state)
+                    .unwrap_or(true);
+
+                match &report.state {
+                    SealTestState::Settling | SealTestState::Measuring => {}
+                    SealTestState::Passed => {
+                        info!("Seal test passed, pressure rose {}/min", report.rise_rate);
+                        seal_test = None;
+                    }
+                    SealTestState::Failed => {
+                        warn!("Seal test failed, pressure rose {}/min", report.rise_rate);
+                        seal_test = None;
+                    }
+                    SealTestState::Aborted(reason) => {
+                        warn!("Seal test aborted: {reason}");
+                        seal_test = None;
+                    }
+                }
+
+                tick_counter += 1;
+                if changed || tick_counter % REPORT_DIVISOR == 0 {
+                    let update = store::create_update(&tokens::SEAL_TEST_REPORT, report.clone());
+                    events.send(Event::Store(update));
+                }
+
+                last_report = Some(report);
+            }
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use common::types::{EscCalibration, EscSweepPhase, EscSweepState, MotorFrame, MotorId};
+use fxhash::FxHashMap as HashMap;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::peripheral::motor::{Motor, MOTOR_IDS};
+
+const CALIBRATION_FILE: &str = "esc_calibration.csv";
+/// Only publish every nth sweep tick to keep network traffic down
+const SWEEP_PUBLISH_DIVISOR: usize = 10;
+
+#[derive(Deserialize, Debug)]
+struct CalibrationRecord {
+    motor: MotorId,
+    min: u64,
+    center: u64,
+    max: u64,
+    deadband: u64,
+    reverse: bool,
+}
+
+/// Reads per channel calibration, channels not in the file keep their defaults
+/// Columns are `motor,min,center,max,deadband,reverse` with pulses in microseconds
+pub fn read_esc_calibration() -> anyhow::Result<HashMap<MotorId, EscCalibration>> {
+    if !Path::new(CALIBRATION_FILE).exists() {
+        info!("No esc calibration found, using defaults");
+        return parse_esc_calibration(&[][..]);
+    }
+
+    let file = File::open(CALIBRATION_FILE).context("Read esc calibration")?;
+    parse_esc_calibration(file)
+}
+
+fn parse_esc_calibration(csv: impl Read) -> anyhow::Result<HashMap<MotorId, EscCalibration>> {
+    let mut calibration: HashMap<MotorId, EscCalibration> = MOTOR_IDS
+        .into_iter()
+        .map(|motor_id| (motor_id, Motor::from(motor_id).calibration()))
+        .collect();
+
+    let reader = csv::Reader::from_reader(csv);
+    for result in reader.into_deserialize() {
+        let record: CalibrationRecord = result.context("Parse calibration record")?;
+
+        calibration.insert(
+            record.motor,
+            EscCalibration {
+                min: Duration::from_micros(record.min),
+                center: Duration::from_micros(record.center),
+                max: Duration::from_micros(record.max),
+                deadband: Duration::from_micros(record.deadband),
+                reverse: record.reverse,
+            },
+        );
+    }
+
+    Ok(calibration)
+}
+
+/// Applies any calibration for the motor on top of its defaults
+pub fn calibrated_motor(
+    motor_id: MotorId,
+    calibration: &HashMap<MotorId, EscCalibration>,
+) -> Motor {
+    let motor = Motor::from(motor_id);
+
+    match calibration.get(&motor_id) {
+        Some(calibration) => motor.calibrated(calibration),
+        None => motor,
+    }
+}
+
+/// Walks one channel away from center until the operator marks where thrust begins
+pub struct EscSweep {
+    motor: MotorId,
+    center: Duration,
+    rate: Duration,
+    limit: Duration,
+
+    phase: EscSweepPhase,
+    offset: Duration,
+    last_tick: Instant,
+    tick_counter: usize,
+
+    forward_start: Option<Duration>,
+    reverse_start: Option<Duration>,
+}
+
+pub enum SweepTick {
+    /// Pulse to output this tick, includes a state update when one should be published
+    Running((MotorId, MotorFrame), Option<EscSweepState>),
+    Finished(EscSweepState),
+}
+
+impl EscSweep {
+    pub fn new(
+        motor: MotorId,
+        calibration: &EscCalibration,
+        rate: Duration,
+        limit: Duration,
+        now: Instant,
+    ) -> Self {
+        Self {
+            motor,
+            center: calibration.center,
+            rate,
+            limit,
+            phase: EscSweepPhase::Forward,
+            offset: Duration::ZERO,
+            last_tick: now,
+            tick_counter: 0,
+            forward_start: None,
+            reverse_start: None,
+        }
+    }
+
+    pub fn motor(&self) -> MotorId {
+        self.motor
+    }
+
+    fn pulse(&self) -> Duration {
+        match self.phase {
+            EscSweepPhase::Forward => self.center + self.offset,
+            EscSweepPhase::Reverse => self.center.saturating_sub(self.offset),
+            _ => self.center,
+        }
+    }
+
+    pub fn state(&self) -> EscSweepState {
+        EscSweepState {
+            motor: self.motor,
+            phase: self.phase.clone(),
+            pulse: self.pulse(),
+            forward_start: self.forward_start,
+            reverse_start: self.reverse_start,
+        }
+    }
+
+    pub fn abort(&self, reason: impl Into<String>) -> EscSweepState {
+        EscSweepState {
+            phase: EscSweepPhase::Aborted(reason.into()),
+            ..self.state()
+        }
+    }
+
+    /// Records the current pulse as the start of thrust and moves to the next direction
+    pub fn mark(&mut self) -> EscSweepState {
+        match self.phase {
+            EscSweepPhase::Forward => {
+                self.forward_start = Some(self.pulse());
+                self.next_phase();
+            }
+            EscSweepPhase::Reverse => {
+                self.reverse_start = Some(self.pulse());
+                self.next_phase();
+            }
+            _ => {}
+        }
+
+        self.state()
+    }
+
+    fn next_phase(&mut self) {
+        self.phase = match self.phase {
+            EscSweepPhase::Forward => EscSweepPhase::Reverse,
+            _ => EscSweepPhase::Complete,
+        };
+        self.offset = Duration::ZERO;
+    }
+
+    pub fn tick(&mut self, now: Instant) -> SweepTick {
+        let elapsed = now.saturating_duration_since(self.last_tick);
+        self.last_tick = now;
+
+        let mut changed = false;
+
+        if let EscSweepPhase::Forward | EscSweepPhase::Reverse = self.phase {
+            self.offset += self.rate.mul_f64(elapsed.as_secs_f64());
+
+            // Nothing was marked, leave this direction empty
+            if self.offset > self.limit {
+                self.next_phase();
+                changed = true;
+            }
+        }
+
+        if let EscSweepPhase::Complete | EscSweepPhase::Aborted(_) = self.phase {
+            return SweepTick::Finished(self.state());
+        }
+
+        self.tick_counter += 1;
+        let publish = changed || self.tick_counter.is_multiple_of(SWEEP_PUBLISH_DIVISOR);
+
+        SweepTick::Running(
+            (self.motor, MotorFrame::Raw(self.pulse())),
+            publish.then(|| self.state()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::clock::{Clock, ManualClock};
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    fn sweep(clock: &ManualClock) -> EscSweep {
+        let calibration = Motor::from(MotorId::FrontLeftTop).calibration();
+
+        // 1µs every 10ms tick, 100µs in a second
+        EscSweep::new(
+            MotorId::FrontLeftTop,
+            &calibration,
+            Duration::from_micros(100),
+            Duration::from_micros(50),
+            clock.now(),
+        )
+    }
+
+    fn run(sweep: &mut EscSweep, clock: &ManualClock, ticks: usize) -> Vec<SweepTick> {
+        (0..ticks)
+            .map(|_| {
+                clock.advance(TICK);
+                sweep.tick(clock.now())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sweep_steps_away_from_center() {
+        let clock = Arc::new(ManualClock::new());
+        let mut sweep = sweep(&clock);
+
+        let ticks = run(&mut sweep, &clock, 20);
+        let SweepTick::Running((motor, MotorFrame::Raw(pulse)), _) = &ticks[19] else {
+            panic!("Sweep should still be running");
+        };
+
+        assert_eq!(*motor, MotorId::FrontLeftTop);
+        assert_eq!(*pulse, Duration::from_micros(1520));
+
+        // Only every nth tick is published
+        let published = ticks
+            .iter()
+            .filter(|it| matches!(it, SweepTick::Running(_, Some(_))))
+            .count();
+        assert_eq!(published, 20 / SWEEP_PUBLISH_DIVISOR);
+    }
+
+    #[test]
+    fn sweep_records_thrust_onset() {
+        let clock = Arc::new(ManualClock::new());
+        let mut sweep = sweep(&clock);
+
+        run(&mut sweep, &clock, 30);
+        let state = sweep.mark();
+        assert_eq!(state.phase, EscSweepPhase::Reverse);
+        assert_eq!(state.forward_start, Some(Duration::from_micros(1530)));
+
+        run(&mut sweep, &clock, 25);
+        let state = sweep.mark();
+        assert_eq!(state.phase, EscSweepPhase::Complete);
+        assert_eq!(state.reverse_start, Some(Duration::from_micros(1475)));
+
+        assert!(matches!(
+            sweep.tick(clock.now()),
+            SweepTick::Finished(EscSweepState {
+                phase: EscSweepPhase::Complete,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn sweep_past_limit_leaves_direction_empty() {
+        let clock = Arc::new(ManualClock::new());
+        let mut sweep = sweep(&clock);
+
+        let ticks = run(&mut sweep, &clock, 51);
+        let SweepTick::Running(_, Some(state)) = &ticks[50] else {
+            panic!("Phase change should be published");
+        };
+
+        assert_eq!(state.phase, EscSweepPhase::Reverse);
+        assert_eq!(state.forward_start, None);
+        assert_eq!(state.pulse, Duration::from_micros(1500));
+    }
+
+    #[test]
+    fn parses_calibration_csv() {
+        let csv = "motor,min,center,max,deadband,reverse\n\
+                   FrontLeftTop,1050,1510,1950,60,true\n";
+        let calibration = parse_esc_calibration(csv.as_bytes()).unwrap();
+
+        assert_eq!(
+            calibration[&MotorId::FrontLeftTop],
+            EscCalibration {
+                min: Duration::from_micros(1050),
+                center: Duration::from_micros(1510),
+                max: Duration::from_micros(1950),
+                deadband: Duration::from_micros(60),
+                reverse: true,
+            }
+        );
+
+        // Channels not in the file keep their defaults
+        assert_eq!(
+            calibration[&MotorId::Camera1],
+            Motor::from(MotorId::Camera1).calibration()
+        );
+        assert_eq!(calibration.len(), MOTOR_IDS.len());
+    }
+
+    #[test]
+    fn rejects_malformed_calibration() {
+        let csv = "motor,min,center,max,deadband,reverse\nFrontLeftTop,1050,oops,1950,60,true\n";
+
+        assert!(parse_esc_calibration(csv.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, Weak,
+};
+
+use anyhow::anyhow;
+use tracing::{error, warn};
+
+use crate::peripheral::pca9685::Pca9685;
+
+static EMERGENCY_STOPPED: AtomicBool = AtomicBool::new(false);
+static PWM_CONTROLLER: Mutex<Option<Weak<Mutex<Pca9685>>>> = Mutex::new(None);
+
+/// Lets `trigger` reach the pwm controller without going through the motor thread
+/// Only a weak reference is kept so the controller still stops on drop
+pub fn register_pwm_controller(controller: &Arc<Mutex<Pca9685>>) {
+    match PWM_CONTROLLER.lock() {
+        Ok(mut registered) => *registered = Some(Arc::downgrade(controller)),
+        Err(_) => error!("Could not register pwm controller for emergency stop"),
+    }
+}
+
+/// Latches the emergency stop and disables all pwm outputs
+pub fn trigger() -> anyhow::Result<()> {
+    EMERGENCY_STOPPED.store(true, Ordering::SeqCst);
+    warn!("Emergency stop triggered");
+
+    let controller = PWM_CONTROLLER
+        .lock()
+        .map_err(|_| anyhow!("Emergency stop registry poisoned"))?
+        .as_ref()
+        .and_then(Weak::upgrade);
+
+    if let Some(controller) = controller {
+        // Keep going if the motor thread panicked while holding the lock
+        let mut controller = controller
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        controller.output_disable();
+    }
+
+    Ok(())
+}
+
+/// Clears the latch, motors stay disarmed until the next arm request
+pub fn reset() {
+    EMERGENCY_STOPPED.store(false, Ordering::SeqCst);
+    warn!("Emergency stop reset");
+}
+
+pub fn emergency_stopped() -> bool {
+    EMERGENCY_STOPPED.load(Ordering::SeqCst)
+}
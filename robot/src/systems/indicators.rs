@@ -1,15 +1,10 @@
-use std::{
-    thread::{self, Scope},
-    time::Duration,
-    usize,
-};
+use std::{sync::Arc, thread::Scope, time::Duration};
 
 use common::{
-    error::LogErrorExt,
     store::{self, tokens},
     types::RobotStatus,
 };
-use crossbeam::channel::{bounded, TrySendError};
+use crossbeam::channel::select;
 use rgb::RGB8;
 use tracing::{span, Level};
 
@@ -17,12 +12,15 @@ use crate::{
     event::Event,
     events::EventHandle,
     peripheral::neopixel::{self, NeoPixel},
-    systems::stop,
+    systems::scheduler::{self, Tick},
     SystemId,
 };
 
 use super::System;
 
+const PERIOD: Duration = Duration::from_millis(10);
+const EFFECT_LENGTH: Duration = Duration::from_millis(500);
+
 pub struct IndicatorsSystem;
 
 impl System for IndicatorsSystem {
@@ -33,32 +31,7 @@ impl System for IndicatorsSystem {
         spawner: &'scope Scope<'scope, '_>,
     ) -> anyhow::Result<()> {
         let listener = events.take_listner().unwrap();
-        let (tx, rx) = bounded(1);
-
-        spawner.spawn(move || {
-            span!(Level::INFO, "Indicator controller");
-
-            for event in listener {
-                match &*event {
-                    Event::Store(update) => {
-                        if let Some(status) = store::handle_update(&tokens::STATUS, update) {
-                            let rst = tx.try_send(status);
-                            match rst {
-                                Ok(()) => {}
-                                Err(TrySendError::Full(_)) => {}
-                                error @ Err(TrySendError::Disconnected(_)) => {
-                                    error.log_error("Send new led state");
-                                }
-                            }
-                        }
-                    }
-                    Event::Exit => {
-                        return;
-                    }
-                    _ => {}
-                }
-            }
-        });
+        let ticks = scheduler::schedule("Indicators", PERIOD);
 
         spawner.spawn(move || {
             span!(Level::INFO, "RGB LED thread");
@@ -67,39 +40,60 @@ impl System for IndicatorsSystem {
                 NeoPixel::new(NeoPixel::SPI_BUS, NeoPixel::SPI_SELECT, NeoPixel::SPI_CLOCK)
                     .expect("Open neopixel");
 
-            let interval = Duration::from_millis(10);
-            let effect_length = Duration::from_millis(500);
-            let steps = (effect_length.as_secs_f64() / interval.as_secs_f64()) as usize;
+            let steps = (EFFECT_LENGTH.as_secs_f64() / PERIOD.as_secs_f64()) as usize;
 
             let mut state = RobotStatus::NoPeer;
             let mut tick_counter = 0;
             let mut last_color = RGB8::default();
+            let mut step = 0;
+            let mut next_state = None;
+
+            loop {
+                let message = select! {
+                    recv(listener) -> event => event.map(Message::Event),
+                    recv(ticks) -> tick => tick.map(Message::Tick),
+                };
+                let Ok(message) = message else {
+                    return;
+                };
+
+                match message {
+                    Message::Event(event) => match &*event {
+                        Event::Store(update) => {
+                            if let Some(status) = store::handle_update(&tokens::STATUS, update) {
+                                next_state = Some(*status);
+                            }
+                        }
+                        Event::Exit => {
+                            return;
+                        }
+                        _ => {}
+                    },
+                    Message::Tick(_tick) => {
+                        let next_color = neopixel::correct_color(state.color(tick_counter));
 
-            while !stop::world_stopped() {
-                let next_color = neopixel::correct_color(state.color(tick_counter));
+                        // Fade when the color changes, otherwise hold it for as long as a fade takes
+                        if next_color != last_color {
+                            let color =
+                                lerp_colors(last_color, next_color, step as f64 / steps as f64);
 
-                if next_color != last_color {
-                    for step in 0..steps {
-                        if stop::world_stopped() {
-                            return;
+                            neopixel.write_color_raw(color).expect("Write to rgb led");
                         }
 
-                        let color = lerp_colors(last_color, next_color, step as f64 / steps as f64);
+                        step += 1;
+                        if step < steps {
+                            continue;
+                        }
+                        step = 0;
 
-                        neopixel.write_color_raw(color).expect("Write to rgb led");
+                        if let Some(new_state) = next_state.take() {
+                            state = new_state;
+                        }
 
-                        thread::sleep(interval);
+                        tick_counter += 1;
+                        last_color = next_color;
                     }
-                } else {
-                    thread::sleep(effect_length);
-                }
-
-                if let Ok(new_state) = rx.try_recv() {
-                    state = *new_state;
                 }
-
-                tick_counter += 1;
-                last_color = next_color;
             }
         });
 
@@ -107,6 +101,11 @@ impl System for IndicatorsSystem {
     }
 }
 
+enum Message {
+    Event(Arc<Event>),
+    Tick(Tick),
+}
+
 trait StatusColorExt {
     fn color(&self, tick_id: usize) -> RGB8;
 }
@@ -123,6 +122,18 @@ impl StatusColorExt for RobotStatus {
             Self::Moving(speed) => {
                 lerp_colors(RGB8::new(0, 0, 0), RGB8::new(255, 255, 255), speed.get())
             }
+            Self::EmergencyStopped => {
+                let red = RGB8::new(255, 0, 0);
+                red * (tick_id % 2) as u8
+            }
+            Self::Degraded => {
+                let yellow = RGB8::new(255, 192, 0);
+                yellow * (tick_id % 3).min(1) as u8
+            }
+            Self::SealTestFailed => {
+                let orange = RGB8::new(255, 96, 0);
+                orange * (tick_id % 2) as u8
+            }
         };
 
         color / 3
@@ -1,21 +1,34 @@
 use std::{
-    iter,
-    thread::{self, Scope},
+    mem,
+    thread::Scope,
     time::{Duration, Instant},
 };
 
-use tracing::{span, Level};
+use anyhow::anyhow;
+use common::{
+    store::{self, tokens},
+    types::ImuSettings,
+};
+use tracing::{info, span, warn, Level};
 
 use crate::{
-    event::{Event, SensorBatch},
+    event::{Event, Sample, SensorBatch},
     events::EventHandle,
-    peripheral::{icm20602::Icm20602, mmc5983::Mcc5983},
-    systems::stop,
+    peripheral::{ak09915::Ak09915, icm20602::Icm20602, mmc5983::Mcc5983},
+    systems::{clock, stop},
     SystemId,
 };
 
 use super::System;
 
+/// How much data is read from the imu fifo at once
+const BURST_LENGTH: Duration = Duration::from_millis(20);
+/// How often the magnetometer is read
+const MAG_PERIOD: Duration = Duration::from_millis(10);
+/// How long to wait for a sample before reading the fifo anyway
+const DATA_READY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Reads the imu in bursts as its fifo fills and the magnetometer alongside it
 pub struct InertialSystem;
 
 impl System for InertialSystem {
@@ -25,12 +38,18 @@ impl System for InertialSystem {
         mut events: EventHandle,
         spawner: &'scope Scope<'scope, '_>,
     ) -> anyhow::Result<()> {
-        let _ = events.take_listner();
+        let listener = events.take_listner().unwrap();
 
         spawner.spawn(move || {
             span!(Level::INFO, "Inertial sensor monitor thread");
 
-            let imu = Icm20602::new(Icm20602::SPI_BUS, Icm20602::SPI_SELECT, Icm20602::SPI_CLOCK);
+            let imu = Icm20602::new(
+                Icm20602::SPI_BUS,
+                Icm20602::SPI_SELECT,
+                Icm20602::SPI_CLOCK,
+                Icm20602::DATA_READY_PIN,
+                ImuSettings::default(),
+            );
             let mut imu = match imu {
                 Ok(imu) => imu,
                 Err(err) => {
@@ -48,69 +67,167 @@ impl System for InertialSystem {
                 }
             };
 
-            let interval = Duration::from_secs_f64(1.0 / 1000.0);
-            let imu_divisor = 1;
-            let mag_divisor = 10;
-            let brodcast_divisor = 20;
+            // The second compass is only there for redundancy, run without it if it fails
+            let secondary_mag = Ak09915::new(Ak09915::I2C_BUS, Ak09915::I2C_ADDRESS);
+            let mut secondary_mag = match secondary_mag {
+                Ok(secondary_mag) => Some(secondary_mag),
+                Err(err) => {
+                    events.send(Event::Error(err.context("AK09915")));
+                    None
+                }
+            };
+
+            let clock = clock::clock();
 
-            let mut inertial_buffer = Vec::with_capacity(20);
-            let mut mag_buffer = Vec::with_capacity(2);
+            let mut signals = 0;
+            let mut last_sample: Option<Instant> = None;
+            let mut total_dropped = 0;
+            let mut mag_buffer = Vec::new();
+            let mut secondary_mag_buffer = Vec::new();
 
-            let mut deadline = Instant::now();
-            let mut counter = 0;
             while !stop::world_stopped() {
-                deadline += interval;
+                for event in listener.try_iter() {
+                    if let Event::Store(update) = &*event {
+                        if update.0 == tokens::IMU_SETTINGS.0 {
+                            // Removing the settings restores the defaults
+                            let settings = store::handle_update(&tokens::IMU_SETTINGS, update)
+                                .map(|it| *it)
+                                .unwrap_or_default();
+                            if settings == imu.settings() {
+                                continue;
+                            }
+
+                            info!("Applying imu settings: {settings:?}");
+                            if let Err(err) = imu.apply_settings(settings) {
+                                events.send(Event::Error(err.context("Apply imu settings")));
+                            }
+
+                            // The fifo was cleared, the gap is not a drop
+                            signals = 0;
+                            last_sample = None;
+                        }
+                    }
+                }
 
-                if counter % imu_divisor == 0 {
-                    let rst = imu.read_frame();
+                let sample_period = imu.sample_period();
+                let burst_samples = samples_in(BURST_LENGTH, sample_period);
+                let mag_divisor = samples_in(MAG_PERIOD, sample_period);
 
-                    match rst {
-                        Ok(frame) => {
-                            inertial_buffer.push(frame);
-                        }
-                        Err(err) => {
-                            events.send(Event::Error(err.context("Could not read imu")));
-                        }
+                match imu.wait_data_ready(DATA_READY_TIMEOUT) {
+                    Ok(true) => {
+                        signals += 1;
+                    }
+                    Ok(false) => {
+                        events.send(Event::Error(anyhow!("No data ready signal from imu")));
+                        // Drain the fifo anyway so a lost interrupt cant stall the sensor
+                        signals = burst_samples;
+                    }
+                    Err(err) => {
+                        events.send(Event::Error(err.context("Could not wait for imu")));
+                        signals = burst_samples;
                     }
                 }
+                let now = clock.now();
 
-                if counter % mag_divisor == 0 {
+                if signals % mag_divisor == 0 {
                     let rst = mag.read_frame();
 
                     match rst {
                         Ok(frame) => {
-                            mag_buffer.push(frame);
+                            mag_buffer.push(Sample { time: now, frame });
                         }
                         Err(err) => {
                             events.send(Event::Error(err.context("Could not read mag")));
                         }
                     }
+
+                    if let Some(secondary_mag) = &mut secondary_mag {
+                        let rst = secondary_mag.read_frame();
+
+                        match rst {
+                            Ok(Some(frame)) => {
+                                secondary_mag_buffer.push(Sample { time: now, frame });
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                events.send(Event::Error(
+                                    err.context("Could not read secondary mag"),
+                                ));
+                            }
+                        }
+                    }
                 }
 
-                if counter % brodcast_divisor == 0 {
-                    if inertial_buffer.len() >= 20 && mag_buffer.len() >= 2 {
-                        let inertial_data = inertial_buffer.split_array_ref().0;
-                        let mag_data = mag_buffer.split_array_ref().0;
+                if signals < burst_samples {
+                    continue;
+                }
+                signals = 0;
+
+                let rst = imu.read_fifo();
+                let fifo = match rst {
+                    Ok(fifo) => fifo,
+                    Err(err) => {
+                        events.send(Event::Error(err.context("Could not read imu")));
+                        continue;
+                    }
+                };
 
-                        let batch = SensorBatch {
-                            inertial: *inertial_data,
-                            mag: *mag_data,
-                        };
-                        events
-                            .send_to(Event::SensorFrame(batch), iter::once(SystemId::Orientation));
+                if fifo.overflowed {
+                    warn!("Imu fifo overflowed");
+                }
 
-                        inertial_buffer.clear();
-                        mag_buffer.clear();
+                // The newest sample is the one that just signaled, the rest are spaced out before
+                // it at the sample rate
+                let count = fifo.frames.len();
+                let inertial: Vec<_> = fifo
+                    .frames
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, frame)| {
+                        let age = sample_period * (count - 1 - idx) as u32;
+                        let time = now.checked_sub(age).unwrap_or(now);
+
+                        Sample { time, frame }
+                    })
+                    .collect();
+
+                if let Some(newest) = inertial.last() {
+                    if let Some(last_sample) = last_sample {
+                        let elapsed = newest.time.saturating_duration_since(last_sample);
+                        let expected =
+                            (elapsed.as_secs_f64() / sample_period.as_secs_f64()).round() as usize;
+
+                        let dropped = expected.saturating_sub(count);
+                        if dropped > 0 {
+                            total_dropped += dropped;
+                            warn!("Dropped {dropped} imu samples, {total_dropped} total");
+                        }
                     }
+
+                    last_sample = Some(newest.time);
                 }
 
-                let remaining = deadline - Instant::now();
-                thread::sleep(remaining);
+                if inertial.is_empty() && mag_buffer.is_empty() && secondary_mag_buffer.is_empty() {
+                    continue;
+                }
 
-                counter += 1;
+                let batch = SensorBatch {
+                    inertial,
+                    mag: mem::take(&mut mag_buffer),
+                    secondary_mag: mem::take(&mut secondary_mag_buffer),
+                };
+                events.send_to(
+                    Event::SensorFrame(batch),
+                    [SystemId::Orientation, SystemId::BlackBox],
+                );
             }
         });
 
         Ok(())
     }
 }
+
+/// How many samples cover `length`, at least one
+fn samples_in(length: Duration, sample_period: Duration) -> usize {
+    ((length.as_secs_f64() / sample_period.as_secs_f64()).round() as usize).max(1)
+}
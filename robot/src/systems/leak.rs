@@ -1,82 +1,193 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    path::Path,
+    thread::Scope,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::Context;
-use common::store::{self, tokens};
-use rppal::gpio::{Gpio, Level, Trigger};
-
-use crate::{event::Event, SystemId};
+use common::{
+    store::{self, tokens},
+    types::{LeakProbeState, LeakResponse, Movement},
+};
+use fxhash::FxHashMap as HashMap;
+use rppal::gpio::{Gpio, InputPin};
+use serde::Deserialize;
+use tracing::{info, span, warn, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{clock, debounce::Debounce, scheduler},
+    SystemId,
+};
 
 use super::System;
 
+const PERIOD: Duration = Duration::from_millis(20);
+/// A probe has to read the same for this long before its state changes
+const DEBOUNCE: Duration = Duration::from_millis(100);
+const PROBES_FILE: &str = "leak_probes.csv";
+/// The navigator's leak input, used when no probes are configured
+const DEFAULT_PROBE: (&str, u8) = ("Enclosure", 27);
+
+/// Watches the leak probes and carries out the leak response
 pub struct LeakSystem;
 
+struct Probe {
+    name: String,
+    pin: InputPin,
+    debounce: Debounce,
+    state: LeakProbeState,
+}
+
 impl System for LeakSystem {
     const ID: SystemId = SystemId::Leak;
 
     fn start<'scope>(
-        mut events: crate::events::EventHandle,
-        spawner: &'scope std::thread::Scope<'scope, '_>,
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
     ) -> anyhow::Result<()> {
         let listener = events.take_listner().unwrap();
 
         let gpio = Gpio::new().context("Open gpio")?;
-        let mut leak_pin = gpio.get(27).context("Open leak pin")?.into_input_pulldown();
+        let mut probes = read_leak_probes()
+            .context("Load leak probes")?
+            .into_iter()
+            .map(|record| {
+                let pin = gpio
+                    .get(record.pin)
+                    .with_context(|| format!("Open leak pin {}", record.pin))?
+                    .into_input_pulldown();
+
+                Ok(Probe {
+                    name: record.name,
+                    pin,
+                    debounce: Debounce::new(DEBOUNCE),
+                    state: LeakProbeState::default(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-        let update_initial = store::create_update(&tokens::LEAK, false);
-        events.send(Event::Store(update_initial));
+        let ticks = scheduler::schedule("Leak", PERIOD);
+        let clock = clock::clock();
 
-        let leak_detected = Arc::new(AtomicBool::new(leak_pin.is_high()));
+        spawner.spawn(move || {
+            span!(Level::INFO, "Leak monitor thread");
 
-        // Listen to pin interrupts
-        {
-            let mut events = events.clone();
-            let leak_detected = leak_detected.clone();
+            // Kept through link loss so the response does not depend on the surface
+            let mut response = LeakResponse::default();
+            let mut responding = false;
 
-            leak_pin
-                .set_async_interrupt(Trigger::Both, move |level| {
-                    let level = match level {
-                        Level::High => true,
-                        Level::Low => false,
-                    };
+            publish_probes(&mut events, &probes);
 
-                    let update = store::create_update(&tokens::LEAK, level);
-                    events.send(Event::Store(update));
-
-                    leak_detected.store(level, Ordering::Relaxed);
-                })
-                .context("Set async leak interrupt")?;
-        }
+            for _tick in ticks {
+                let mut changed = false;
 
-        // Rebrodcast state when sync is requested
-        // Dont drop leak pin until program exit
-        {
-            let mut events = events.clone();
-            let leak_detected = leak_detected;
-
-            spawner.spawn(move || {
-                let _leak_pin = leak_pin;
-
-                for event in listener {
+                for event in listener.try_iter() {
                     match &*event {
-                        Event::SyncStore => {
-                            let update = store::create_update(
-                                &tokens::LEAK,
-                                leak_detected.load(Ordering::Relaxed),
-                            );
-                            events.send(Event::Store(update));
+                        Event::Store(update) => {
+                            if update.0 == tokens::LEAK_RESPONSE.0 {
+                                // Removing the response restores the default
+                                response = store::handle_update(&tokens::LEAK_RESPONSE, update)
+                                    .map(|it| *it)
+                                    .unwrap_or_default();
+
+                                info!("Leak response set to {response:?}");
+                            }
                         }
-                        Event::Exit => {
-                            return;
+                        // Rebrodcast state when sync is requested
+                        Event::SyncStore => {
+                            changed = true;
                         }
                         _ => {}
                     }
                 }
-            });
-        }
+
+                let now = clock.now();
+                for probe in &mut probes {
+                    let Some(leaking) = probe.debounce.update(probe.pin.is_high(), now) else {
+                        continue;
+                    };
+
+                    if leaking {
+                        warn!("Leak detected by {}", probe.name);
+                        probe.state.first_detected.get_or_insert(SystemTime::now());
+                    } else {
+                        info!("{} is dry", probe.name);
+                    }
+
+                    probe.state.leaking = leaking;
+                    changed = true;
+                }
+
+                if changed {
+                    publish_probes(&mut events, &probes);
+                }
+
+                let leaking = probes.iter().any(|probe| probe.state.leaking);
+                if leaking != responding {
+                    if leaking {
+                        warn!("Responding to leak with {response:?}");
+                    } else {
+                        info!("All leak probes are dry");
+                    }
+
+                    responding = leaking;
+                }
+
+                // Disarming is left to the arming system, which reads the same response
+                if let (true, LeakResponse::Ascend(speed)) = (leaking, response) {
+                    let movement = Movement {
+                        z: speed,
+                        ..Default::default()
+                    };
+
+                    let update = store::create_update(&tokens::MOVEMENT_LEAK, movement);
+                    events.send(Event::Store(update));
+                }
+            }
+        });
 
         Ok(())
     }
 }
+
+fn publish_probes(events: &mut EventHandle, probes: &[Probe]) {
+    let states: HashMap<String, LeakProbeState> = probes
+        .iter()
+        .map(|probe| (probe.name.clone(), probe.state))
+        .collect();
+    let leaking = probes.iter().any(|probe| probe.state.leaking);
+
+    let update = store::create_update(&tokens::LEAK_PROBES, states);
+    events.send(Event::Store(update));
+
+    let update = store::create_update(&tokens::LEAK, leaking);
+    events.send(Event::Store(update));
+}
+
+#[derive(Deserialize, Debug)]
+struct ProbeRecord {
+    name: String,
+    pin: u8,
+}
+
+/// Reads the configured probes, just the navigator's leak input if there are none
+/// Columns are `name,pin` with the pin as a bcm gpio number
+fn read_leak_probes() -> anyhow::Result<Vec<ProbeRecord>> {
+    if !Path::new(PROBES_FILE).exists() {
+        info!("No leak probes configured, using the navigator's leak input");
+
+        let (name, pin) = DEFAULT_PROBE;
+        return Ok(vec![ProbeRecord {
+            name: name.to_owned(),
+            pin,
+        }]);
+    }
+
+    let reader = csv::Reader::from_path(PROBES_FILE).context("Read leak probes")?;
+    reader
+        .into_deserialize()
+        .map(|result| result.context("Parse leak probe"))
+        .collect()
+}
@@ -1,20 +1,30 @@
 use std::{
     f32::consts::{PI, TAU},
     sync::Arc,
-    thread::{self, Scope},
+    thread::Scope,
     time::{Duration, Instant},
 };
 
 use common::{
-    error::LogErrorExt,
-    store::{tokens, Store},
-    types::{LevelingCorrection, LevelingMode, Movement, Percent, PidConfig, PidController},
+    store::{tokens, Store, UpdateCallback},
+    types::{
+        LevelingCorrection, LevelingMode, Movement, Percent, PidConfig, PidController, SensorId,
+    },
 };
-use crossbeam::channel::bounded;
+use crossbeam::channel::select;
 use glam::{Quat, Vec3};
-use tracing::{span, warn, Level};
+use tracing::{span, Level};
 
-use crate::{event::Event, events::EventHandle, systems::stop, SystemId};
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{
+        clock,
+        scheduler::{self, Tick},
+        sensor_health::FaultLatch,
+    },
+    SystemId,
+};
 
 use super::System;
 
@@ -39,167 +49,155 @@ impl System for LevelingSystem {
     ) -> anyhow::Result<()> {
         let listner = events.take_listner().unwrap();
 
-        let (tx, rx) = bounded(30);
+        let ticks = scheduler::schedule("Leveling", PERIOD);
+        let clock = clock::clock();
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Leveling thread");
+
+            let mut store = {
+                let mut events = events.clone();
+                Store::with_clock(
+                    move |update| {
+                        events.send(Event::Store(update));
+                    },
+                    clock.clone(),
+                )
+            };
 
-        {
-            let tx = tx.clone();
-            spawner.spawn(move || {
-                span!(Level::INFO, "Leveling watcher thread");
+            let mut controller = LevelingController::default();
+
+            loop {
+                let event = select! {
+                    recv(listner) -> event => event.map(LevelingEvent::Event),
+                    recv(ticks) -> tick => tick.map(LevelingEvent::Tick),
+                };
+                let Ok(event) = event else {
+                    return;
+                };
 
-                for event in listner {
-                    match &*event {
-                        Event::Store(_) | Event::SyncStore | Event::ResetForignStore => {
-                            tx.try_send(LevelingEvent::Event(event))
-                                .log_error("Send Event");
+                match event {
+                    LevelingEvent::Event(event) => match &*event {
+                        Event::SyncStore => {
+                            store.refresh();
+                        }
+                        Event::ResetForignStore => {
+                            store.reset_shared();
+                        }
+                        Event::Store(update) => {
+                            store.handle_update_shared(update);
                         }
                         Event::Exit => {
-                            tx.try_send(LevelingEvent::Exit).log_error("Send Exit");
                             return;
                         }
                         _ => {}
+                    },
+                    LevelingEvent::Tick(tick) => {
+                        controller.tick(&mut store, tick.scheduled());
                     }
                 }
-            });
-        }
+            }
+        });
 
-        {
-            let tx = tx;
-            spawner.spawn(move || {
-                span!(Level::INFO, "Leveling tick thread");
+        Ok(())
+    }
+}
 
-                let mut deadline = Instant::now() + PERIOD;
+enum LevelingEvent {
+    Event(Arc<Event>),
+    Tick(Tick),
+}
 
-                while !stop::world_stopped() {
-                    tx.try_send(LevelingEvent::Tick).log_error("Send tick");
+struct LevelingController {
+    pitch: PidController,
+    roll: PidController,
+    fault_latch: FaultLatch<LevelingMode>,
+}
 
-                    let remaining = deadline - Instant::now();
-                    if !remaining.is_zero() {
-                        thread::sleep(remaining);
-                    } else {
-                        warn!("Behind schedual");
-                    }
-                    deadline += PERIOD;
-                }
-            });
+impl Default for LevelingController {
+    fn default() -> Self {
+        Self {
+            pitch: PidController::new(PERIOD),
+            roll: PidController::new(PERIOD),
+            // Pitch and roll do not depend on the compass
+            fault_latch: FaultLatch::new(
+                "Leveling",
+                &[SensorId::Inertial],
+                tokens::LEVELING_DISENGAGED,
+            ),
         }
+    }
+}
 
-        {
-            let rx = rx;
-            spawner.spawn(move || {
-                span!(Level::INFO, "Leveling fusion thread");
+impl LevelingController {
+    fn tick<C: UpdateCallback>(&mut self, store: &mut Store<C>, scheduled: Instant) {
+        let mode = store.get(&tokens::LEVELING_MODE).map(|it| *it);
+        let engaged = matches!(mode, Some(LevelingMode::Enabled(_)));
+        // A latched fault stands in for the surface disabling leveling
+        let mode = if self.fault_latch.disengaged(store, mode, engaged) {
+            Some(LevelingMode::Disabled)
+        } else {
+            mode
+        };
 
-                let mut store = {
-                    let mut events = events.clone();
-                    Store::new(move |update| {
-                        events.send(Event::Store(update));
-                    })
-                };
+        if let Some((mode, orientation)) = Option::zip(mode, store.get(&tokens::ORIENTATION)) {
+            let orientation = Quat::from(orientation.0);
 
-                let mut pitch_controller = PidController::new(PERIOD);
-                let mut roll_controller = PidController::new(PERIOD);
-
-                for event in rx {
-                    match event {
-                        LevelingEvent::Event(event) => match &*event {
-                            Event::SyncStore => {
-                                store.refresh();
-                            }
-                            Event::ResetForignStore => {
-                                store.reset_shared();
-                            }
-                            Event::Store(update) => {
-                                store.handle_update_shared(update);
-                            }
-                            _ => unreachable!(),
-                        },
-                        LevelingEvent::Tick => {
-                            if let Some((mode, orientation)) = Option::zip(
-                                store.get(&tokens::LEVELING_MODE),
-                                store.get(&tokens::ORIENTATION),
-                            ) {
-                                let orientation = Quat::from(orientation.0);
-
-                                if let LevelingMode::Enabled(target_up) = *mode {
-                                    let target_up: Vec3 = target_up.into();
-                                    let observed_up = orientation * Vec3::Z;
-
-                                    let error = Quat::from_rotation_arc(observed_up, target_up);
-                                    let pitch_error =
-                                        instant_twist(error, orientation * Vec3::X).to_degrees();
-                                    let roll_error =
-                                        instant_twist(error, orientation * Vec3::Y).to_degrees();
-
-                                    let config = store
-                                        .get(&tokens::LEVELING_PID_OVERRIDE)
-                                        .map(|it| *it)
-                                        .unwrap_or(PID_CONFIG);
-                                    let pitch_pid_result =
-                                        pitch_controller.update(pitch_error as f64, config);
-                                    let roll_pid_result =
-                                        roll_controller.update(roll_error as f64, config);
-
-                                    let max_correction = 0.30;
-                                    let pitch_corection = pitch_pid_result
-                                        .correction()
-                                        .clamp(-max_correction, max_correction);
-                                    let roll_corection = roll_pid_result
-                                        .correction()
-                                        .clamp(-max_correction, max_correction);
-
-                                    store.insert(&tokens::LEVELING_PITCH_RESULT, pitch_pid_result);
-                                    store.insert(&tokens::LEVELING_ROLL_RESULT, roll_pid_result);
-                                    store.insert(
-                                        &tokens::LEVELING_CORRECTION,
-                                        LevelingCorrection {
-                                            pitch: pitch_pid_result.correction(),
-                                            roll: roll_pid_result.correction(),
-                                        },
-                                    );
-                                    store.insert(
-                                        &tokens::MOVEMENT_LEVELING,
-                                        Movement {
-                                            z: Percent::new(high_pass(
-                                                pitch_corection * PID_PITCH_MULTIPLIER,
-                                                0.05,
-                                            )),
-                                            x_rot: Percent::new(high_pass(
-                                                pitch_corection * PID_PITCH_MULTIPLIER,
-                                                0.05,
-                                            )),
-                                            y_rot: Percent::new(high_pass(
-                                                roll_corection * PID_ROLL_MULTIPLIER,
-                                                0.05,
-                                            )),
-                                            ..Movement::default()
-                                        },
-                                    );
-                                } else {
-                                    pitch_controller = PidController::new(PERIOD);
-                                    roll_controller = PidController::new(PERIOD);
-                                    store.remove(&tokens::MOVEMENT_LEVELING);
-                                }
-                            } else {
-                                pitch_controller = PidController::new(PERIOD);
-                                roll_controller = PidController::new(PERIOD);
-                                store.remove(&tokens::MOVEMENT_LEVELING);
-                            }
-                        }
-                        LevelingEvent::Exit => {
-                            return;
-                        }
-                    }
-                }
-            });
-        }
+            if let LevelingMode::Enabled(target_up) = mode {
+                let target_up: Vec3 = target_up.into();
+                let observed_up = orientation * Vec3::Z;
 
-        Ok(())
-    }
-}
+                let error = Quat::from_rotation_arc(observed_up, target_up);
+                let pitch_error = instant_twist(error, orientation * Vec3::X).to_degrees();
+                let roll_error = instant_twist(error, orientation * Vec3::Y).to_degrees();
 
-enum LevelingEvent {
-    Event(Arc<Event>),
-    Tick,
-    Exit,
+                let config = store
+                    .get(&tokens::LEVELING_PID_OVERRIDE)
+                    .map(|it| *it)
+                    .unwrap_or(PID_CONFIG);
+                let pitch_pid_result = self.pitch.update_at(pitch_error as f64, config, scheduled);
+                let roll_pid_result = self.roll.update_at(roll_error as f64, config, scheduled);
+
+                let max_correction = 0.30;
+                let pitch_corection = pitch_pid_result
+                    .correction()
+                    .clamp(-max_correction, max_correction);
+                let roll_corection = roll_pid_result
+                    .correction()
+                    .clamp(-max_correction, max_correction);
+
+                store.insert(&tokens::LEVELING_PITCH_RESULT, pitch_pid_result);
+                store.insert(&tokens::LEVELING_ROLL_RESULT, roll_pid_result);
+                store.insert(
+                    &tokens::LEVELING_CORRECTION,
+                    LevelingCorrection {
+                        pitch: pitch_pid_result.correction(),
+                        roll: roll_pid_result.correction(),
+                    },
+                );
+                store.insert(
+                    &tokens::MOVEMENT_LEVELING,
+                    Movement {
+                        z: Percent::new(high_pass(pitch_corection * PID_PITCH_MULTIPLIER, 0.05)),
+                        x_rot: Percent::new(high_pass(
+                            pitch_corection * PID_PITCH_MULTIPLIER,
+                            0.05,
+                        )),
+                        y_rot: Percent::new(high_pass(roll_corection * PID_ROLL_MULTIPLIER, 0.05)),
+                        ..Movement::default()
+                    },
+                );
+            } else {
+                self.pitch = PidController::new(PERIOD);
+                self.roll = PidController::new(PERIOD);
+                store.remove(&tokens::MOVEMENT_LEVELING);
+            }
+        } else {
+            self.pitch = PidController::new(PERIOD);
+            self.roll = PidController::new(PERIOD);
+            store.remove(&tokens::MOVEMENT_LEVELING);
+        }
+    }
 }
 
 fn instant_twist(q: Quat, twist_axis: Vec3) -> f32 {
@@ -233,3 +231,72 @@ fn high_pass(value: f64, threshold: f64) -> f64 {
         0.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        clock::{Clock, ManualClock},
+        store::create_update,
+        types::{GForce, HoldDisengaged, Orientation, SensorFault},
+    };
+    use fxhash::FxHashMap as HashMap;
+
+    use super::*;
+
+    fn health(inertial: Vec<SensorFault>) -> HashMap<SensorId, Vec<SensorFault>> {
+        let mut health = HashMap::default();
+        health.insert(SensorId::Inertial, inertial);
+        health.insert(SensorId::Depth, vec![]);
+        health.insert(SensorId::Magnetometer, vec![]);
+
+        health
+    }
+
+    #[test]
+    fn sensor_fault_stops_output_until_mode_changes() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+        let mut controller = LevelingController::default();
+
+        let tilted = Orientation(Quat::from_rotation_x(0.35).into());
+        store.handle_update_shared(&create_update(&tokens::ORIENTATION, tilted));
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health(vec![])));
+        let level = LevelingMode::Enabled(Vec3::Z.into());
+        store.handle_update_shared(&create_update(&tokens::LEVELING_MODE, level));
+
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_LEVELING).is_some());
+
+        let fault = SensorFault::AccelSaturated(GForce(4.0));
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health(vec![fault])));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_LEVELING).is_none());
+        assert_eq!(
+            store.get(&tokens::LEVELING_DISENGAGED).as_deref(),
+            Some(&HoldDisengaged {
+                sensor: SensorId::Inertial,
+                fault,
+            })
+        );
+
+        // Stays off after the fault clears, the surface still has leveling enabled
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health(vec![])));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_LEVELING).is_none());
+
+        store.handle_update_shared(&create_update(
+            &tokens::LEVELING_MODE,
+            LevelingMode::Disabled,
+        ));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::LEVELING_DISENGAGED).is_none());
+
+        store.handle_update_shared(&create_update(&tokens::LEVELING_MODE, level));
+        clock.advance(PERIOD);
+        controller.tick(&mut store, clock.now());
+        assert!(store.get(&tokens::MOVEMENT_LEVELING).is_some());
+    }
+}
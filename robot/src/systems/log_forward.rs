@@ -0,0 +1,421 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::Scope,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Context};
+use common::{
+    protocol::Protocol,
+    store::{self, tokens, Update},
+    types::{LogLevel, LogRecord},
+};
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use tracing::{
+    error, field::Field, field::Visit, info, metadata::LevelFilter, span, Level, Subscriber,
+};
+use tracing_subscriber::{
+    fmt, layer::Context as LayerContext, prelude::*, registry::LookupSpan, reload, EnvFilter, Layer,
+};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{stop, System},
+    SystemId,
+};
+
+/// What gets sent to the surface until it asks for something else
+pub const DEFAULT_FILTER: &str = "info";
+/// Records forwarded per second once the burst is used up
+const RATE: f64 = 50.0;
+const BURST: f64 = 200.0;
+const BUFFER: usize = 500;
+/// How often dropped records are reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+static FORWARDING: Mutex<Option<Forwarding>> = Mutex::new(None);
+
+type SetFilter = Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send>;
+
+/// The other end of the tracing layer, picked up by `LogForwardSystem`
+struct Forwarding {
+    records: Receiver<LogRecord>,
+    /// Records the layer could not queue because the forwarder was behind
+    dropped: Arc<AtomicU64>,
+    set_filter: SetFilter,
+}
+
+/// Sets up logging to stdout and a layer that forwards records to the surface
+pub fn init_tracing() {
+    let (layer, forwarding) = forward_layer();
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_filter(LevelFilter::DEBUG))
+        .with(layer)
+        .init();
+
+    match FORWARDING.lock() {
+        Ok(mut slot) => *slot = Some(forwarding),
+        Err(_) => error!("Could not register log forwarding"),
+    }
+}
+
+/// The forwarding layer behind a filter that can be swapped while running
+fn forward_layer<S>() -> (impl Layer<S>, Forwarding)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let (tx, rx) = bounded(BUFFER);
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(DEFAULT_FILTER));
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    let layer = ForwardLayer {
+        records: tx,
+        dropped: dropped.clone(),
+    };
+    let set_filter: SetFilter =
+        Box::new(move |filter| handle.reload(filter).context("Reload log filter"));
+
+    let forwarding = Forwarding {
+        records: rx,
+        dropped,
+        set_filter,
+    };
+
+    (layer.with_filter(filter), forwarding)
+}
+
+/// Sends log records to the surface and applies the filter it asks for
+pub struct LogForwardSystem;
+
+impl System for LogForwardSystem {
+    const ID: SystemId = SystemId::LogForward;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listner = events.take_listner().unwrap();
+
+        let forwarding = FORWARDING
+            .lock()
+            .map_err(|_| anyhow!("Log forwarding registry poisoned"))?
+            .take();
+        let Some(Forwarding {
+            records,
+            dropped,
+            set_filter,
+        }) = forwarding
+        else {
+            bail!("Log forwarding was not set up");
+        };
+
+        {
+            let mut events = events.clone();
+            spawner.spawn(move || {
+                span!(Level::INFO, "Log filter updater");
+
+                for event in listner {
+                    match &*event {
+                        Event::Store(update) => {
+                            let Some(directives) = requested_filter(update) else {
+                                continue;
+                            };
+
+                            let res = EnvFilter::try_new(&directives)
+                                .with_context(|| format!("Parse log filter `{directives}`"))
+                                .and_then(&set_filter);
+                            match res {
+                                Ok(()) => info!("Forwarding logs matching `{directives}`"),
+                                Err(err) => events.send(Event::Error(err)),
+                            }
+                        }
+                        Event::Exit => {
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Log forwarder");
+
+            let mut limiter = RateLimiter::new(RATE, BURST, Instant::now());
+            let mut limited = 0;
+            let mut last_report = Instant::now();
+
+            loop {
+                match records.recv_timeout(REPORT_INTERVAL) {
+                    Ok(record) => {
+                        if limiter.take(Instant::now()) {
+                            events.send(Event::PacketTx(Protocol::LogRecord(record)));
+                        } else {
+                            limited += 1;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+
+                if stop::world_stopped() {
+                    return;
+                }
+
+                if last_report.elapsed() >= REPORT_INTERVAL {
+                    let dropped = limited + dropped.swap(0, Ordering::Relaxed);
+                    if let Some(record) = dropped_report(dropped) {
+                        events.send(Event::PacketTx(Protocol::LogRecord(record)));
+                    }
+
+                    limited = 0;
+                    last_report = Instant::now();
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// The filter directives a store update asks for, back to the default when it is removed
+fn requested_filter(update: &Update) -> Option<String> {
+    if let Some(filter) = store::handle_update(&tokens::LOG_FILTER, update) {
+        Some((*filter).clone())
+    } else if update.0 == tokens::LOG_FILTER.0 {
+        Some(DEFAULT_FILTER.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Tells the surface how many records it did not get
+fn dropped_report(dropped: u64) -> Option<LogRecord> {
+    if dropped == 0 {
+        return None;
+    }
+
+    Some(LogRecord {
+        time: SystemTime::now(),
+        level: LogLevel::Warn,
+        target: module_path!().to_owned(),
+        spans: Vec::new(),
+        message: format!("Dropped {dropped} log records"),
+        fields: Vec::new(),
+    })
+}
+
+/// Turns tracing events into `LogRecord`s
+struct ForwardLayer {
+    records: Sender<LogRecord>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S> Layer<S> for ForwardLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| span.name().to_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut visitor = RecordVisitor::default();
+        event.record(&mut visitor);
+
+        let level = match *metadata.level() {
+            Level::TRACE | Level::DEBUG => LogLevel::Debug,
+            Level::INFO => LogLevel::Info,
+            Level::WARN => LogLevel::Warn,
+            Level::ERROR => LogLevel::Error,
+        };
+
+        let record = LogRecord {
+            time: SystemTime::now(),
+            level,
+            target: metadata.target().to_owned(),
+            spans,
+            message: visitor.message,
+            fields: visitor.fields,
+        };
+
+        // Logging must never block, the forwarder reports what was lost
+        if self.records.try_send(record).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Default)]
+struct RecordVisitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for RecordVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_owned();
+        } else {
+            self.fields
+                .push((field.name().to_owned(), value.to_owned()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.fields
+                .push((field.name().to_owned(), format!("{value:?}")));
+        }
+    }
+}
+
+/// Token bucket
+struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64, burst: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            last: now,
+        }
+    }
+
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::store::create_update;
+    use tracing::{debug, info, info_span, warn};
+    use tracing_subscriber::Registry;
+
+    use super::*;
+
+    fn messages(records: &Receiver<LogRecord>) -> Vec<String> {
+        records.try_iter().map(|it| it.message).collect()
+    }
+
+    #[test]
+    fn limiter_allows_a_burst_then_the_rate() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(10.0, 3.0, start);
+
+        let taken = (0..5).filter(|_| limiter.take(start)).count();
+        assert_eq!(taken, 3);
+
+        // One token every 100ms
+        let later = start + Duration::from_millis(250);
+        let taken = (0..5).filter(|_| limiter.take(later)).count();
+        assert_eq!(taken, 2);
+
+        // Refills no further than the burst
+        let much_later = later + Duration::from_secs(60);
+        let taken = (0..5).filter(|_| limiter.take(much_later)).count();
+        assert_eq!(taken, 3);
+    }
+
+    #[test]
+    fn dropped_records_are_reported() {
+        assert!(dropped_report(0).is_none());
+
+        let report = dropped_report(7).unwrap();
+        assert_eq!(report.level, LogLevel::Warn);
+        assert_eq!(report.message, "Dropped 7 log records");
+    }
+
+    #[test]
+    fn layer_counts_records_it_cannot_queue() {
+        let (tx, rx) = bounded(2);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let layer = ForwardLayer {
+            records: tx,
+            dropped: dropped.clone(),
+        };
+
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            let _span = info_span!("Outer").entered();
+            warn!(depth = 2.5, "First");
+            for _ in 0..4 {
+                info!("More");
+            }
+        });
+
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.level, LogLevel::Warn);
+        assert_eq!(first.message, "First");
+        assert_eq!(first.spans, vec!["Outer".to_owned()]);
+        assert_eq!(first.fields, vec![("depth".to_owned(), "2.5".to_owned())]);
+
+        assert_eq!(messages(&rx), vec!["More".to_owned()]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn filter_reloads_while_running() {
+        let (layer, forwarding) = forward_layer::<Registry>();
+
+        let set = |update: Update| {
+            let directives = requested_filter(&update).expect("Log filter update");
+            let filter = EnvFilter::try_new(directives).unwrap();
+            (forwarding.set_filter)(filter).unwrap();
+        };
+
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            debug!("Hidden");
+            info!("Default");
+
+            set(create_update(&tokens::LOG_FILTER, "debug".to_owned()));
+            debug!("Debug");
+
+            set(create_update(&tokens::LOG_FILTER, "warn".to_owned()));
+            info!("Hidden");
+            warn!("Warn");
+
+            // Removing the filter goes back to the default
+            set((tokens::LOG_FILTER.0, None));
+            debug!("Hidden");
+            info!("Back to default");
+        });
+
+        assert_eq!(
+            messages(&forwarding.records),
+            ["Default", "Debug", "Warn", "Back to default"]
+        );
+        assert!(requested_filter(&create_update(&tokens::LEAK, true)).is_none());
+    }
+}
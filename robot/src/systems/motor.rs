@@ -1,32 +1,36 @@
 use crate::events::EventHandle;
-use crate::peripheral::motor::Motor;
-use crate::systems::{stop, System};
+use crate::peripheral::motor::MOTOR_IDS;
+use crate::systems::esc_calibration::{
+    calibrated_motor, read_esc_calibration, EscSweep, SweepTick,
+};
+use crate::systems::scheduler::{self, Tick};
+use crate::systems::thruster_test::{TestTick, ThrusterTest};
+use crate::systems::{arming, clock, estop, stop, System};
 use crate::SystemId;
 use crate::{event::Event, peripheral::pca9685::Pca9685};
 use anyhow::{anyhow, Context};
 use common::store::UpdateCallback;
 use common::{
-    error::LogErrorExt,
-    store::{tokens, KeyImpl, Store},
-    types::{Armed, MotorFrame, MotorId, Movement},
+    store::{self, tokens, KeyImpl, Store},
+    types::{EscCalibration, EscSweepRequest, MotorFrame, MotorId, Movement},
 };
-use crossbeam::channel;
+use crossbeam::channel::select;
 use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
 use serde::Deserialize;
-use std::sync::Arc;
-use std::thread::{self, Scope};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::Scope;
 use std::time::Duration;
-use std::time::Instant;
-use tracing::{span, Level};
+use tracing::{info, span, warn, Level};
 
 pub const MAX_UPDATE_AGE: Duration = Duration::from_millis(250);
+const PERIOD: Duration = Duration::from_millis(10);
 
 /// Handles Motor speed updated and controls the motors
 pub struct MotorSystem;
 
 enum Message {
     Event(Arc<Event>),
-    Tick,
+    Tick(Tick),
 }
 
 impl System for MotorSystem {
@@ -38,9 +42,11 @@ impl System for MotorSystem {
     ) -> anyhow::Result<()> {
         let listner = events.take_listner().unwrap();
 
-        let (tx, rx) = channel::bounded(32);
+        let ticks = scheduler::schedule("Motor", PERIOD);
+        let clock = clock::clock();
 
         let motor_data = read_motor_data().context("Load motor data")?;
+        let file_calibration = read_esc_calibration().context("Load esc calibration")?;
 
         {
             let mut events = events.clone();
@@ -49,9 +55,12 @@ impl System for MotorSystem {
 
                 let mut store = {
                     let mut events = events.clone();
-                    Store::new(move |update| {
-                        events.send(Event::Store(update));
-                    })
+                    Store::with_clock(
+                        move |update| {
+                            events.send(Event::Store(update));
+                        },
+                        clock.clone(),
+                    )
                 };
 
                 let pwm_controller = Pca9685::new(
@@ -59,16 +68,18 @@ impl System for MotorSystem {
                     Pca9685::I2C_ADDRESS,
                     Duration::from_secs_f64(1.0 / 400.0),
                 );
-                let mut pwm_controller = match pwm_controller {
-                    Ok(pwm_controller) => pwm_controller,
+                let pwm_controller = match pwm_controller {
+                    Ok(pwm_controller) => Arc::new(Mutex::new(pwm_controller)),
                     Err(err) => {
                         events.send(Event::Error(err.context("PCA9685")));
                         return;
                     }
                 };
 
+                estop::register_pwm_controller(&pwm_controller);
+
                 const STOP_PWMS: [Duration; 16] = [Duration::from_micros(1500); 16];
-                let rst = pwm_controller
+                let rst = lock_controller(&pwm_controller)
                     .set_pwms(STOP_PWMS)
                     .context("Set initial pwms");
                 if let Err(error) = rst {
@@ -78,52 +89,151 @@ impl System for MotorSystem {
                     return;
                 }
 
-                pwm_controller.output_enable();
+                // Enabled on the first tick unless the emergency stop is latched
+                let mut outputs_enabled = false;
+                let mut thruster_test: Option<ThrusterTest> = None;
+                let mut esc_sweep: Option<EscSweep> = None;
+
+                let mut calibration = file_calibration.clone();
+                store.insert(&tokens::ESC_CALIBRATION, calibration.clone());
+
+                let listening: HashSet<KeyImpl> = vec![
+                    tokens::ARMED.0,
+                    tokens::ARMING_STATE.0,
+                    tokens::MOVEMENT_JOYSTICK.0,
+                    tokens::MOVEMENT_OPENCV.0,
+                    tokens::MOVEMENT_DEPTH.0,
+                    tokens::MOVEMENT_LEVELING.0,
+                    tokens::MOVEMENT_OVERRIDE.0,
+                    tokens::MOVEMENT_LEAK.0,
+                    tokens::THRUSTER_TEST.0,
+                    tokens::ESC_CALIBRATION_OVERRIDE.0,
+                    tokens::ESC_SWEEP.0,
+                    tokens::SERVO_PULSES.0,
+                    tokens::RAW_INERTIAL.0,
+                ]
+                .into_iter()
+                .collect();
+
+                loop {
+                    let message = select! {
+                        recv(listner) -> event => event.map(Message::Event),
+                        recv(ticks) -> tick => tick.map(Message::Tick),
+                    };
+                    let Ok(message) = message else {
+                        return;
+                    };
+
+                    // Only the keys motor control depends on are worth handling
+                    if let Message::Event(event) = &message {
+                        if let Event::Store(update) = &**event {
+                            if !listening.contains(&update.0) {
+                                continue;
+                            }
+                        }
+                    }
 
-                for message in rx {
                     if stop::world_stopped() {
                         // Pca9685 stops on drop
                         return;
                     }
 
                     match message {
-                        Message::Tick => {
+                        Message::Tick(_tick) => {
+                            let emergency_stopped = estop::emergency_stopped();
+
+                            // Only run motors once the arming system has approved the request
+                            let approved = store
+                                .get(&tokens::ARMING_STATE)
+                                .map(|it| it.is_armed())
+                                .unwrap_or(false);
+                            let armed = !emergency_stopped
+                                && approved
+                                && arming::wants_armed(&store, approved);
+
                             // Recalculate motor speeds
-                            let calculated_speeds = if let Some(armed) =
-                                store.get_alive(&tokens::ARMED, MAX_UPDATE_AGE)
-                            {
-                                if matches!(*armed, Armed::Armed) {
-                                    if let Some(speed_overrides) =
-                                        store.get(&tokens::MOVEMENT_OVERRIDE)
-                                    {
-                                        let mut new_speeds = HashMap::default();
+                            let calculated_speeds = if !armed {
+                                abort_on_disarm(&mut store, &mut thruster_test, &mut esc_sweep);
 
-                                        // TODO: Use an iterator?
-                                        for (motor, speed) in speed_overrides.iter() {
-                                            new_speeds.insert(*motor, MotorFrame::Percent(*speed));
+                                // Disarmed
+                                Default::default()
+                            } else if let Some(test) = &mut thruster_test {
+                                let inertial =
+                                    store.get_alive(&tokens::RAW_INERTIAL, MAX_UPDATE_AGE);
+
+                                match test.tick(clock.now(), inertial.as_deref()) {
+                                    TestTick::Running(speeds, report) => {
+                                        if let Some(report) = report {
+                                            store.insert(&tokens::THRUSTER_TEST_REPORT, report);
                                         }
 
-                                        new_speeds
-                                    } else {
-                                        let movement = sum_movements(&store);
-                                        store.insert(&tokens::MOVEMENT_CALCULATED, movement);
+                                        speeds
+                                    }
+                                    TestTick::Finished(report) => {
+                                        info!("Thruster self-test finished: {:?}", report.state);
+                                        store.insert(&tokens::THRUSTER_TEST_REPORT, report);
+                                        thruster_test = None;
 
-                                        mix_movement(movement, &motor_data)
+                                        Default::default()
                                     }
-                                } else {
-                                    // Disarmed
-                                    Default::default()
                                 }
+                            } else if let Some(sweep) = &mut esc_sweep {
+                                match sweep.tick(clock.now()) {
+                                    SweepTick::Running((motor_id, frame), state) => {
+                                        if let Some(state) = state {
+                                            store.insert(&tokens::ESC_SWEEP_STATE, state);
+                                        }
+
+                                        [(motor_id, frame)].into_iter().collect()
+                                    }
+                                    SweepTick::Finished(state) => {
+                                        info!("Esc sweep finished: {state:?}");
+                                        store.insert(&tokens::ESC_SWEEP_STATE, state);
+                                        esc_sweep = None;
+
+                                        Default::default()
+                                    }
+                                }
+                            } else if let Some(speed_overrides) =
+                                store.get(&tokens::MOVEMENT_OVERRIDE)
+                            {
+                                let mut new_speeds = HashMap::default();
+
+                                // TODO: Use an iterator?
+                                for (motor, speed) in speed_overrides.iter() {
+                                    new_speeds.insert(*motor, MotorFrame::Percent(*speed));
+                                }
+
+                                new_speeds
                             } else {
-                                // events.send(Event::Error(anyhow!("No armed token")));
-                                Default::default()
+                                let movement = sum_movements(&store);
+                                store.insert(&tokens::MOVEMENT_CALCULATED, movement);
+
+                                let mut speeds = mix_movement(movement, &motor_data, &calibration);
+
+                                // The servo controller takes over servos from `Movement`
+                                if let Some(pulses) =
+                                    store.get_alive(&tokens::SERVO_PULSES, MAX_UPDATE_AGE)
+                                {
+                                    for (servo, pulse) in pulses.iter() {
+                                        speeds.insert(*servo, MotorFrame::Raw(*pulse));
+                                    }
+                                }
+
+                                speeds
                             };
                             store.insert(&tokens::MOTOR_SPEED, calculated_speeds.clone());
 
-                            // Speeds to PWMs
+                            // Idle channels sit at their calibrated center
                             let mut pwms = STOP_PWMS;
+                            for motor_id in MOTOR_IDS {
+                                let motor = calibrated_motor(motor_id, &calibration);
+                                pwms[motor.channel as usize] = motor.center;
+                            }
+
+                            // Speeds to PWMs
                             for (motor_id, frame) in &calculated_speeds {
-                                let motor = Motor::from(*motor_id);
+                                let motor = calibrated_motor(*motor_id, &calibration);
 
                                 let pwm = match frame {
                                     MotorFrame::Percent(pct) => motor.value_to_pwm(*pct),
@@ -133,6 +243,13 @@ impl System for MotorSystem {
                                 pwms[motor.channel as usize] = pwm;
                             }
 
+                            let mut pwm_controller = lock_controller(&pwm_controller);
+
+                            if emergency_stopped && outputs_enabled {
+                                pwm_controller.output_disable();
+                                outputs_enabled = false;
+                            }
+
                             // Write motor speeds
                             let rst = pwm_controller.set_pwms(pwms);
                             if let Err(error) = rst {
@@ -140,6 +257,12 @@ impl System for MotorSystem {
                                     error.context("Couldn't set speeds".to_string()),
                                 ));
                             }
+
+                            // Only re-enable once neutral pwms have been written
+                            if !emergency_stopped && !outputs_enabled {
+                                pwm_controller.output_enable();
+                                outputs_enabled = true;
+                            }
                         }
                         Message::Event(event) => match &*event {
                             Event::SyncStore => {
@@ -150,6 +273,93 @@ impl System for MotorSystem {
                             }
                             Event::Store(update) => {
                                 store.handle_update_shared(update);
+
+                                if let Some(config) =
+                                    store::handle_update(&tokens::THRUSTER_TEST, update)
+                                {
+                                    info!("Starting thruster self-test");
+
+                                    if let Some(sweep) = esc_sweep.take() {
+                                        store.insert(
+                                            &tokens::ESC_SWEEP_STATE,
+                                            sweep.abort("Thruster self-test started"),
+                                        );
+                                    }
+
+                                    let test = ThrusterTest::new(*config, clock.now());
+                                    store.insert(&tokens::THRUSTER_TEST_REPORT, test.progress());
+                                    thruster_test = Some(test);
+                                } else if update.0 == tokens::THRUSTER_TEST.0 && update.1.is_none()
+                                {
+                                    if let Some(test) = thruster_test.take() {
+                                        info!("Thruster self-test cancelled");
+                                        store.insert(
+                                            &tokens::THRUSTER_TEST_REPORT,
+                                            test.abort("Cancelled"),
+                                        );
+                                    }
+                                }
+
+                                if update.0 == tokens::ESC_CALIBRATION_OVERRIDE.0 {
+                                    calibration = file_calibration.clone();
+                                    if let Some(overrides) =
+                                        store.get(&tokens::ESC_CALIBRATION_OVERRIDE)
+                                    {
+                                        calibration.extend(overrides.iter());
+                                    }
+
+                                    info!("Esc calibration updated");
+                                    store.insert(&tokens::ESC_CALIBRATION, calibration.clone());
+                                }
+
+                                match store::handle_update(&tokens::ESC_SWEEP, update).as_deref() {
+                                    Some(EscSweepRequest::Start { motor, rate, limit }) => {
+                                        if thruster_test.is_some() {
+                                            warn!("Esc sweep requested during thruster self-test");
+                                        } else {
+                                            info!("Starting esc sweep on {motor:?}");
+
+                                            let motor_calibration = calibration
+                                                .get(motor)
+                                                .copied()
+                                                .unwrap_or_else(|| {
+                                                    calibrated_motor(*motor, &calibration)
+                                                        .calibration()
+                                                });
+                                            let sweep = EscSweep::new(
+                                                *motor,
+                                                &motor_calibration,
+                                                *rate,
+                                                *limit,
+                                                clock.now(),
+                                            );
+                                            store.insert(&tokens::ESC_SWEEP_STATE, sweep.state());
+                                            esc_sweep = Some(sweep);
+                                        }
+                                    }
+                                    Some(EscSweepRequest::MarkThrust) => {
+                                        if let Some(sweep) = &mut esc_sweep {
+                                            let state = sweep.mark();
+                                            info!(
+                                                "Thrust marked on {:?} at {:?}",
+                                                sweep.motor(),
+                                                state.pulse
+                                            );
+                                            store.insert(&tokens::ESC_SWEEP_STATE, state);
+                                        }
+                                    }
+                                    None => {
+                                        if update.0 == tokens::ESC_SWEEP.0 && update.1.is_none() {
+                                            if let Some(sweep) = esc_sweep.take() {
+                                                info!("Esc sweep cancelled");
+                                                store.insert(
+                                                    &tokens::ESC_SWEEP_STATE,
+                                                    sweep.abort("Cancelled"),
+                                                );
+                                            }
+                                        }
+                                    }
+                                }
                             }
                             Event::Exit => {
                                 return;
@@ -161,70 +371,33 @@ impl System for MotorSystem {
             });
         }
 
-        {
-            let mut events = events.clone();
-            let tx = tx.clone();
-            spawner.spawn(move || {
-                span!(Level::INFO, "Motor forward thread");
-
-                let listening: HashSet<KeyImpl> = vec![
-                    tokens::ARMED.0,
-                    tokens::MOVEMENT_JOYSTICK.0,
-                    tokens::MOVEMENT_OPENCV.0,
-                    tokens::MOVEMENT_DEPTH.0,
-                    tokens::MOVEMENT_LEVELING.0,
-                    tokens::MOVEMENT_OVERRIDE.0,
-                ]
-                .into_iter()
-                .collect();
-
-                for event in listner {
-                    match &*event {
-                        Event::Store(store) => {
-                            if listening.contains(&store.0) {
-                                tx.try_send(Message::Event(event))
-                                    .log_error("Forward event to motor thread");
-                            }
-                        }
-                        Event::SyncStore | Event::ResetForignStore => {
-                            tx.try_send(Message::Event(event))
-                                .log_error("Forward event to motor thread");
-                        }
-                        Event::Exit => {
-                            tx.try_send(Message::Event(event))
-                                .log_error("Forward event to motor thread");
-                            return;
-                        }
-                        _ => {}
-                    }
-                }
-            });
-        }
-
-        {
-            let tx = tx;
-            spawner.spawn(move || {
-                span!(Level::INFO, "Motor deadline check thread");
-
-                let interval = Duration::from_secs_f64(1.0 / 100.0);
-                let mut deadline = Instant::now();
-
-                while !stop::world_stopped() {
-                    deadline += interval;
-
-                    tx.send(Message::Tick)
-                        .log_error("Could not send deadline check");
-
-                    let remaining = deadline - Instant::now();
-                    thread::sleep(remaining);
-                }
-            });
-        }
-
         Ok(())
     }
 }
 
+/// Stops any self-test that is running, they cannot continue with the motors disabled
+fn abort_on_disarm<C: UpdateCallback>(
+    store: &mut Store<C>,
+    thruster_test: &mut Option<ThrusterTest>,
+    esc_sweep: &mut Option<EscSweep>,
+) {
+    if let Some(test) = thruster_test.take() {
+        warn!("Thruster self-test aborted, robot disarmed");
+        store.insert(&tokens::THRUSTER_TEST_REPORT, test.abort("Robot disarmed"));
+    }
+    if let Some(sweep) = esc_sweep.take() {
+        warn!("Esc sweep aborted, robot disarmed");
+        store.insert(&tokens::ESC_SWEEP_STATE, sweep.abort("Robot disarmed"));
+    }
+}
+
+fn lock_controller(controller: &Mutex<Pca9685>) -> MutexGuard<'_, Pca9685> {
+    // The emergency stop must still reach the controller if a holder panicked
+    controller
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 pub fn sum_movements<C: UpdateCallback>(store: &Store<C>) -> Movement {
     let mut movement = Movement::default();
 
@@ -240,12 +413,19 @@ pub fn sum_movements<C: UpdateCallback>(store: &Store<C>) -> Movement {
     if let Some(depth) = store.get_alive(&tokens::MOVEMENT_DEPTH, MAX_UPDATE_AGE) {
         movement += *depth;
     }
+    if let Some(leak) = store.get_alive(&tokens::MOVEMENT_LEAK, MAX_UPDATE_AGE) {
+        movement += *leak;
+    }
 
     movement
 }
 
 // TODO Fix motor math
-pub fn mix_movement<'a>(mov: Movement, motor_data: &MotorData) -> HashMap<MotorId, MotorFrame> {
+pub fn mix_movement<'a>(
+    mov: Movement,
+    motor_data: &MotorData,
+    calibration: &HashMap<MotorId, EscCalibration>,
+) -> HashMap<MotorId, MotorFrame> {
     const MAX_AMPERAGE: f64 = 20.0;
 
     let drive_ids = [
@@ -292,21 +472,10 @@ pub fn mix_movement<'a>(mov: Movement, motor_data: &MotorData) -> HashMap<MotorI
     let mut raw_mix = HashMap::default();
 
     for motor_id in drive_ids {
-        let motor = Motor::from(motor_id);
+        let motor = calibrated_motor(motor_id, calibration);
 
-        #[rustfmt::skip]
-        let speed = match motor_id {
-            MotorId::FrontLeftBottom =>   -x - y + z + x_rot + y_rot - z_rot,
-            MotorId::FrontLeftTop =>      -x - y - z - x_rot - y_rot - z_rot,
-            MotorId::FrontRightBottom =>   x - y + z + x_rot - y_rot + z_rot,
-            MotorId::FrontRightTop =>      x - y - z - x_rot + y_rot + z_rot,
-            MotorId::BackLeftBottom =>    -x + y + z - x_rot + y_rot + z_rot,
-            MotorId::BackLeftTop =>       -x + y - z + x_rot - y_rot + z_rot,
-            MotorId::BackRightBottom =>    x + y + z - x_rot - y_rot - z_rot,
-            MotorId::BackRightTop =>       x + y - z + x_rot + y_rot - z_rot,
-
-            _ => unreachable!()
-        };
+        let [cx, cy, cz, cx_rot, cy_rot, cz_rot] = mix_coefficients(motor_id).expect("Drive motor");
+        let speed = cx * x + cy * y + cz * z + cx_rot * x_rot + cy_rot * y_rot + cz_rot * z_rot;
 
         let skew = if speed >= 0.0 { 1.0 } else { 1.25 };
         let direction = motor.max_value.get().signum();
@@ -326,9 +495,19 @@ pub fn mix_movement<'a>(mov: Movement, motor_data: &MotorData) -> HashMap<MotorI
     let motor_amperage = MAX_AMPERAGE / max_raw;
     let mut speeds: HashMap<MotorId, MotorFrame> = raw_mix
         .into_iter()
-        .map(|(motor, value)| (motor, value * scale_raw * motor_amperage))
-        .map(|(motor, current)| (motor, motor_data.pwm_for_current(current)))
-        .map(|(motor, pwm)| (motor, MotorFrame::Raw(pwm)))
+        .map(|(motor_id, value)| {
+            let motor = calibrated_motor(motor_id, calibration);
+            let current = value * scale_raw * motor_amperage;
+
+            // The motor data gives the shape of the curve, the calibration where it sits
+            let pwm = if current == 0.0 {
+                motor.center
+            } else {
+                motor.pulse_past_deadband(current > 0.0, motor_data.travel_for_current(current))
+            };
+
+            (motor_id, MotorFrame::Raw(pwm))
+        })
         .collect();
 
     for motor in servo_ids {
@@ -353,6 +532,26 @@ pub fn mix_movement<'a>(mov: Movement, motor_data: &MotorData) -> HashMap<MotorI
     speeds
 }
 
+/// Contribution of a thruster to each axis of `Movement`, `None` for servos
+/// Order is `[x, y, z, x_rot, y_rot, z_rot]`
+#[rustfmt::skip]
+pub fn mix_coefficients(motor_id: MotorId) -> Option<[f64; 6]> {
+    let coefficients = match motor_id {
+        MotorId::FrontLeftBottom =>   [-1.0, -1.0,  1.0,  1.0,  1.0, -1.0],
+        MotorId::FrontLeftTop =>      [-1.0, -1.0, -1.0, -1.0, -1.0, -1.0],
+        MotorId::FrontRightBottom =>  [ 1.0, -1.0,  1.0,  1.0, -1.0,  1.0],
+        MotorId::FrontRightTop =>     [ 1.0, -1.0, -1.0, -1.0,  1.0,  1.0],
+        MotorId::BackLeftBottom =>    [-1.0,  1.0,  1.0, -1.0,  1.0,  1.0],
+        MotorId::BackLeftTop =>       [-1.0,  1.0, -1.0,  1.0, -1.0,  1.0],
+        MotorId::BackRightBottom =>   [ 1.0,  1.0,  1.0, -1.0, -1.0, -1.0],
+        MotorId::BackRightTop =>      [ 1.0,  1.0, -1.0,  1.0,  1.0, -1.0],
+
+        _ => return None,
+    };
+
+    Some(coefficients)
+}
+
 pub struct MotorData {
     forward: Vec<MotorRecord>,
     backward: Vec<MotorRecord>,
@@ -384,12 +583,37 @@ impl MotorData {
             let alpha = (current - a.current) / (b.current - a.current);
 
             a.pwm * (1.0 - alpha) + (b.pwm * alpha)
+        } else if idx == data_set.len() {
+            data_set[idx - 1].pwm
         } else {
             data_set[0].pwm
         };
 
         Duration::from_micros(pwm as u64)
     }
+
+    /// How far past its deadband the measured esc had to be driven for a current, from `0.0` at
+    /// the first pulse that drew current to `1.0` at the endpoint
+    pub fn travel_for_current(&self, signed_current: f64) -> f64 {
+        let data_set = if signed_current >= 0.0 {
+            &self.forward
+        } else {
+            &self.backward
+        };
+
+        // Sorted by current, the last record is the endpoint
+        let (Some(edge), Some(end)) =
+            (data_set.iter().find(|it| it.current > 0.0), data_set.last())
+        else {
+            return 0.0;
+        };
+        if edge.pwm == end.pwm {
+            return 1.0;
+        }
+
+        let pwm = self.pwm_for_current(signed_current).as_micros() as f64;
+        ((pwm - edge.pwm) / (end.pwm - edge.pwm)).clamp(0.0, 1.0)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -427,3 +651,165 @@ pub fn read_motor_data() -> anyhow::Result<MotorData> {
 
     Ok(motor_data)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use common::{
+        clock::{Clock, ManualClock},
+        store::create_update,
+        types::{Percent, ThrusterTestState},
+    };
+
+    use super::*;
+
+    fn movement(x: f64) -> Movement {
+        Movement {
+            x: Percent::new(x),
+            ..Movement::default()
+        }
+    }
+
+    fn record(pwm: f64, current: f64) -> MotorRecord {
+        MotorRecord {
+            pwm,
+            rpm: 0.0,
+            current,
+            voltage: 12.0,
+            power: 0.0,
+            force: 0.0,
+            efficiency: 0.0,
+        }
+    }
+
+    fn motor_data() -> MotorData {
+        let mut motor_data = MotorData {
+            forward: vec![
+                record(1500.0, 0.0),
+                record(1540.0, 0.05),
+                record(1900.0, 20.0),
+            ],
+            backward: vec![
+                record(1500.0, 0.0),
+                record(1460.0, 0.05),
+                record(1100.0, 20.0),
+            ],
+        };
+        motor_data.sort();
+
+        motor_data
+    }
+
+    fn calibration(motor_id: MotorId) -> HashMap<MotorId, EscCalibration> {
+        let calibration = EscCalibration {
+            min: Duration::from_micros(1000),
+            center: Duration::from_micros(1520),
+            max: Duration::from_micros(2000),
+            deadband: Duration::from_micros(80),
+            reverse: false,
+        };
+
+        [(motor_id, calibration)].into_iter().collect()
+    }
+
+    #[test]
+    fn mix_stops_at_calibrated_center() {
+        let calibration = calibration(MotorId::FrontLeftTop);
+        let speeds = mix_movement(Movement::default(), &motor_data(), &calibration);
+
+        assert_eq!(
+            speeds[&MotorId::FrontLeftTop],
+            MotorFrame::Raw(Duration::from_micros(1520))
+        );
+    }
+
+    #[test]
+    fn mix_small_commands_jump_the_deadband() {
+        let calibration = calibration(MotorId::FrontLeftTop);
+        let movement = Movement {
+            x_rot: Percent::new(-0.001),
+            ..Movement::default()
+        };
+
+        // Forward on this thruster, which is not reversed
+        let speeds = mix_movement(movement, &motor_data(), &calibration);
+        let MotorFrame::Raw(pwm) = speeds[&MotorId::FrontLeftTop] else {
+            panic!("Thrusters are driven with raw pulses");
+        };
+
+        assert!(pwm >= Duration::from_micros(1560), "{pwm:?}");
+        assert!(pwm < Duration::from_micros(1570), "{pwm:?}");
+    }
+
+    #[test]
+    fn travel_follows_the_measured_curve() {
+        let motor_data = motor_data();
+
+        assert_eq!(motor_data.travel_for_current(0.01), 0.0);
+        assert_eq!(motor_data.travel_for_current(20.0), 1.0);
+        assert_eq!(motor_data.travel_for_current(-20.0), 1.0);
+
+        let half = motor_data.travel_for_current(10.025);
+        assert!((half - 0.5).abs() < 0.01, "{half}");
+    }
+
+    #[test]
+    fn sum_movements_adds_fresh_sources() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_JOYSTICK, movement(0.25)));
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_DEPTH, movement(0.5)));
+
+        assert_eq!(sum_movements(&store), movement(0.75));
+    }
+
+    #[test]
+    fn sum_movements_drops_stale_sources() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_JOYSTICK, movement(0.25)));
+        clock.advance(MAX_UPDATE_AGE / 2);
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_LEVELING, movement(0.5)));
+
+        clock.advance(MAX_UPDATE_AGE / 2);
+        assert_eq!(sum_movements(&store), movement(0.5));
+
+        clock.advance(MAX_UPDATE_AGE / 2);
+        assert_eq!(sum_movements(&store), Movement::default());
+
+        // A new update brings a source back
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_JOYSTICK, movement(0.25)));
+        assert_eq!(sum_movements(&store), movement(0.25));
+    }
+
+    #[test]
+    fn sum_movements_ignores_removed_sources() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        store.handle_update_shared(&create_update(&tokens::MOVEMENT_OPENCV, movement(0.25)));
+        store.handle_update_shared(&(tokens::MOVEMENT_OPENCV.0, None));
+
+        assert_eq!(sum_movements(&store), Movement::default());
+    }
+
+    #[test]
+    fn disarming_aborts_the_thruster_test() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+
+        let mut thruster_test = Some(ThrusterTest::new(Default::default(), clock.now()));
+        abort_on_disarm(&mut store, &mut thruster_test, &mut None);
+
+        assert!(thruster_test.is_none());
+        assert_eq!(
+            store
+                .get(&tokens::THRUSTER_TEST_REPORT)
+                .map(|it| it.state.clone()),
+            Some(ThrusterTestState::Aborted("Robot disarmed".to_owned()))
+        );
+    }
+}
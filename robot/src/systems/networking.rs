@@ -1,6 +1,6 @@
 use crate::event::Event as RobotEvent;
 use crate::events::EventHandle;
-use crate::systems::System;
+use crate::systems::{estop, System};
 use crate::SystemId;
 use anyhow::{Context, Error};
 use common::types::LogLevel;
@@ -51,12 +51,40 @@ impl System for NetworkSystem {
                     NetEvent::Data(token, packet) => {
                         // TODO Should any of this happen here?
                         match &packet {
+                            // Handled here so a backed up event queue cant delay stopping
+                            Protocol::EmergencyStop | Protocol::ResetEmergencyStop => {
+                                if let Protocol::EmergencyStop = packet {
+                                    if let Err(err) = estop::trigger() {
+                                        events
+                                            .send(RobotEvent::Error(err.context("Emergency stop")));
+                                    }
+                                } else {
+                                    estop::reset();
+                                }
+
+                                let response =
+                                    Protocol::EmergencyStopState(estop::emergency_stopped());
+                                let res = messenger
+                                    .brodcast_packet(response)
+                                    .context("Brodcast emergency stop state");
+                                if let Err(err) = res {
+                                    events.send(RobotEvent::Error(err));
+                                }
+                            }
                             Protocol::Log(level, msg) => match level {
                                 LogLevel::Debug => debug!("Peer logged: `{msg}`"),
                                 LogLevel::Info => info!("Peer logged: `{msg}`"),
                                 LogLevel::Warn => warn!("Peer logged: `{msg}`"),
                                 LogLevel::Error => error!("Peer logged: `{msg}`"),
                             },
+                            Protocol::CameraSubscriptions(cameras) => {
+                                if let Some(addrs) = peers.get(&token) {
+                                    events.send(RobotEvent::CameraSubscriptions(
+                                        *addrs,
+                                        cameras.clone(),
+                                    ));
+                                }
+                            }
                             Protocol::Ping(ping) => {
                                 let response = Protocol::Pong(*ping, SystemTime::now());
                                 let res = messenger
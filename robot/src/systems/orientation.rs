@@ -1,15 +1,29 @@
-use std::thread::Scope;
+use std::{thread::Scope, time::Duration};
 
 use ahrs::{Ahrs, Madgwick};
 use common::{
     error::LogErrorExt,
     store::{self, tokens},
-    types::Orientation,
+    types::{MagSource, Orientation},
 };
 use nalgebra::Vector3;
-use tracing::{span, Level};
+use tracing::{info, span, warn, Level};
 
-use crate::{event::Event, events::EventHandle, systems::System, SystemId};
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{clock, System},
+    SystemId,
+};
+
+use self::mag_vote::MagVoter;
+
+pub mod mag_vote;
+
+/// Time between inertial samples when the sensor is keeping up
+const NOMINAL_PERIOD: Duration = Duration::from_millis(1);
+/// The longest step the filter takes, anything longer is a dropout
+const MAX_PERIOD: Duration = Duration::from_millis(20);
 
 /// Handles error events
 pub struct OrientationSystem;
@@ -27,13 +41,30 @@ impl System for OrientationSystem {
             spawner.spawn(move || {
                 span!(Level::INFO, "Sensor fusion thread");
 
-                let mut madgwick_filter = Madgwick::new(0.001, 0.041);
+                let mut madgwick_filter = Madgwick::new(NOMINAL_PERIOD.as_secs_f64(), 0.041);
+                let mut last_sample = None;
+
+                let clock = clock::clock();
+                let mut mag_voter = MagVoter::default();
+                let mut last_mag_status = None;
 
                 for event in listner {
                     match &*event {
-                        Event::SensorFrame(frame) => {
+                        Event::SensorFrame(batch) => {
                             // We currently ignore mag updates as the compass is not calibrated
-                            for inertial in frame.inertial {
+                            for sample in &batch.inertial {
+                                // Steps across gaps are clamped so a dropout cant throw off the
+                                // estimate
+                                let period = match last_sample {
+                                    Some(last) if sample.time > last => {
+                                        (sample.time - last).min(MAX_PERIOD)
+                                    }
+                                    _ => NOMINAL_PERIOD,
+                                };
+                                last_sample = Some(sample.time);
+                                *madgwick_filter.sample_period_mut() = period.as_secs_f64();
+
+                                let inertial = sample.frame;
                                 let gyro = Vector3::new(
                                     inertial.gyro_x.0,
                                     inertial.gyro_y.0,
@@ -58,14 +89,60 @@ impl System for OrientationSystem {
                                     store::create_update(&tokens::ORIENTATION, orientation);
                                 events.send(Event::Store(orientation_update));
 
-                                let imu_update =
-                                    store::create_update(&tokens::RAW_INERTIAL, frame.inertial[19]);
-                                events.send(Event::Store(imu_update));
+                                if let Some(inertial) = batch.inertial.last() {
+                                    let imu_update =
+                                        store::create_update(&tokens::RAW_INERTIAL, inertial.frame);
+                                    events.send(Event::Store(imu_update));
+                                }
+
+                                if let Some(mag) = batch.mag.last() {
+                                    let mag_update =
+                                        store::create_update(&tokens::RAW_MAGNETIC, mag.frame);
+                                    events.send(Event::Store(mag_update));
+                                }
+
+                                if let Some(mag) = batch.secondary_mag.last() {
+                                    let mag_update = store::create_update(
+                                        &tokens::RAW_MAGNETIC_AK09915,
+                                        mag.frame,
+                                    );
+                                    events.send(Event::Store(mag_update));
+                                }
+                            }
+
+                            for sample in &batch.mag {
+                                mag_voter.update(MagSource::Mmc5983, sample.time, sample.frame);
+                            }
+                            for sample in &batch.secondary_mag {
+                                mag_voter.update(MagSource::Ak09915, sample.time, sample.frame);
+                            }
 
-                                let mag_update =
-                                    store::create_update(&tokens::RAW_MAGNETIC, frame.mag[1]);
+                            let (mag, status) = mag_voter.vote(clock.now());
+
+                            if let Some(mag) = mag {
+                                let mag_update = store::create_update(&tokens::MAGNETIC, mag);
                                 events.send(Event::Store(mag_update));
                             }
+
+                            if last_mag_status.as_ref() != Some(&status) {
+                                match status.active {
+                                    Some(active) if status.rejected.is_empty() => {
+                                        info!("Compasses agree, using {active:?}")
+                                    }
+                                    Some(active) => warn!(
+                                        "Using {active:?} compass, rejected {:?}",
+                                        status.rejected
+                                    ),
+                                    None => {
+                                        warn!("No usable compass, rejected {:?}", status.rejected)
+                                    }
+                                }
+
+                                let status_update =
+                                    store::create_update(&tokens::MAG_STATUS, status.clone());
+                                events.send(Event::Store(status_update));
+                                last_mag_status = Some(status);
+                            }
                         }
                         Event::Exit => {
                             return;
@@ -0,0 +1,311 @@
+//! Cross-checks the magnetometers and picks the one to trust
+//!
+//! Compasses are compared by field strength and direction, their drivers rotate both into the
+//! robot's frame. With only two, a disagreement is settled by which one moved further from the
+//! field it read while it was trusted.
+
+use std::time::{Duration, Instant};
+
+use common::types::{Degrees, Gauss, MagFault, MagFrame, MagSource, MagStatus};
+
+/// Readings older than this do not count
+const MAX_AGE: Duration = Duration::from_millis(100);
+/// Field strengths an uncalibrated compass could plausibly read on earth
+pub const FIELD_RANGE: (Gauss, Gauss) = (Gauss(0.1), Gauss(1.0));
+/// How far apart the field strengths can be before one compass is voted out
+const MAX_DISAGREEMENT: Gauss = Gauss(0.15);
+/// How far apart the field directions can be, leaves room for uncalibrated hard iron offsets
+const MAX_ANGLE: Degrees = Degrees(30.0);
+/// Weight of each trusted reading in a compass's baseline field
+const BASELINE_WEIGHT: f64 = 0.01;
+
+/// In order of preference
+const SOURCES: [MagSource; 2] = [MagSource::Mmc5983, MagSource::Ak09915];
+
+#[derive(Debug, Default)]
+pub struct MagVoter {
+    compasses: [Compass; 2],
+    active: Option<MagSource>,
+}
+
+#[derive(Debug, Default)]
+struct Compass {
+    latest: Option<(Instant, MagFrame)>,
+    /// Field while the compass was trusted
+    baseline: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    idx: usize,
+    frame: MagFrame,
+    strength: f64,
+}
+
+impl MagVoter {
+    pub fn update(&mut self, source: MagSource, time: Instant, frame: MagFrame) {
+        self.compasses[index(source)].latest = Some((time, frame));
+    }
+
+    /// Votes on the latest readings, returns the trusted one and why the others were rejected
+    pub fn vote(&mut self, now: Instant) -> (Option<MagFrame>, MagStatus) {
+        let mut rejected = Vec::new();
+        let mut candidates = Vec::new();
+
+        for (idx, source) in SOURCES.into_iter().enumerate() {
+            let Some((time, frame)) = self.compasses[idx].latest else {
+                rejected.push((source, MagFault::Stale));
+                continue;
+            };
+            if now.saturating_duration_since(time) > MAX_AGE {
+                rejected.push((source, MagFault::Stale));
+                continue;
+            }
+
+            let strength = strength(&frame);
+            if strength < FIELD_RANGE.0 .0 || strength > FIELD_RANGE.1 .0 {
+                rejected.push((source, MagFault::OutOfRange(Gauss(strength))));
+                continue;
+            }
+
+            candidates.push(Candidate {
+                idx,
+                frame,
+                strength,
+            });
+        }
+
+        if let [first, second] = candidates[..] {
+            let strength_gap = (first.strength - second.strength).abs();
+            let angle = angle(&first.frame, &second.frame);
+
+            let fault = if strength_gap > MAX_DISAGREEMENT.0 {
+                Some(MagFault::Disagrees(Gauss(strength_gap)))
+            } else if angle > MAX_ANGLE.0 {
+                Some(MagFault::Misaligned(Degrees(angle)))
+            } else {
+                None
+            };
+
+            if let Some(fault) = fault {
+                let deviation = |candidate: Candidate| {
+                    self.compasses[candidate.idx]
+                        .baseline
+                        .map(|baseline| distance(&vector(&candidate.frame), &baseline))
+                        .unwrap_or(0.0)
+                };
+
+                // Ties go against the less preferred compass
+                let outlier = if deviation(second) >= deviation(first) {
+                    1
+                } else {
+                    0
+                };
+                let outlier = candidates.remove(outlier);
+
+                rejected.push((SOURCES[outlier.idx], fault));
+            }
+        }
+
+        for candidate in &candidates {
+            let field = vector(&candidate.frame);
+            let baseline = self.compasses[candidate.idx].baseline.get_or_insert(field);
+            for (baseline, field) in baseline.iter_mut().zip(field) {
+                *baseline += (field - *baseline) * BASELINE_WEIGHT;
+            }
+        }
+
+        // Stay on the current compass while it is trusted so the output does not flip between them
+        let active = candidates
+            .iter()
+            .find(|candidate| Some(SOURCES[candidate.idx]) == self.active)
+            .or(candidates.first());
+        self.active = active.map(|candidate| SOURCES[candidate.idx]);
+
+        let status = MagStatus {
+            active: self.active,
+            rejected,
+        };
+
+        (active.map(|candidate| candidate.frame), status)
+    }
+}
+
+fn index(source: MagSource) -> usize {
+    match source {
+        MagSource::Mmc5983 => 0,
+        MagSource::Ak09915 => 1,
+    }
+}
+
+fn strength(frame: &MagFrame) -> f64 {
+    (frame.mag_x.0.powi(2) + frame.mag_y.0.powi(2) + frame.mag_z.0.powi(2)).sqrt()
+}
+
+fn vector(frame: &MagFrame) -> [f64; 3] {
+    [frame.mag_x.0, frame.mag_y.0, frame.mag_z.0]
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Between the field directions in degrees, both have to be in range so neither is zero
+fn angle(a: &MagFrame, b: &MagFrame) -> f64 {
+    let dot = vector(a)
+        .iter()
+        .zip(vector(b))
+        .map(|(a, b)| a * b)
+        .sum::<f64>();
+    let cos = dot / (strength(a) * strength(b));
+
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(strength: f64) -> MagFrame {
+        MagFrame {
+            mag_x: Gauss(strength),
+            mag_y: Gauss(0.0),
+            mag_z: Gauss(0.0),
+        }
+    }
+
+    /// Feeds both compasses the same reading for a second so their baselines settle
+    fn settled(start: Instant, strength: f64) -> (MagVoter, Instant) {
+        let mut voter = MagVoter::default();
+        let mut now = start;
+
+        for _ in 0..100 {
+            now += Duration::from_millis(10);
+            voter.update(MagSource::Mmc5983, now, frame(strength));
+            voter.update(MagSource::Ak09915, now, frame(strength));
+            voter.vote(now);
+        }
+
+        (voter, now)
+    }
+
+    #[test]
+    fn agreeing_compasses_use_the_primary() {
+        let (mut voter, now) = settled(Instant::now(), 0.5);
+
+        let (mag, status) = voter.vote(now);
+        assert_eq!(mag, Some(frame(0.5)));
+        assert_eq!(status.active, Some(MagSource::Mmc5983));
+        assert!(status.rejected.is_empty());
+    }
+
+    #[test]
+    fn stale_compass_is_rejected() {
+        let mut voter = MagVoter::default();
+        let now = Instant::now();
+
+        voter.update(MagSource::Ak09915, now, frame(0.5));
+
+        let (mag, status) = voter.vote(now);
+        assert_eq!(mag, Some(frame(0.5)));
+        assert_eq!(status.active, Some(MagSource::Ak09915));
+        assert_eq!(status.rejected, vec![(MagSource::Mmc5983, MagFault::Stale)]);
+    }
+
+    #[test]
+    fn out_of_range_compass_is_rejected() {
+        let (mut voter, now) = settled(Instant::now(), 0.5);
+
+        voter.update(MagSource::Mmc5983, now, frame(3.0));
+
+        let (mag, status) = voter.vote(now);
+        assert_eq!(mag, Some(frame(0.5)));
+        assert_eq!(status.active, Some(MagSource::Ak09915));
+        assert_eq!(
+            status.rejected,
+            vec![(MagSource::Mmc5983, MagFault::OutOfRange(Gauss(3.0)))]
+        );
+    }
+
+    #[test]
+    fn compass_that_jumps_is_voted_out() {
+        for (jumped, trusted) in [
+            (MagSource::Mmc5983, MagSource::Ak09915),
+            (MagSource::Ak09915, MagSource::Mmc5983),
+        ] {
+            let (mut voter, now) = settled(Instant::now(), 0.5);
+
+            voter.update(jumped, now, frame(0.8));
+
+            let (mag, status) = voter.vote(now);
+            assert_eq!(mag, Some(frame(0.5)));
+            assert_eq!(status.active, Some(trusted));
+            assert!(matches!(
+                status.rejected[..],
+                [(source, MagFault::Disagrees(_))] if source == jumped
+            ));
+        }
+    }
+
+    #[test]
+    fn compass_that_turns_is_voted_out() {
+        let turned = MagFrame {
+            mag_x: Gauss(0.0),
+            mag_y: Gauss(0.5),
+            mag_z: Gauss(0.0),
+        };
+
+        for (faulty, trusted) in [
+            (MagSource::Mmc5983, MagSource::Ak09915),
+            (MagSource::Ak09915, MagSource::Mmc5983),
+        ] {
+            let (mut voter, now) = settled(Instant::now(), 0.5);
+
+            // Same strength, so only the direction gives it away
+            voter.update(faulty, now, turned);
+
+            let (mag, status) = voter.vote(now);
+            assert_eq!(mag, Some(frame(0.5)));
+            assert_eq!(status.active, Some(trusted));
+            assert!(matches!(
+                status.rejected[..],
+                [(source, MagFault::Misaligned(Degrees(angle)))]
+                    if source == faulty && (angle - 90.0).abs() < 1e-6
+            ));
+        }
+    }
+
+    #[test]
+    fn small_direction_differences_are_tolerated() {
+        let (mut voter, now) = settled(Instant::now(), 0.5);
+
+        // About 11 degrees off
+        let offset = MagFrame {
+            mag_x: Gauss(0.49),
+            mag_y: Gauss(0.1),
+            mag_z: Gauss(0.0),
+        };
+        voter.update(MagSource::Ak09915, now, offset);
+
+        let (_, status) = voter.vote(now);
+        assert_eq!(status.active, Some(MagSource::Mmc5983));
+        assert!(status.rejected.is_empty());
+    }
+
+    #[test]
+    fn active_compass_is_kept_once_both_agree_again() {
+        let (mut voter, now) = settled(Instant::now(), 0.5);
+
+        voter.update(MagSource::Mmc5983, now, frame(0.8));
+        voter.vote(now);
+
+        voter.update(MagSource::Mmc5983, now, frame(0.5));
+        let (_, status) = voter.vote(now);
+        assert_eq!(status.active, Some(MagSource::Ak09915));
+        assert!(status.rejected.is_empty());
+    }
+}
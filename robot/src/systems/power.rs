@@ -0,0 +1,269 @@
+use std::{
+    thread::Scope,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use common::{
+    store::{self, tokens},
+    types::{Amps, PowerConfig, PowerFault, PowerFrame, Volts, WattHours},
+};
+use tracing::{info, span, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    peripheral::ads1115::Ads1115,
+    systems::{clock, debounce::Debounce, scheduler},
+    SystemId,
+};
+
+use super::System;
+
+const PERIOD: Duration = Duration::from_millis(100);
+/// How long a threshold has to stay crossed before a fault is raised or cleared, thrusters
+/// spinning up cause short sags and spikes
+const FAULT_HOLD: Duration = Duration::from_secs(2);
+/// Longest gap integrated over, anything longer is a dropout
+const MAX_STEP: Duration = Duration::from_secs(1);
+
+/// Publishes supply voltage, current and energy used from the power sense module
+pub struct PowerSystem;
+
+impl System for PowerSystem {
+    const ID: SystemId = SystemId::Power;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listener = events.take_listner().unwrap();
+        let ticks = scheduler::schedule("Power", PERIOD);
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Power monitor thread");
+
+            let adc = Ads1115::new(Ads1115::I2C_BUS, Ads1115::I2C_ADDRESS);
+            let mut adc = match adc {
+                Ok(adc) => adc,
+                Err(err) => {
+                    events.send(Event::Error(err.context("ADS1115")));
+                    return;
+                }
+            };
+
+            let clock = clock::clock();
+            let mut config = PowerConfig::default();
+            let mut monitor = PowerMonitor::default();
+
+            let update = store::create_update(&tokens::POWER_FAULTS, monitor.faults());
+            events.send(Event::Store(update));
+
+            for _tick in ticks {
+                for event in listener.try_iter() {
+                    match &*event {
+                        Event::Store(update) => {
+                            if update.0 == tokens::POWER_CONFIG.0 {
+                                // Removing the config restores the defaults
+                                config = store::handle_update(&tokens::POWER_CONFIG, update)
+                                    .map(|it| *it)
+                                    .unwrap_or_default();
+
+                                info!("Applying power config: {config:?}");
+                            }
+                        }
+                        Event::SyncStore => {
+                            let update =
+                                store::create_update(&tokens::POWER_FAULTS, monitor.faults());
+                            events.send(Event::Store(update));
+                        }
+                        _ => {}
+                    }
+                }
+
+                let voltage = adc.read_channel(config.voltage_channel);
+                let current = adc.read_channel(config.current_channel);
+
+                let (voltage, current) = match (voltage, current) {
+                    (Ok(voltage), Ok(current)) => (voltage, current),
+                    (Err(err), _) | (_, Err(err)) => {
+                        events.send(Event::Error(err.context("Could not read power")));
+                        continue;
+                    }
+                };
+
+                let voltage = Volts(voltage.0 * config.voltage_scale);
+                let current = Amps((current.0 - config.current_offset.0) * config.current_scale);
+
+                let (frame, changes) = monitor.update(clock.now(), voltage, current, &config);
+
+                let update = store::create_update(&tokens::POWER, frame);
+                events.send(Event::Store(update));
+
+                if changes.is_empty() {
+                    continue;
+                }
+
+                for (fault, active) in changes {
+                    match (fault, active) {
+                        (PowerFault::LowVoltage, true) => {
+                            events.send(Event::Error(anyhow!(
+                                "Supply voltage low, {} is below {}",
+                                frame.voltage,
+                                config.low_voltage
+                            )));
+                        }
+                        (PowerFault::OverCurrent, true) => {
+                            events.send(Event::Error(anyhow!(
+                                "Over current, {} is above {}",
+                                frame.current,
+                                config.max_current
+                            )));
+                        }
+                        (fault, false) => {
+                            info!("{fault:?} cleared");
+                        }
+                    }
+                }
+
+                let update = store::create_update(&tokens::POWER_FAULTS, monitor.faults());
+                events.send(Event::Store(update));
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Integrates energy used and checks the supply against the configured thresholds
+pub struct PowerMonitor {
+    /// In watt hours
+    energy_used: f64,
+    last_sample: Option<Instant>,
+
+    low_voltage: Debounce,
+    over_current: Debounce,
+}
+
+impl Default for PowerMonitor {
+    fn default() -> Self {
+        Self {
+            energy_used: 0.0,
+            last_sample: None,
+            low_voltage: Debounce::new(FAULT_HOLD),
+            over_current: Debounce::new(FAULT_HOLD),
+        }
+    }
+}
+
+impl PowerMonitor {
+    /// Returns the frame for the sample and any faults that were raised or cleared
+    pub fn update(
+        &mut self,
+        now: Instant,
+        voltage: Volts,
+        current: Amps,
+        config: &PowerConfig,
+    ) -> (PowerFrame, Vec<(PowerFault, bool)>) {
+        if let Some(last) = self.last_sample {
+            let step = now.saturating_duration_since(last).min(MAX_STEP);
+            self.energy_used += voltage.0 * current.0 * step.as_secs_f64() / 3600.0;
+        }
+        self.last_sample = Some(now);
+
+        let frame = PowerFrame {
+            voltage,
+            current,
+            energy_used: WattHours(self.energy_used),
+        };
+
+        let mut changes = Vec::new();
+        if let Some(active) = self.low_voltage.update(voltage < config.low_voltage, now) {
+            changes.push((PowerFault::LowVoltage, active));
+        }
+        if let Some(active) = self.over_current.update(current > config.max_current, now) {
+            changes.push((PowerFault::OverCurrent, active));
+        }
+
+        (frame, changes)
+    }
+
+    pub fn faults(&self) -> Vec<PowerFault> {
+        let mut faults = Vec::new();
+
+        if self.low_voltage.active() {
+            faults.push(PowerFault::LowVoltage);
+        }
+        if self.over_current.active() {
+            faults.push(PowerFault::OverCurrent);
+        }
+
+        faults
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrates_energy_used() {
+        let config = PowerConfig::default();
+        let mut monitor = PowerMonitor::default();
+        let start = Instant::now();
+
+        // 16V at 10A for an hour
+        let mut frame = None;
+        for step in 0..=36_000 {
+            let now = start + PERIOD * step;
+            frame = Some(monitor.update(now, Volts(16.0), Amps(10.0), &config).0);
+        }
+
+        let energy_used = frame.unwrap().energy_used.0;
+        assert!((energy_used - 160.0).abs() < 1e-6, "{energy_used}");
+    }
+
+    #[test]
+    fn dropouts_are_not_integrated() {
+        let config = PowerConfig::default();
+        let mut monitor = PowerMonitor::default();
+        let start = Instant::now();
+
+        monitor.update(start, Volts(16.0), Amps(10.0), &config);
+        let (frame, _) = monitor.update(
+            start + Duration::from_secs(60),
+            Volts(16.0),
+            Amps(10.0),
+            &config,
+        );
+
+        let expected = 16.0 * 10.0 * MAX_STEP.as_secs_f64() / 3600.0;
+        assert!((frame.energy_used.0 - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn faults_are_debounced() {
+        let config = PowerConfig::default();
+        let mut monitor = PowerMonitor::default();
+        let start = Instant::now();
+
+        // A short sag is ignored
+        let (_, changes) = monitor.update(start, Volts(11.0), Amps(5.0), &config);
+        assert!(changes.is_empty());
+        let (_, changes) = monitor.update(start + PERIOD, Volts(15.0), Amps(5.0), &config);
+        assert!(changes.is_empty());
+
+        let sag = start + Duration::from_secs(1);
+        monitor.update(sag, Volts(11.0), Amps(5.0), &config);
+        let (_, changes) = monitor.update(sag + FAULT_HOLD, Volts(11.0), Amps(5.0), &config);
+        assert_eq!(changes, vec![(PowerFault::LowVoltage, true)]);
+        assert_eq!(monitor.faults(), vec![PowerFault::LowVoltage]);
+
+        let recovered = sag + FAULT_HOLD + PERIOD;
+        monitor.update(recovered, Volts(15.0), Amps(5.0), &config);
+        assert_eq!(monitor.faults(), vec![PowerFault::LowVoltage]);
+        let (_, changes) = monitor.update(recovered + FAULT_HOLD, Volts(15.0), Amps(5.0), &config);
+        assert_eq!(changes, vec![(PowerFault::LowVoltage, false)]);
+        assert!(monitor.faults().is_empty());
+    }
+}
@@ -0,0 +1,396 @@
+//! Drives every fixed rate loop on the robot from a single thread
+//!
+//! Tasks get a channel of `Tick`s from `schedule`. A tick counts as executing from when it is
+//! released until it is dropped, so a task should hold it for as long as it works on it.
+
+use std::{
+    mem,
+    sync::{Arc, Mutex, MutexGuard},
+    thread::Scope,
+    time::{Duration, Instant},
+};
+
+use common::{
+    clock::SharedClock,
+    store::{tokens, Store},
+    types::TaskStats,
+};
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+use fxhash::FxHashMap as HashMap;
+use tracing::{span, warn, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{clock, stop, System},
+    SystemId,
+};
+
+/// How often task statistics are published
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long to wait when nothing is scheduled
+const IDLE_PERIOD: Duration = Duration::from_millis(100);
+
+static TASKS: Mutex<Vec<Task>> = Mutex::new(Vec::new());
+
+/// Registers a task to be ticked every `period`
+///
+/// A tick is skipped and counted as a deadline miss if the task has not picked up the previous
+/// one by the time the next is due.
+pub fn schedule(name: &'static str, period: Duration) -> Receiver<Tick> {
+    let (task, ticks) = Task::new(name, period);
+    lock_tasks().push(task);
+
+    ticks
+}
+
+/// Permission for a task to run one period of work
+pub struct Tick {
+    scheduled: Instant,
+    released: Instant,
+    period: Duration,
+    /// `None` for ticks that were never delivered
+    window: Option<Arc<Mutex<Window>>>,
+    clock: SharedClock,
+}
+
+impl Tick {
+    /// When this tick was due, spaced exactly one period from the last
+    pub fn scheduled(&self) -> Instant {
+        self.scheduled
+    }
+}
+
+impl Drop for Tick {
+    fn drop(&mut self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+
+        let finished = self.clock.now();
+        let execution = finished.saturating_duration_since(self.released);
+
+        let mut window = lock(window);
+        window.executions += 1;
+        window.total_execution += execution;
+        window.max_execution = window.max_execution.max(execution);
+        if finished > self.scheduled + self.period {
+            window.deadline_misses += 1;
+        }
+    }
+}
+
+struct Task {
+    name: &'static str,
+    period: Duration,
+    /// `None` until the scheduler first sees the task
+    next_release: Option<Instant>,
+    ticks: Sender<Tick>,
+    window: Arc<Mutex<Window>>,
+    total_deadline_misses: u64,
+}
+
+impl Task {
+    fn new(name: &'static str, period: Duration) -> (Self, Receiver<Tick>) {
+        let (tx, rx) = bounded(1);
+
+        let task = Self {
+            name,
+            period,
+            next_release: None,
+            ticks: tx,
+            window: Arc::new(Mutex::new(Window::default())),
+            total_deadline_misses: 0,
+        };
+
+        (task, rx)
+    }
+}
+
+/// Measurements since the last report
+#[derive(Default)]
+struct Window {
+    ticks: u64,
+    deadline_misses: u64,
+    executions: u32,
+    total_execution: Duration,
+    max_execution: Duration,
+    total_jitter: Duration,
+    max_jitter: Duration,
+}
+
+pub struct SchedulerSystem;
+
+impl System for SchedulerSystem {
+    const ID: SystemId = SystemId::Scheduler;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let _ = events.take_listner();
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Scheduler thread");
+
+            let clock = clock::clock();
+            let mut store = Store::with_clock(
+                move |update| {
+                    events.send(Event::Store(update));
+                },
+                clock.clone(),
+            );
+
+            let mut last_report = clock.now();
+
+            while !stop::world_stopped() {
+                let now = clock.now();
+                let next_wake = release_all(&mut lock_tasks(), now, &clock);
+
+                if now.saturating_duration_since(last_report) >= REPORT_INTERVAL {
+                    store.insert(&tokens::SCHEDULER_STATS, report(&mut lock_tasks()));
+                    last_report = now;
+                }
+
+                clock.sleep_until(next_wake);
+            }
+
+            // Disconnects the tasks waiting on ticks
+            lock_tasks().clear();
+        });
+
+        Ok(())
+    }
+}
+
+/// Releases every due tick, returns when the scheduler next has to wake up
+fn release_all(tasks: &mut Vec<Task>, now: Instant, clock: &SharedClock) -> Instant {
+    let mut next_wake = now + IDLE_PERIOD;
+
+    tasks.retain_mut(|task| {
+        let Some(next_release) = release(task, now, clock) else {
+            // The task has stopped listening
+            return false;
+        };
+
+        next_wake = next_wake.min(next_release);
+        true
+    });
+
+    next_wake
+}
+
+/// Sends the task its tick if one is due, returns when the next one is
+///
+/// Returns `None` once the task has dropped its receiver
+fn release(task: &mut Task, now: Instant, clock: &SharedClock) -> Option<Instant> {
+    let scheduled = *task.next_release.get_or_insert(now);
+    if scheduled > now {
+        return Some(scheduled);
+    }
+
+    // Periods that passed entirely while the scheduler was behind are skipped
+    let behind = now - scheduled;
+    let skipped = (behind.as_nanos() / task.period.as_nanos()) as u32;
+    let scheduled = scheduled + task.period * skipped;
+    let jitter = now - scheduled;
+
+    let tick = Tick {
+        scheduled,
+        released: now,
+        period: task.period,
+        window: Some(task.window.clone()),
+        clock: clock.clone(),
+    };
+
+    let dropped = match task.ticks.try_send(tick) {
+        Ok(()) => 0,
+        // Still busy with the last tick
+        Err(TrySendError::Full(mut tick)) => {
+            tick.window = None;
+            1
+        }
+        Err(TrySendError::Disconnected(mut tick)) => {
+            tick.window = None;
+            return None;
+        }
+    };
+
+    {
+        let mut window = lock(&task.window);
+        window.ticks += 1;
+        window.deadline_misses += skipped as u64 + dropped;
+        window.total_jitter += jitter;
+        window.max_jitter = window.max_jitter.max(jitter);
+    }
+
+    let next_release = scheduled + task.period;
+    task.next_release = Some(next_release);
+
+    Some(next_release)
+}
+
+fn report(tasks: &mut [Task]) -> HashMap<String, TaskStats> {
+    tasks
+        .iter_mut()
+        .map(|task| {
+            let window = mem::take(&mut *lock(&task.window));
+
+            if window.deadline_misses > 0 {
+                warn!(
+                    "{} missed {} deadlines in the last {REPORT_INTERVAL:?}",
+                    task.name, window.deadline_misses
+                );
+            }
+            task.total_deadline_misses += window.deadline_misses;
+
+            let stats = TaskStats {
+                period: task.period,
+                ticks: window.ticks,
+                deadline_misses: window.deadline_misses,
+                total_deadline_misses: task.total_deadline_misses,
+                mean_execution: window
+                    .total_execution
+                    .checked_div(window.executions)
+                    .unwrap_or_default(),
+                max_execution: window.max_execution,
+                mean_jitter: window
+                    .total_jitter
+                    .checked_div(window.ticks as u32)
+                    .unwrap_or_default(),
+                max_jitter: window.max_jitter,
+            };
+
+            (task.name.to_owned(), stats)
+        })
+        .collect()
+}
+
+fn lock_tasks() -> MutexGuard<'static, Vec<Task>> {
+    lock(&TASKS)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // Statistics are still usable after a panic elsewhere
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use common::clock::{Clock, ManualClock};
+
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    fn clock() -> (Arc<ManualClock>, SharedClock) {
+        let clock = Arc::new(ManualClock::new());
+        let shared: SharedClock = clock.clone();
+
+        (clock, shared)
+    }
+
+    fn stats(task: &mut Task) -> TaskStats {
+        report(std::slice::from_mut(task)).remove("Test").unwrap()
+    }
+
+    #[test]
+    fn on_time_tick() {
+        let (clock, shared) = clock();
+        let (mut task, ticks) = Task::new("Test", PERIOD);
+        let start = clock.now();
+
+        assert_eq!(release(&mut task, start, &shared), Some(start + PERIOD));
+        let tick = ticks.try_recv().unwrap();
+        assert_eq!(tick.scheduled(), start);
+
+        // Not due yet
+        clock.advance(Duration::from_millis(4));
+        assert_eq!(
+            release(&mut task, clock.now(), &shared),
+            Some(start + PERIOD)
+        );
+        assert!(ticks.try_recv().is_err());
+        drop(tick);
+
+        let stats = stats(&mut task);
+        assert_eq!(stats.ticks, 1);
+        assert_eq!(stats.deadline_misses, 0);
+        assert_eq!(stats.max_execution, Duration::from_millis(4));
+        assert_eq!(stats.max_jitter, Duration::ZERO);
+    }
+
+    #[test]
+    fn late_scheduler_skips_periods() {
+        let (clock, shared) = clock();
+        let (mut task, ticks) = Task::new("Test", PERIOD);
+        let start = clock.now();
+
+        release(&mut task, start, &shared);
+        drop(ticks.try_recv().unwrap());
+
+        // Two whole periods pass before the scheduler gets back to the task
+        clock.advance(Duration::from_millis(35));
+        let next = release(&mut task, clock.now(), &shared);
+        assert_eq!(next, Some(start + PERIOD * 4));
+        let tick = ticks.try_recv().unwrap();
+        assert_eq!(tick.scheduled(), start + PERIOD * 3);
+        drop(tick);
+
+        let stats = stats(&mut task);
+        assert_eq!(stats.ticks, 2);
+        assert_eq!(stats.deadline_misses, 2);
+        assert_eq!(stats.total_deadline_misses, 2);
+        assert_eq!(stats.max_jitter, Duration::from_millis(5));
+    }
+
+    #[test]
+    fn busy_task_misses_deadline() {
+        let (clock, shared) = clock();
+        let (mut task, ticks) = Task::new("Test", PERIOD);
+        let start = clock.now();
+
+        release(&mut task, start, &shared);
+        let held = ticks.try_recv().unwrap();
+
+        // The next tick waits in the channel while the first is still held
+        clock.advance(PERIOD);
+        release(&mut task, clock.now(), &shared);
+        clock.advance(Duration::from_millis(5));
+        drop(held);
+
+        // The task never picked up the waiting tick, so this one is skipped
+        clock.advance(Duration::from_millis(5));
+        release(&mut task, clock.now(), &shared);
+        assert_eq!(ticks.try_recv().unwrap().scheduled(), start + PERIOD);
+        assert!(ticks.try_recv().is_err());
+
+        let stats = stats(&mut task);
+        assert_eq!(stats.ticks, 3);
+        // One for finishing late, one for the skipped tick
+        assert_eq!(stats.deadline_misses, 2);
+        assert_eq!(stats.max_execution, Duration::from_millis(15));
+    }
+
+    #[test]
+    fn dropped_receiver_is_removed() {
+        let (clock, shared) = clock();
+        let (kept, kept_ticks) = Task::new("Kept", PERIOD);
+        let (stopped, stopped_ticks) = Task::new("Stopped", PERIOD / 2);
+        let mut tasks = vec![kept, stopped];
+
+        let now = clock.now();
+        assert_eq!(release_all(&mut tasks, now, &shared), now + PERIOD / 2);
+        assert_eq!(tasks.len(), 2);
+        drop(stopped_ticks);
+        drop(kept_ticks.try_recv().unwrap());
+
+        clock.advance(PERIOD);
+        let now = clock.now();
+        assert_eq!(release_all(&mut tasks, now, &shared), now + PERIOD);
+        let names: Vec<_> = tasks.iter().map(|task| task.name).collect();
+        assert_eq!(names, ["Kept"]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::{Duration, Instant};
+
+use common::types::{EnclosureFrame, Mbar, SealTestConfig, SealTestReport, SealTestState};
+
+/// Offset between celsius and kelvin
+const ZERO_CELSIUS: f64 = 273.15;
+
+/// Watches the enclosure pressure after it was pumped down, a sealed enclosure holds its vacuum
+pub struct SealTest {
+    config: SealTestConfig,
+    started: Instant,
+    window: Option<Window>,
+    report: SealTestReport,
+}
+
+struct Window {
+    started: Instant,
+    start_pressure: Mbar,
+    /// In kelvin
+    start_temperature: f64,
+    /// Seconds into the window and the corrected pressure
+    samples: Vec<(f64, f64)>,
+}
+
+impl SealTest {
+    pub fn new(config: SealTestConfig, now: Instant) -> Self {
+        Self {
+            config,
+            started: now,
+            window: None,
+            report: SealTestReport {
+                state: SealTestState::Settling,
+                start_pressure: Mbar(0.0),
+                pressure: Mbar(0.0),
+                rise_rate: Mbar(0.0),
+                remaining: config.settle_time,
+            },
+        }
+    }
+
+    /// Report for the test in its current state
+    pub fn progress(&self) -> SealTestReport {
+        self.report.clone()
+    }
+
+    pub fn abort(&self, reason: impl Into<String>) -> SealTestReport {
+        SealTestReport {
+            state: SealTestState::Aborted(reason.into()),
+            remaining: Duration::ZERO,
+            ..self.report.clone()
+        }
+    }
+
+    pub fn update(&mut self, now: Instant, frame: &EnclosureFrame) -> SealTestReport {
+        let elapsed = now.saturating_duration_since(self.started);
+
+        if elapsed < self.config.settle_time {
+            self.report = SealTestReport {
+                state: SealTestState::Settling,
+                start_pressure: frame.pressure,
+                pressure: frame.pressure,
+                rise_rate: Mbar(0.0),
+                remaining: self.config.settle_time - elapsed,
+            };
+
+            return self.progress();
+        }
+
+        let window = match &mut self.window {
+            Some(window) => window,
+            None => {
+                if frame.pressure > self.config.max_start_pressure {
+                    self.report.pressure = frame.pressure;
+                    self.report = self.abort(format!(
+                        "Enclosure is not pumped down, at {}",
+                        frame.pressure
+                    ));
+
+                    return self.progress();
+                }
+
+                self.window.insert(Window {
+                    started: now,
+                    start_pressure: frame.pressure,
+                    start_temperature: frame.temperature.0 + ZERO_CELSIUS,
+                    samples: Vec::new(),
+                })
+            }
+        };
+
+        let time = now.saturating_duration_since(window.started);
+
+        // Pressure follows the absolute temperature, warming electronics would otherwise look
+        // like a leak
+        let pressure =
+            frame.pressure.0 * window.start_temperature / (frame.temperature.0 + ZERO_CELSIUS);
+        window.samples.push((time.as_secs_f64(), pressure));
+
+        let rise_rate = Mbar(slope(&window.samples) * 60.0);
+
+        let state = if time < self.config.window {
+            SealTestState::Measuring
+        } else if rise_rate <= self.config.max_rise_rate {
+            SealTestState::Passed
+        } else {
+            SealTestState::Failed
+        };
+
+        self.report = SealTestReport {
+            state,
+            start_pressure: window.start_pressure,
+            pressure: Mbar(pressure),
+            rise_rate,
+            remaining: self.config.window.saturating_sub(time),
+        };
+
+        self.progress()
+    }
+}
+
+/// Least squares slope, less sensitive to noise than the first and last samples
+fn slope(samples: &[(f64, f64)]) -> f64 {
+    let count = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let (covariance, variance) =
+        samples
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                (
+                    covariance + (x - mean_x) * (y - mean_y),
+                    variance + (x - mean_x).powi(2),
+                )
+            });
+
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::Celsius;
+
+    use super::*;
+
+    const CONFIG: SealTestConfig = SealTestConfig {
+        settle_time: Duration::from_secs(10),
+        window: Duration::from_secs(60),
+        max_start_pressure: Mbar(850.0),
+        max_rise_rate: Mbar(1.0),
+    };
+
+    /// Runs a test to completion with readings every second
+    fn run(reading: impl Fn(f64) -> EnclosureFrame) -> SealTestReport {
+        let start = Instant::now();
+        let mut test = SealTest::new(CONFIG, start);
+
+        for second in 0.. {
+            let report = test.update(start + Duration::from_secs(second), &reading(second as f64));
+
+            if let SealTestState::Settling | SealTestState::Measuring = report.state {
+                continue;
+            }
+            return report;
+        }
+
+        unreachable!()
+    }
+
+    fn frame(pressure: f64, temperature: f64) -> EnclosureFrame {
+        EnclosureFrame {
+            pressure: Mbar(pressure),
+            temperature: Celsius(temperature),
+        }
+    }
+
+    #[test]
+    fn sealed_enclosure_passes() {
+        let report = run(|second| frame(600.0 + (second * 0.7).sin() * 0.05, 25.0));
+
+        assert_eq!(report.state, SealTestState::Passed);
+        assert!(report.rise_rate.0.abs() < 0.1, "{}", report.rise_rate);
+    }
+
+    #[test]
+    fn leaking_enclosure_fails() {
+        let report = run(|second| frame(600.0 + second / 30.0, 25.0));
+
+        assert_eq!(report.state, SealTestState::Failed);
+        assert!(
+            (report.rise_rate.0 - 2.0).abs() < 0.01,
+            "{}",
+            report.rise_rate
+        );
+    }
+
+    #[test]
+    fn warming_enclosure_passes() {
+        // A sealed enclosure warming by a degree a minute
+        let report = run(|second| {
+            let temperature = 25.0 + second / 60.0;
+            frame(
+                600.0 * (temperature + ZERO_CELSIUS) / (25.0 + ZERO_CELSIUS),
+                temperature,
+            )
+        });
+
+        assert_eq!(report.state, SealTestState::Passed);
+        assert!(report.rise_rate.0.abs() < 0.01, "{}", report.rise_rate);
+    }
+
+    #[test]
+    fn unpumped_enclosure_aborts() {
+        let report = run(|_| frame(1013.0, 25.0));
+
+        assert!(matches!(report.state, SealTestState::Aborted(_)));
+    }
+}
@@ -0,0 +1,535 @@
+use std::{
+    mem,
+    thread::Scope,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::anyhow;
+use common::{
+    store::{self, tokens, Store, Token, UpdateCallback},
+    types::{
+        Celsius, DepthFrame, Dps, GForce, Gauss, HoldDisengaged, ImuSettings, InertialFrame,
+        MagFrame, Mbar, Meters, SensorFault, SensorId,
+    },
+};
+use fxhash::FxHashMap as HashMap;
+use tracing::{info, span, warn, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    peripheral::ms5937,
+    systems::{clock, debounce::Debounce, orientation::mag_vote, scheduler},
+    SystemId,
+};
+
+use super::System;
+
+const PERIOD: Duration = Duration::from_millis(50);
+/// Longest gap between readings before a sensor counts as stale
+const MAX_AGE: Duration = Duration::from_millis(500);
+/// Identical readings for this long mean the sensor stopped updating
+const STUCK_TIME: Duration = Duration::from_secs(3);
+/// Faults seen in single readings stay reported this long after the last one
+const FAULT_HOLD: Duration = Duration::from_secs(2);
+
+/// Depths the sensor can plausibly report, above the surface is only tare drift
+const DEPTH_RANGE: (Meters, Meters) = (Meters(-1.0), Meters(300.0));
+/// Fastest the robot can change depth, in meters per second
+const MAX_DEPTH_RATE: f64 = 2.0;
+/// Fraction of full scale an axis is saturated at
+const SATURATION: f64 = 0.98;
+/// Rotation rates below this count as the robot being still
+const STILL_GYRO: Dps = Dps(2.0);
+/// Accelerometer magnitudes plausible while still
+const STILL_ACCEL: (GForce, GForce) = (GForce(0.8), GForce(1.2));
+/// How long the accelerometer has to disagree with the gyro, ignores bumps
+const INCONSISTENT_HOLD: Duration = Duration::from_secs(1);
+
+const SENSORS: [SensorId; 3] = [SensorId::Inertial, SensorId::Depth, SensorId::Magnetometer];
+
+/// Checks sensor readings for values the hardware or the robot could not produce
+pub struct SensorHealthSystem;
+
+impl System for SensorHealthSystem {
+    const ID: SystemId = SystemId::SensorHealth;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listener = events.take_listner().unwrap();
+        let ticks = scheduler::schedule("Sensor health", PERIOD);
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Sensor health thread");
+
+            let clock = clock::clock();
+            let mut settings = ImuSettings::default();
+            let mut monitor = PlausibilityMonitor::default();
+            let mut last_health: Option<HashMap<SensorId, Vec<SensorFault>>> = None;
+
+            for _tick in ticks {
+                for event in listener.try_iter() {
+                    match &*event {
+                        Event::Store(update) => {
+                            let now = clock.now();
+
+                            if let Some(frame) = store::handle_update(&tokens::RAW_INERTIAL, update)
+                            {
+                                monitor.update_inertial(*frame, &settings, now);
+                            } else if let Some(frame) =
+                                store::handle_update(&tokens::RAW_DEPTH, update)
+                            {
+                                monitor.update_depth(*frame, now);
+                            } else if let Some(frame) =
+                                store::handle_update(&tokens::MAGNETIC, update)
+                            {
+                                monitor.update_mag(*frame, now);
+                            } else if update.0 == tokens::IMU_SETTINGS.0 {
+                                settings = store::handle_update(&tokens::IMU_SETTINGS, update)
+                                    .map(|it| *it)
+                                    .unwrap_or_default();
+                            }
+                        }
+                        Event::SyncStore => {
+                            last_health = None;
+                        }
+                        _ => {}
+                    }
+                }
+
+                let health = monitor.health(clock.now());
+                if last_health.as_ref() == Some(&health) {
+                    continue;
+                }
+
+                let previous = last_health.replace(health.clone());
+                if let Some(previous) = previous {
+                    for sensor in SENSORS {
+                        let before = &previous[&sensor];
+                        let after = &health[&sensor];
+
+                        for fault in after.iter().filter(|it| !before.contains(it)) {
+                            events.send(Event::Error(anyhow!("{sensor:?} implausible: {fault}")));
+                        }
+                        if !before.is_empty() && after.is_empty() {
+                            info!("{sensor:?} readings plausible again");
+                        }
+                    }
+                }
+
+                let update = store::create_update(&tokens::SENSOR_HEALTH, health);
+                events.send(Event::Store(update));
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// The first fault on any of `sensors`, no health data counts as healthy
+pub fn fault<C: UpdateCallback>(
+    store: &Store<C>,
+    sensors: &[SensorId],
+) -> Option<(SensorId, SensorFault)> {
+    let health = store.get(&tokens::SENSOR_HEALTH)?;
+
+    sensors.iter().find_map(|sensor| {
+        health
+            .get(sensor)
+            .and_then(|faults| faults.first())
+            .map(|fault| (*sensor, *fault))
+    })
+}
+
+/// Keeps a hold off after a sensor fault until the surface changes the hold's mode
+///
+/// The surface owns the mode, so the robot cannot switch it off itself. The latch stands in for
+/// it and is published on its token so the surface can drop the mode.
+pub struct FaultLatch<M> {
+    name: &'static str,
+    sensors: &'static [SensorId],
+    token: Token<HoldDisengaged>,
+    /// Mode the hold was dropped from
+    disengaged_from: Option<M>,
+}
+
+impl<M: Copy + PartialEq> FaultLatch<M> {
+    pub fn new(
+        name: &'static str,
+        sensors: &'static [SensorId],
+        token: Token<HoldDisengaged>,
+    ) -> Self {
+        Self {
+            name,
+            sensors,
+            token,
+            disengaged_from: None,
+        }
+    }
+
+    /// Whether the hold has to stay off, `engaged` is if `mode` holds anything
+    pub fn disengaged<C: UpdateCallback>(
+        &mut self,
+        store: &mut Store<C>,
+        mode: Option<M>,
+        engaged: bool,
+    ) -> bool {
+        // The surface changing the mode, to acknowledge or to re-engage, clears the latch
+        if self.disengaged_from.is_some() && self.disengaged_from != mode {
+            info!("{} can be engaged again", self.name);
+            self.disengaged_from = None;
+            store.remove(&self.token);
+        }
+
+        if let (None, Some(mode), true) = (self.disengaged_from, mode, engaged) {
+            if let Some((sensor, fault)) = fault(store, self.sensors) {
+                warn!("{} disengaged, {sensor:?} implausible: {fault}", self.name);
+                self.disengaged_from = Some(mode);
+                store.insert(&self.token, HoldDisengaged { sensor, fault });
+            }
+        }
+
+        self.disengaged_from.is_some()
+    }
+}
+
+/// Applies range, rate of change, stuck value and cross sensor checks to each sensor
+pub struct PlausibilityMonitor {
+    inertial: SensorTrack<InertialFrame>,
+    /// Only the measurements, the sample time changes even when they are stuck
+    depth: SensorTrack<(Mbar, Celsius)>,
+    mag: SensorTrack<MagFrame>,
+
+    /// Pressure and sample time of the last depth reading, frames are handled in bursts so
+    /// the rate has to come from when they were sampled
+    last_pressure: Option<(Mbar, SystemTime)>,
+
+    accel_inconsistent: Debounce,
+    /// Accelerometer magnitude when it started disagreeing with the gyro
+    inconsistent_accel: GForce,
+}
+
+impl Default for PlausibilityMonitor {
+    fn default() -> Self {
+        Self {
+            inertial: SensorTrack::default(),
+            depth: SensorTrack::default(),
+            mag: SensorTrack::default(),
+            last_pressure: None,
+            accel_inconsistent: Debounce::new(INCONSISTENT_HOLD),
+            inconsistent_accel: GForce(0.0),
+        }
+    }
+}
+
+impl PlausibilityMonitor {
+    pub fn update_inertial(&mut self, frame: InertialFrame, settings: &ImuSettings, now: Instant) {
+        self.inertial.update(frame, now);
+
+        let accel = [frame.accel_x, frame.accel_y, frame.accel_z];
+        let gyro = [frame.gyro_x, frame.gyro_y, frame.gyro_z];
+
+        let max_accel = settings.accel_range.full_scale().0 * SATURATION;
+        if let Some(axis) = accel.into_iter().find(|it| it.0.abs() >= max_accel) {
+            self.inertial.flag(SensorFault::AccelSaturated(axis), now);
+        }
+
+        let max_gyro = settings.gyro_range.full_scale().0 * SATURATION;
+        if let Some(axis) = gyro.into_iter().find(|it| it.0.abs() >= max_gyro) {
+            self.inertial.flag(SensorFault::GyroSaturated(axis), now);
+        }
+
+        // Without rotation the only acceleration the robot sees for long is gravity
+        let norm = GForce(accel.iter().map(|it| it.0.powi(2)).sum::<f64>().sqrt());
+        let still = gyro.iter().all(|it| it.0.abs() < STILL_GYRO.0);
+        let (min_accel, max_accel) = STILL_ACCEL;
+        let inconsistent = still && (norm < min_accel || norm > max_accel);
+
+        if let Some(true) = self.accel_inconsistent.update(inconsistent, now) {
+            self.inconsistent_accel = norm;
+        }
+    }
+
+    pub fn update_depth(&mut self, frame: DepthFrame, now: Instant) {
+        self.depth.update((frame.pressure, frame.temperature), now);
+
+        // Rate comes from pressure so a new tare does not look like a jump
+        let previous = self.last_pressure.replace((frame.pressure, frame.sampled));
+        if let Some((previous, sampled)) = previous {
+            let elapsed = frame.sampled.duration_since(sampled).unwrap_or_default();
+
+            if !elapsed.is_zero() {
+                let change =
+                    ms5937::pressure_to_depth(frame.pressure, previous, frame.fluid_density);
+                let rate = change.0 / elapsed.as_secs_f64();
+
+                if rate.abs() > MAX_DEPTH_RATE {
+                    self.depth.flag(SensorFault::DepthRate(rate), now);
+                }
+            }
+        }
+
+        let (min_depth, max_depth) = DEPTH_RANGE;
+        if frame.depth < min_depth || frame.depth > max_depth {
+            self.depth
+                .flag(SensorFault::DepthOutOfRange(frame.depth), now);
+        }
+    }
+
+    pub fn update_mag(&mut self, frame: MagFrame, now: Instant) {
+        self.mag.update(frame, now);
+
+        let field = (frame.mag_x.0.powi(2) + frame.mag_y.0.powi(2) + frame.mag_z.0.powi(2)).sqrt();
+        let (min_field, max_field) = mag_vote::FIELD_RANGE;
+        if field < min_field.0 || field > max_field.0 {
+            self.mag.flag(SensorFault::MagOutOfRange(Gauss(field)), now);
+        }
+    }
+
+    /// Faults of every sensor, an empty list is a healthy sensor
+    pub fn health(&self, now: Instant) -> HashMap<SensorId, Vec<SensorFault>> {
+        let mut inertial = self.inertial.faults(now);
+        if self.accel_inconsistent.active() {
+            inertial.push(SensorFault::AccelInconsistent(self.inconsistent_accel));
+        }
+
+        let mut health = HashMap::default();
+        health.insert(SensorId::Inertial, inertial);
+        health.insert(SensorId::Depth, self.depth.faults(now));
+        health.insert(SensorId::Magnetometer, self.mag.faults(now));
+
+        health
+    }
+}
+
+/// Staleness and stuck values of one sensor, plus the faults its recent readings raised
+struct SensorTrack<F> {
+    last: Option<(F, Instant)>,
+    /// When the readings last changed
+    changed: Option<Instant>,
+    /// Faults with when they were last seen
+    recent: Vec<(SensorFault, Instant)>,
+}
+
+impl<F> Default for SensorTrack<F> {
+    fn default() -> Self {
+        Self {
+            last: None,
+            changed: None,
+            recent: Vec::new(),
+        }
+    }
+}
+
+impl<F: PartialEq + Copy> SensorTrack<F> {
+    fn update(&mut self, frame: F, now: Instant) {
+        let previous = self.last.replace((frame, now));
+
+        if previous.map(|(it, _)| it != frame).unwrap_or(true) {
+            self.changed = Some(now);
+        }
+    }
+
+    /// Keeps the value a fault was first seen with until it clears
+    fn flag(&mut self, fault: SensorFault, now: Instant) {
+        let existing = self.recent.iter_mut().find(|(it, seen)| {
+            mem::discriminant(it) == mem::discriminant(&fault)
+                && now.saturating_duration_since(*seen) < FAULT_HOLD
+        });
+
+        match existing {
+            Some((_, seen)) => *seen = now,
+            None => {
+                self.recent
+                    .retain(|(it, _)| mem::discriminant(it) != mem::discriminant(&fault));
+                self.recent.push((fault, now));
+            }
+        }
+    }
+
+    fn faults(&self, now: Instant) -> Vec<SensorFault> {
+        let Some((_, received)) = self.last else {
+            return vec![SensorFault::Stale];
+        };
+
+        let mut faults = Vec::new();
+
+        if now.saturating_duration_since(received) > MAX_AGE {
+            faults.push(SensorFault::Stale);
+        } else if let Some(changed) = self.changed {
+            if now.saturating_duration_since(changed) >= STUCK_TIME {
+                faults.push(SensorFault::Stuck);
+            }
+        }
+
+        faults.extend(
+            self.recent
+                .iter()
+                .filter(|(_, seen)| now.saturating_duration_since(*seen) < FAULT_HOLD)
+                .map(|(fault, _)| *fault),
+        );
+
+        faults
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::WaterType;
+
+    use super::*;
+
+    fn inertial(accel_z: f64, gyro_x: f64, noise: f64) -> InertialFrame {
+        InertialFrame {
+            gyro_x: Dps(gyro_x),
+            gyro_y: Dps(noise),
+            gyro_z: Dps(0.0),
+            accel_x: GForce(noise),
+            accel_y: GForce(0.0),
+            accel_z: GForce(accel_z),
+            tempature: Celsius(25.0),
+        }
+    }
+
+    /// A reading sampled `after` the first one
+    fn depth(pressure: f64, after: Duration) -> DepthFrame {
+        DepthFrame {
+            sampled: SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000) + after,
+            depth: ms5937::pressure_to_depth(Mbar(pressure), Mbar(1013.25), 997.0),
+            altitude: Meters(0.0),
+            pressure: Mbar(pressure),
+            temperature: Celsius(15.0),
+            surface_pressure: Mbar(1013.25),
+            fluid_density: 997.0,
+            water_type: WaterType::Fresh,
+        }
+    }
+
+    fn noise(step: u32) -> f64 {
+        (step % 7) as f64 * 0.001
+    }
+
+    #[test]
+    fn missing_and_stale_sensors_are_faulted() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+
+        let health = monitor.health(start);
+        assert_eq!(health[&SensorId::Depth], vec![SensorFault::Stale]);
+
+        monitor.update_depth(depth(1100.0, Duration::ZERO), start);
+        assert!(monitor.health(start)[&SensorId::Depth].is_empty());
+
+        let later = start + MAX_AGE * 2;
+        assert_eq!(
+            monitor.health(later)[&SensorId::Depth],
+            vec![SensorFault::Stale]
+        );
+    }
+
+    #[test]
+    fn frozen_readings_are_stuck() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+
+        for step in 0..100 {
+            let now = start + PERIOD * step;
+            monitor.update_depth(depth(1100.0, PERIOD * step), now);
+            monitor.update_inertial(
+                inertial(1.0, 0.0, noise(step)),
+                &ImuSettings::default(),
+                now,
+            );
+        }
+
+        let now = start + PERIOD * 100;
+        assert_eq!(
+            monitor.health(now)[&SensorId::Depth],
+            vec![SensorFault::Stuck]
+        );
+        assert!(monitor.health(now)[&SensorId::Inertial].is_empty());
+    }
+
+    #[test]
+    fn depth_jumps_are_held() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+
+        monitor.update_depth(depth(1100.0, Duration::ZERO), start);
+        // A meter in 50ms
+        monitor.update_depth(depth(1197.8, PERIOD), start + PERIOD);
+
+        let faults = &monitor.health(start + PERIOD)[&SensorId::Depth];
+        assert!(matches!(faults[..], [SensorFault::DepthRate(rate)] if rate > 19.0));
+
+        // Still reported for a while with normal readings
+        monitor.update_depth(depth(1197.9, PERIOD * 2), start + PERIOD * 2);
+        assert_eq!(
+            monitor.health(start + PERIOD * 2)[&SensorId::Depth].len(),
+            1
+        );
+
+        let later = start + PERIOD * 2 + FAULT_HOLD;
+        monitor.update_depth(depth(1197.8, PERIOD * 2 + FAULT_HOLD), later);
+        assert!(monitor.health(later)[&SensorId::Depth].is_empty());
+    }
+
+    #[test]
+    fn depth_rate_uses_sample_times() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+        let sample_period = Duration::from_millis(10);
+
+        // Several noisy frames are handled together each tick, microseconds apart
+        for step in 0..100 {
+            let now = start + PERIOD * (step / 5) + Duration::from_micros(step as u64 % 5);
+            let pressure = 1100.0 + noise(step) * 100.0;
+            monitor.update_depth(depth(pressure, sample_period * step), now);
+        }
+
+        assert!(monitor.health(start + PERIOD * 20)[&SensorId::Depth].is_empty());
+    }
+
+    #[test]
+    fn saturated_gyro_is_faulted() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+
+        monitor.update_inertial(inertial(1.0, 499.9, 0.0), &ImuSettings::default(), start);
+
+        assert_eq!(
+            monitor.health(start)[&SensorId::Inertial],
+            vec![SensorFault::GyroSaturated(Dps(499.9))]
+        );
+    }
+
+    #[test]
+    fn accel_must_read_gravity_while_still() {
+        let mut monitor = PlausibilityMonitor::default();
+        let start = Instant::now();
+        let settings = ImuSettings::default();
+
+        // Short bumps are fine
+        monitor.update_inertial(inertial(1.6, 0.0, 0.0), &settings, start);
+        monitor.update_inertial(inertial(1.0, 0.0, noise(1)), &settings, start + PERIOD);
+        assert!(monitor.health(start + PERIOD)[&SensorId::Inertial].is_empty());
+
+        // Turning while accelerating is fine
+        for step in 2..40 {
+            let now = start + PERIOD * step;
+            monitor.update_inertial(inertial(1.6, 30.0, noise(step)), &settings, now);
+        }
+        assert!(monitor.health(start + PERIOD * 40)[&SensorId::Inertial].is_empty());
+
+        for step in 40..80 {
+            let now = start + PERIOD * step;
+            monitor.update_inertial(inertial(1.6, 0.0, noise(step)), &settings, now);
+        }
+        assert!(matches!(
+            monitor.health(start + PERIOD * 80)[&SensorId::Inertial][..],
+            [SensorFault::AccelInconsistent(_)]
+        ));
+    }
+}
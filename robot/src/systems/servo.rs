@@ -0,0 +1,403 @@
+use std::{sync::Arc, thread::Scope, time::Duration};
+
+use common::{
+    store::{tokens, Store},
+    types::{
+        Degrees, GimbalAxis, GimbalMode, GimbalState, MotorId, Orientation, Percent, ServoCommand,
+        ServoConfig,
+    },
+};
+use crossbeam::channel::select;
+use fxhash::FxHashMap as HashMap;
+use glam::{EulerRot, Quat};
+use tracing::{span, Level};
+
+use crate::{
+    event::Event,
+    events::EventHandle,
+    systems::{
+        clock,
+        scheduler::{self, Tick},
+    },
+    SystemId,
+};
+
+use super::{motor, System};
+
+const PERIOD: Duration = Duration::from_millis(20);
+pub const SERVOS: [MotorId; 8] = [
+    MotorId::Camera1,
+    MotorId::Camera2,
+    MotorId::Camera3,
+    MotorId::Camera4,
+    MotorId::Aux1,
+    MotorId::Aux2,
+    MotorId::Aux3,
+    MotorId::Aux4,
+];
+
+/// Moves the servos towards their commanded positions within their configured limits
+/// One servo can be stabilized against the vehicle's rotation to act as a gimbal
+pub struct ServoSystem;
+
+impl System for ServoSystem {
+    const ID: SystemId = SystemId::Servo;
+
+    fn start<'scope>(
+        mut events: EventHandle,
+        spawner: &'scope Scope<'scope, '_>,
+    ) -> anyhow::Result<()> {
+        let listner = events.take_listner().unwrap();
+
+        let ticks = scheduler::schedule("Servo", PERIOD);
+        let clock = clock::clock();
+
+        spawner.spawn(move || {
+            span!(Level::INFO, "Servo thread");
+
+            let mut store = {
+                let mut events = events.clone();
+                Store::with_clock(
+                    move |update| {
+                        events.send(Event::Store(update));
+                    },
+                    clock.clone(),
+                )
+            };
+
+            let mut positions: HashMap<MotorId, Percent> =
+                SERVOS.into_iter().map(|it| (it, Percent::ZERO)).collect();
+            let mut last_tick = clock.now();
+            // World frame position of the stabilized servo
+            let mut tilt: Option<(MotorId, Percent)> = None;
+
+            loop {
+                let event = select! {
+                    recv(listner) -> event => event.map(ServoEvent::Event),
+                    recv(ticks) -> tick => tick.map(ServoEvent::Tick),
+                };
+                let Ok(event) = event else {
+                    return;
+                };
+
+                match event {
+                    ServoEvent::Event(event) => match &*event {
+                        Event::SyncStore => {
+                            store.refresh();
+                        }
+                        Event::ResetForignStore => {
+                            store.reset_shared();
+                        }
+                        Event::Store(update) => {
+                            store.handle_update_shared(update);
+                        }
+                        Event::Exit => {
+                            return;
+                        }
+                        _ => {}
+                    },
+                    ServoEvent::Tick(tick) => {
+                        let now = tick.scheduled();
+                        let elapsed = now - last_tick;
+                        last_tick = now;
+
+                        let configs = store.get(&tokens::SERVO_CONFIG_OVERRIDE);
+                        let commands = store.get(&tokens::SERVO_COMMANDS);
+                        let commands_alive = store
+                            .get_alive(&tokens::SERVO_COMMANDS, motor::MAX_UPDATE_AGE)
+                            .is_some();
+                        let gimbal = store.get(&tokens::GIMBAL_MODE).and_then(|it| match *it {
+                            GimbalMode::Stabilized { servo, axis } => Some((servo, axis)),
+                            GimbalMode::Disabled => None,
+                        });
+                        let orientation =
+                            store.get_alive(&tokens::ORIENTATION, motor::MAX_UPDATE_AGE);
+
+                        let mut pulses = HashMap::default();
+
+                        for servo in SERVOS {
+                            let config = configs
+                                .as_ref()
+                                .and_then(|it| it.get(&servo).cloned())
+                                .unwrap_or_default();
+                            let command = commands.as_ref().and_then(|it| it.get(&servo));
+
+                            let position = positions.entry(servo).or_insert(Percent::ZERO);
+
+                            match gimbal {
+                                Some((gimbal_servo, axis)) if gimbal_servo == servo => {
+                                    // No orientation data holds relative to the vehicle
+                                    let vehicle = orientation
+                                        .as_deref()
+                                        .map(|it| vehicle_angle(it, axis))
+                                        .unwrap_or_default();
+
+                                    // Start from wherever the servo is pointing to avoid a jump
+                                    let world = match tilt {
+                                        Some((tilt_servo, world)) if tilt_servo == servo => world,
+                                        _ => to_world(*position, vehicle, &config),
+                                    };
+                                    let world = step_servo(
+                                        world,
+                                        command,
+                                        commands_alive,
+                                        &config,
+                                        elapsed,
+                                    );
+                                    tilt = Some((servo, world));
+
+                                    let state = stabilize(servo, world, vehicle, &config);
+                                    *position = config.calibration.position(state.servo_angle);
+
+                                    store.insert(&tokens::GIMBAL_STATE, state);
+                                }
+                                _ => {
+                                    *position = step_servo(
+                                        *position,
+                                        command,
+                                        commands_alive,
+                                        &config,
+                                        elapsed,
+                                    );
+                                }
+                            }
+
+                            pulses.insert(servo, config.calibration.pulse(*position));
+                        }
+
+                        if gimbal.is_none() && tilt.take().is_some() {
+                            store.remove(&tokens::GIMBAL_STATE);
+                        }
+
+                        store.insert(&tokens::SERVO_POSITIONS, positions.clone());
+                        store.insert(&tokens::SERVO_PULSES, pulses);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+enum ServoEvent {
+    Event(Arc<Event>),
+    Tick(Tick),
+}
+
+/// Moves a servo one tick towards its command, respecting travel limits and the max rate
+pub fn step_servo(
+    position: Percent,
+    command: Option<&ServoCommand>,
+    commands_alive: bool,
+    config: &ServoConfig,
+    elapsed: Duration,
+) -> Percent {
+    let max_step = config.max_rate * elapsed.as_secs_f64();
+    let position = position.get();
+
+    let target = match command {
+        Some(ServoCommand::Position(target)) => Some(target.get()),
+        Some(ServoCommand::Preset(name)) => config.presets.get(name).map(|it| it.get()),
+        Some(ServoCommand::Angle(angle)) => Some(config.calibration.position(*angle).get()),
+        Some(ServoCommand::Velocity(_)) | None => None,
+    };
+
+    let next = if let Some(target) = target {
+        let delta = (target - position).clamp(-max_step, max_step);
+        position + delta
+    } else if let (Some(ServoCommand::Velocity(velocity)), true) = (command, commands_alive) {
+        // A stale velocity command holds the current position
+        position + velocity.get() * max_step
+    } else {
+        position
+    };
+
+    Percent::new(next).clamp(config.min, config.max)
+}
+
+/// Vehicle rotation about the gimbal axis
+fn vehicle_angle(orientation: &Orientation, axis: GimbalAxis) -> Degrees {
+    let (_yaw, pitch, roll) = Quat::from(orientation.0).to_euler(EulerRot::ZXY);
+
+    let angle = match axis {
+        GimbalAxis::Pitch => pitch,
+        GimbalAxis::Roll => roll,
+    };
+
+    Degrees(angle.to_degrees() as f64)
+}
+
+/// World frame position of a servo at a vehicle relative position
+fn to_world(position: Percent, vehicle: Degrees, config: &ServoConfig) -> Percent {
+    let angle = config.calibration.angle(position);
+    config.calibration.position(Degrees(angle.0 + vehicle.0))
+}
+
+/// Counter-rotates a servo against the vehicle so it holds a world frame position
+pub fn stabilize(
+    servo: MotorId,
+    world: Percent,
+    vehicle: Degrees,
+    config: &ServoConfig,
+) -> GimbalState {
+    let tilt = config.calibration.angle(world);
+    let wanted = Degrees(tilt.0 - vehicle.0);
+
+    let min = config.calibration.angle(config.min);
+    let max = config.calibration.angle(config.max);
+    let (low, high) = if min.0 <= max.0 {
+        (min.0, max.0)
+    } else {
+        (max.0, min.0)
+    };
+    let servo_angle = Degrees(wanted.0.clamp(low, high));
+
+    GimbalState {
+        servo,
+        tilt,
+        vehicle,
+        servo_angle,
+        saturated: servo_angle != wanted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(20);
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    fn limited() -> ServoConfig {
+        ServoConfig {
+            min: Percent::new(-0.5),
+            max: Percent::new(0.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn positions_stay_within_travel_limits() {
+        let config = limited();
+        let step = |position: f64, command| {
+            step_servo(
+                Percent::new(position),
+                Some(&command),
+                true,
+                &config,
+                Duration::from_secs(10),
+            )
+            .get()
+        };
+
+        assert_close(step(0.0, ServoCommand::Position(Percent::new(1.0))), 0.5);
+        assert_close(step(0.0, ServoCommand::Position(Percent::new(-1.0))), -0.5);
+        assert_close(step(0.4, ServoCommand::Velocity(Percent::new(1.0))), 0.5);
+        assert_close(step(-0.4, ServoCommand::Angle(Degrees(-45.0))), -0.5);
+    }
+
+    #[test]
+    fn positions_move_at_the_max_rate() {
+        let config = ServoConfig {
+            max_rate: 0.5,
+            ..Default::default()
+        };
+        let command = ServoCommand::Position(Percent::new(0.1));
+
+        let mut position = Percent::ZERO;
+        let mut ticks = 0;
+        while (position.get() - 0.1).abs() > 1e-9 {
+            let next = step_servo(position, Some(&command), true, &config, TICK);
+            assert!(next.get() - position.get() <= 0.5 * TICK.as_secs_f64() + 1e-9);
+
+            position = next;
+            ticks += 1;
+            assert!(ticks <= 10, "Did not reach the target");
+        }
+
+        // 0.01 a tick
+        assert_eq!(ticks, 10);
+        let held = step_servo(position, Some(&command), true, &config, TICK);
+        assert_close(held.get(), 0.1);
+    }
+
+    #[test]
+    fn stale_velocity_commands_hold_position() {
+        let config = ServoConfig::default();
+        let command = ServoCommand::Velocity(Percent::new(-0.5));
+        let position = Percent::new(0.2);
+
+        let moved = step_servo(position, Some(&command), true, &config, TICK);
+        assert_close(moved.get(), 0.2 - 0.5 * TICK.as_secs_f64());
+
+        assert_eq!(
+            step_servo(position, Some(&command), false, &config, TICK),
+            position
+        );
+        assert_eq!(step_servo(position, None, true, &config, TICK), position);
+    }
+
+    #[test]
+    fn presets_and_angles_map_to_positions() {
+        let mut config = ServoConfig::default();
+        config.presets.insert("down".to_owned(), Percent::new(-0.8));
+        let long = Duration::from_secs(10);
+        let step = |command| step_servo(Percent::ZERO, Some(&command), true, &config, long).get();
+
+        assert_close(step(ServoCommand::Preset("down".to_owned())), -0.8);
+        // Unknown presets hold
+        assert_close(step(ServoCommand::Preset("up".to_owned())), 0.0);
+
+        // Default calibration spans -45 to 45 degrees
+        assert_close(step(ServoCommand::Angle(Degrees(22.5))), 0.5);
+        assert_close(step(ServoCommand::Angle(Degrees(-45.0))), -1.0);
+        assert_close(step(ServoCommand::Angle(Degrees(90.0))), 1.0);
+    }
+
+    #[test]
+    fn stabilized_servo_cancels_vehicle_pitch() {
+        let config = ServoConfig::default();
+        let level = Percent::ZERO;
+
+        for pitch in [-30.0_f64, -10.0, 0.0, 10.0, 30.0] {
+            let orientation = Orientation(Quat::from_rotation_x(pitch.to_radians() as f32).into());
+            let vehicle = vehicle_angle(&orientation, GimbalAxis::Pitch);
+            assert!((vehicle.0 - pitch).abs() < 1e-3);
+
+            let state = stabilize(MotorId::Camera1, level, vehicle, &config);
+            assert!(!state.saturated);
+            // Servo and vehicle together point the camera at the commanded tilt
+            assert_close(state.servo_angle.0 + state.vehicle.0, state.tilt.0);
+            assert_close(state.tilt.0, 0.0);
+        }
+
+        // Pitch does not leak into roll
+        let orientation = Orientation(Quat::from_rotation_x(0.5).into());
+        assert!(vehicle_angle(&orientation, GimbalAxis::Roll).0.abs() < 1e-3);
+    }
+
+    #[test]
+    fn stabilized_servo_saturates_at_travel_limits() {
+        let config = limited();
+
+        // Limits are at -22.5 and 22.5 degrees
+        let state = stabilize(MotorId::Camera1, Percent::ZERO, Degrees(30.0), &config);
+        assert!(state.saturated);
+        assert_close(state.servo_angle.0, -22.5);
+    }
+
+    #[test]
+    fn world_position_starts_where_the_servo_points() {
+        let config = ServoConfig::default();
+
+        let world = to_world(Percent::new(0.5), Degrees(-22.5), &config);
+        assert_close(world.get(), 0.0);
+
+        let state = stabilize(MotorId::Camera1, world, Degrees(-22.5), &config);
+        assert_close(config.calibration.position(state.servo_angle).get(), 0.5);
+    }
+}
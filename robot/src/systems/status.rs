@@ -2,7 +2,7 @@ use std::thread::Scope;
 
 use common::{
     store::{tokens, Store, UpdateCallback},
-    types::{Armed, Percent, RobotStatus},
+    types::{Percent, RobotStatus, SealTestState},
 };
 use tracing::{span, Level};
 
@@ -81,10 +81,39 @@ fn compute_status<C: UpdateCallback>(store: &Store<C>, peers: i32) -> RobotStatu
         return RobotStatus::NoPeer;
     }
 
-    let mut state = RobotStatus::Disarmed;
+    if store
+        .get(&tokens::EMERGENCY_STOP)
+        .map(|it| *it)
+        .unwrap_or(false)
+    {
+        return RobotStatus::EmergencyStopped;
+    }
+
+    let power_fault = store
+        .get(&tokens::POWER_FAULTS)
+        .map(|it| !it.is_empty())
+        .unwrap_or(false);
+    let sensor_fault = store
+        .get(&tokens::SENSOR_HEALTH)
+        .map(|it| it.values().any(|faults| !faults.is_empty()))
+        .unwrap_or(false);
+    if power_fault || sensor_fault {
+        return RobotStatus::Degraded;
+    }
 
-    if let Some(armed) = store.get(&tokens::ARMED) {
-        if matches!(*armed, Armed::Armed) {
+    let seal_test_failed = store
+        .get(&tokens::SEAL_TEST_REPORT)
+        .map(|it| it.state == SealTestState::Failed)
+        .unwrap_or(false);
+
+    let mut state = if seal_test_failed {
+        RobotStatus::SealTestFailed
+    } else {
+        RobotStatus::Disarmed
+    };
+
+    if let Some(arming_state) = store.get(&tokens::ARMING_STATE) {
+        if arming_state.is_armed() {
             state = RobotStatus::Ready;
 
             if let Some(speeds) = store.get_alive(&tokens::MOTOR_SPEED, motor::MAX_UPDATE_AGE) {
@@ -101,3 +130,157 @@ fn compute_status<C: UpdateCallback>(store: &Store<C>, peers: i32) -> RobotStatu
 
     state
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use common::{
+        clock::ManualClock,
+        store::create_update,
+        types::{
+            ArmingState, Mbar, MotorFrame, MotorId, PowerFault, SealTestReport, SensorFault,
+            SensorId,
+        },
+    };
+    use fxhash::FxHashMap as HashMap;
+
+    use super::*;
+
+    fn armed_store(clock: &Arc<ManualClock>) -> Store<()> {
+        let mut store = Store::with_clock((), clock.clone());
+        store.handle_update_shared(&create_update(&tokens::ARMING_STATE, ArmingState::Armed));
+
+        store
+    }
+
+    fn speeds(speeds: &[(MotorId, f64)]) -> HashMap<MotorId, MotorFrame> {
+        speeds
+            .iter()
+            .map(|(id, speed)| (*id, MotorFrame::Percent(Percent::new(*speed))))
+            .collect()
+    }
+
+    #[test]
+    fn no_peer_takes_priority() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+        store.handle_update_shared(&create_update(&tokens::EMERGENCY_STOP, true));
+
+        assert_eq!(compute_status(&store, 0), RobotStatus::NoPeer);
+    }
+
+    #[test]
+    fn emergency_stop_overrides_arming() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+        store.handle_update_shared(&create_update(&tokens::EMERGENCY_STOP, true));
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::EmergencyStopped);
+    }
+
+    #[test]
+    fn disarmed_without_arming_state() {
+        let clock = Arc::new(ManualClock::new());
+        let store = Store::with_clock((), clock.clone());
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::Disarmed);
+    }
+
+    #[test]
+    fn refused_arming_is_disarmed() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+        store.handle_update_shared(&create_update(
+            &tokens::ARMING_STATE,
+            ArmingState::Refused(Vec::new()),
+        ));
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::Disarmed);
+    }
+
+    #[test]
+    fn failed_seal_test_shows_while_disarmed() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = Store::with_clock((), clock.clone());
+        let report = SealTestReport {
+            state: SealTestState::Failed,
+            start_pressure: Mbar(600.0),
+            pressure: Mbar(620.0),
+            rise_rate: Mbar(4.0),
+            remaining: Duration::ZERO,
+        };
+        store.handle_update_shared(&create_update(&tokens::SEAL_TEST_REPORT, report));
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::SealTestFailed);
+
+        store.handle_update_shared(&create_update(&tokens::ARMING_STATE, ArmingState::Armed));
+        assert_eq!(compute_status(&store, 1), RobotStatus::Ready);
+    }
+
+    #[test]
+    fn power_fault_degrades_armed_robot() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+        store.handle_update_shared(&create_update(
+            &tokens::POWER_FAULTS,
+            vec![PowerFault::LowVoltage],
+        ));
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::Degraded);
+
+        store.handle_update_shared(&create_update(&tokens::POWER_FAULTS, vec![]));
+        assert_eq!(compute_status(&store, 1), RobotStatus::Ready);
+    }
+
+    #[test]
+    fn sensor_fault_degrades_armed_robot() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+
+        let mut health = HashMap::default();
+        health.insert(SensorId::Depth, vec![SensorFault::Stuck]);
+        health.insert(SensorId::Inertial, vec![]);
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health.clone()));
+
+        assert_eq!(compute_status(&store, 1), RobotStatus::Degraded);
+
+        health.insert(SensorId::Depth, vec![]);
+        store.handle_update_shared(&create_update(&tokens::SENSOR_HEALTH, health));
+        assert_eq!(compute_status(&store, 1), RobotStatus::Ready);
+    }
+
+    #[test]
+    fn moving_reports_fastest_motor() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+        store.handle_update_shared(&create_update(
+            &tokens::MOTOR_SPEED,
+            speeds(&[(MotorId::FrontLeftTop, 0.25), (MotorId::BackRightTop, -0.5)]),
+        ));
+
+        assert_eq!(
+            compute_status(&store, 1),
+            RobotStatus::Moving(Percent::new(0.5))
+        );
+    }
+
+    #[test]
+    fn stale_motor_speeds_are_ready() {
+        let clock = Arc::new(ManualClock::new());
+        let mut store = armed_store(&clock);
+        store.handle_update_shared(&create_update(
+            &tokens::MOTOR_SPEED,
+            speeds(&[(MotorId::FrontLeftTop, 0.25)]),
+        ));
+
+        clock.advance(motor::MAX_UPDATE_AGE - Duration::from_millis(1));
+        assert_eq!(
+            compute_status(&store, 1),
+            RobotStatus::Moving(Percent::new(0.25))
+        );
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(compute_status(&store, 1), RobotStatus::Ready);
+    }
+}
@@ -23,7 +23,7 @@ impl System for StopSystem {
         let _ = events.take_listner();
 
         ctrlc::set_handler(move || {
-            STOP_THE_WORLD.store(true, Ordering::Relaxed);
+            stop_world();
             events.send(Event::Exit);
         })
         .context("Set ctrl-c")?;
@@ -32,6 +32,11 @@ impl System for StopSystem {
     }
 }
 
+/// Tells the threads polling `world_stopped` to exit, `Event::Exit` still needs to be sent
+pub fn stop_world() {
+    STOP_THE_WORLD.store(true, Ordering::Relaxed);
+}
+
 pub fn world_stopped() -> bool {
     STOP_THE_WORLD.load(Ordering::Relaxed)
 }
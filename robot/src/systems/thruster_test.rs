@@ -0,0 +1,360 @@
+use std::time::Instant;
+
+use common::types::{
+    Dps, InertialFrame, MotorFrame, MotorId, ThrusterTestConfig, ThrusterTestMeasurement,
+    ThrusterTestReport, ThrusterTestResult, ThrusterTestState,
+};
+use fxhash::FxHashMap as HashMap;
+
+use super::motor;
+
+const THRUSTERS: [MotorId; 8] = [
+    MotorId::FrontLeftBottom,
+    MotorId::FrontLeftTop,
+    MotorId::FrontRightBottom,
+    MotorId::FrontRightTop,
+    MotorId::BackLeftBottom,
+    MotorId::BackLeftTop,
+    MotorId::BackRightBottom,
+    MotorId::BackRightTop,
+];
+
+/// Sign of each gyro axis relative to the rotation axes of `Movement`
+/// Yaw is flipped as `Movement` uses clockwise from the top
+const GYRO_TO_MOVEMENT: [f64; 3] = [1.0, 1.0, -1.0];
+/// Minimum cosine similarity for a response to count as lined up with the model
+const MIN_AGREEMENT: f64 = 0.5;
+
+/// Spins one thruster at a time and compares the rotation it causes to the mixing model
+pub struct ThrusterTest {
+    config: ThrusterTestConfig,
+    current: usize,
+    phase: Phase,
+    phase_start: Instant,
+
+    baseline: RateAverage,
+    response: RateAverage,
+
+    results: Vec<(MotorId, ThrusterTestMeasurement)>,
+}
+
+pub enum TestTick {
+    /// Speeds to command this tick, includes a report when the test progressed
+    Running(HashMap<MotorId, MotorFrame>, Option<ThrusterTestReport>),
+    Finished(ThrusterTestReport),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Settle,
+    Spin,
+}
+
+impl ThrusterTest {
+    pub fn new(config: ThrusterTestConfig, now: Instant) -> Self {
+        Self {
+            config,
+            current: 0,
+            phase: Phase::Settle,
+            phase_start: now,
+            baseline: Default::default(),
+            response: Default::default(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn report(&self, state: ThrusterTestState) -> ThrusterTestReport {
+        ThrusterTestReport {
+            state,
+            results: self.results.clone(),
+        }
+    }
+
+    /// Report for the test in its current state
+    pub fn progress(&self) -> ThrusterTestReport {
+        match THRUSTERS.get(self.current) {
+            Some(motor_id) => self.report(ThrusterTestState::Running(*motor_id)),
+            None => self.report(ThrusterTestState::Complete),
+        }
+    }
+
+    pub fn abort(&self, reason: impl Into<String>) -> ThrusterTestReport {
+        self.report(ThrusterTestState::Aborted(reason.into()))
+    }
+
+    pub fn tick(&mut self, now: Instant, inertial: Option<&InertialFrame>) -> TestTick {
+        let Some(&motor_id) = THRUSTERS.get(self.current) else {
+            return TestTick::Finished(self.progress());
+        };
+
+        let Some(inertial) = inertial else {
+            return TestTick::Finished(self.abort("No IMU data"));
+        };
+
+        let elapsed = now.saturating_duration_since(self.phase_start);
+        let mut progressed = false;
+
+        match self.phase {
+            Phase::Settle => {
+                self.baseline.add(inertial);
+
+                if elapsed >= self.config.settle_time {
+                    self.phase = Phase::Spin;
+                    self.phase_start = now;
+                }
+            }
+            Phase::Spin => {
+                // Give the thruster half the window to spin up before sampling
+                if elapsed >= self.config.spin_time / 2 {
+                    self.response.add(inertial);
+                }
+
+                if elapsed >= self.config.spin_time {
+                    let measurement = evaluate(
+                        motor_id,
+                        &self.baseline,
+                        &self.response,
+                        self.config.min_response,
+                    );
+                    self.results.push((motor_id, measurement));
+
+                    self.current += 1;
+                    self.phase = Phase::Settle;
+                    self.phase_start = now;
+                    self.baseline = Default::default();
+                    self.response = Default::default();
+
+                    progressed = true;
+                }
+            }
+        }
+
+        if self.current >= THRUSTERS.len() {
+            return TestTick::Finished(self.progress());
+        }
+
+        let mut speeds = HashMap::default();
+        if self.phase == Phase::Spin {
+            speeds.insert(motor_id, MotorFrame::Percent(self.config.speed));
+        }
+
+        TestTick::Running(speeds, progressed.then(|| self.progress()))
+    }
+}
+
+/// Judges a thruster from the change in rotation rate while it was spinning
+fn evaluate(
+    motor_id: MotorId,
+    baseline: &RateAverage,
+    response: &RateAverage,
+    min_response: Dps,
+) -> ThrusterTestMeasurement {
+    let baseline = baseline.mean();
+    let response = response.mean();
+    let measured: [f64; 3] =
+        std::array::from_fn(|axis| (response[axis] - baseline[axis]) * GYRO_TO_MOVEMENT[axis]);
+
+    let [_, _, _, pitch, roll, yaw] =
+        motor::mix_coefficients(motor_id).expect("Thruster has mix coefficients");
+    let expected = [pitch, roll, yaw];
+
+    let magnitude = norm(measured);
+    let agreement = if magnitude > 0.0 {
+        dot(expected, measured) / (norm(expected) * magnitude)
+    } else {
+        0.0
+    };
+
+    let result = if magnitude < min_response.0 {
+        ThrusterTestResult::NoResponse
+    } else if agreement >= MIN_AGREEMENT {
+        ThrusterTestResult::Pass
+    } else if agreement <= -MIN_AGREEMENT {
+        ThrusterTestResult::Reversed
+    } else {
+        ThrusterTestResult::WrongAxis
+    };
+
+    ThrusterTestMeasurement {
+        result,
+        pitch: Dps(measured[0]),
+        roll: Dps(measured[1]),
+        yaw: Dps(measured[2]),
+        agreement,
+    }
+}
+
+#[derive(Default)]
+struct RateAverage {
+    sum: [f64; 3],
+    samples: u32,
+}
+
+impl RateAverage {
+    fn add(&mut self, inertial: &InertialFrame) {
+        self.sum[0] += inertial.gyro_x.0;
+        self.sum[1] += inertial.gyro_y.0;
+        self.sum[2] += inertial.gyro_z.0;
+        self.samples += 1;
+    }
+
+    fn mean(&self) -> [f64; 3] {
+        if self.samples == 0 {
+            return [0.0; 3];
+        }
+
+        self.sum.map(|it| it / self.samples as f64)
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn norm(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use common::types::{Celsius, GForce, Percent};
+
+    use super::*;
+
+    const CONFIG: ThrusterTestConfig = ThrusterTestConfig {
+        speed: Percent::new(0.15),
+        spin_time: Duration::from_millis(200),
+        settle_time: Duration::from_millis(100),
+        min_response: Dps(3.0),
+    };
+    const PERIOD: Duration = Duration::from_millis(10);
+    /// Gyro offset the baseline has to remove
+    const BIAS: [f64; 3] = [1.5, -0.5, 2.0];
+
+    fn gyro(rates: [f64; 3]) -> InertialFrame {
+        InertialFrame {
+            gyro_x: Dps(rates[0]),
+            gyro_y: Dps(rates[1]),
+            gyro_z: Dps(rates[2]),
+            accel_x: GForce(0.0),
+            accel_y: GForce(0.0),
+            accel_z: GForce(1.0),
+            tempature: Celsius(25.0),
+        }
+    }
+
+    /// Gyro rates for the rotation the mixing model expects from `motor_id`, times `scale`
+    fn modeled(motor_id: MotorId, scale: f64) -> [f64; 3] {
+        let [_, _, _, pitch, roll, yaw] = motor::mix_coefficients(motor_id).unwrap();
+
+        std::array::from_fn(|axis| [pitch, roll, yaw][axis] * GYRO_TO_MOVEMENT[axis] * scale)
+    }
+
+    /// Runs the test on a robot that turns at `response` while a thruster is commanded
+    fn run(response: impl Fn(MotorId) -> [f64; 3]) -> ThrusterTestReport {
+        let start = Instant::now();
+        let mut test = ThrusterTest::new(CONFIG, start);
+        let mut spinning = None;
+
+        for tick in 0..1000 {
+            let rates = spinning.map(&response).unwrap_or_default();
+            let frame = gyro(std::array::from_fn(|axis| BIAS[axis] + rates[axis]));
+
+            match test.tick(start + PERIOD * tick, Some(&frame)) {
+                TestTick::Running(speeds, _) => {
+                    assert!(speeds.len() <= 1, "One thruster at a time");
+                    spinning = speeds.keys().next().copied();
+                }
+                TestTick::Finished(report) => return report,
+            }
+        }
+
+        panic!("Thruster test did not finish");
+    }
+
+    fn results(report: &ThrusterTestReport) -> Vec<(MotorId, ThrusterTestResult)> {
+        report
+            .results
+            .iter()
+            .map(|(motor_id, measurement)| (*motor_id, measurement.result))
+            .collect()
+    }
+
+    #[test]
+    fn thrusters_matching_the_model_pass() {
+        let report = run(|motor_id| modeled(motor_id, 10.0));
+
+        assert_eq!(report.state, ThrusterTestState::Complete);
+        assert_eq!(
+            results(&report),
+            THRUSTERS.map(|motor_id| (motor_id, ThrusterTestResult::Pass))
+        );
+
+        let (motor_id, measurement) = report.results[0];
+        let [_, _, _, pitch, roll, yaw] = motor::mix_coefficients(motor_id).unwrap();
+        assert!((measurement.pitch.0 - pitch * 10.0).abs() < 1e-9);
+        assert!((measurement.roll.0 - roll * 10.0).abs() < 1e-9);
+        assert!((measurement.yaw.0 - yaw * 10.0).abs() < 1e-9);
+        assert!((measurement.agreement - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn faulty_thrusters_are_classified() {
+        let report = run(|motor_id| match motor_id {
+            MotorId::FrontLeftTop => modeled(motor_id, -10.0),
+            MotorId::BackRightBottom => {
+                // At right angles to the expected rotation
+                let [pitch, roll, _] = modeled(motor_id, 10.0);
+                [roll, -pitch, 0.0]
+            }
+            MotorId::BackLeftTop => modeled(motor_id, 1.0),
+            _ => modeled(motor_id, 10.0),
+        });
+
+        let results = results(&report);
+        let result = |motor_id| {
+            results
+                .iter()
+                .find(|(it, _)| *it == motor_id)
+                .map(|(_, result)| *result)
+        };
+
+        assert_eq!(report.state, ThrusterTestState::Complete);
+        assert_eq!(
+            result(MotorId::FrontLeftTop),
+            Some(ThrusterTestResult::Reversed)
+        );
+        assert_eq!(
+            result(MotorId::BackRightBottom),
+            Some(ThrusterTestResult::WrongAxis)
+        );
+        assert_eq!(
+            result(MotorId::BackLeftTop),
+            Some(ThrusterTestResult::NoResponse)
+        );
+        assert_eq!(
+            result(MotorId::FrontLeftBottom),
+            Some(ThrusterTestResult::Pass)
+        );
+    }
+
+    #[test]
+    fn missing_imu_aborts() {
+        let start = Instant::now();
+        let mut test = ThrusterTest::new(CONFIG, start);
+
+        assert!(matches!(
+            test.tick(start, Some(&gyro(BIAS))),
+            TestTick::Running(..)
+        ));
+        let TestTick::Finished(report) = test.tick(start + PERIOD, None) else {
+            panic!("Test kept running without IMU data");
+        };
+        assert_eq!(
+            report.state,
+            ThrusterTestState::Aborted("No IMU data".to_owned())
+        );
+    }
+}
@@ -21,20 +21,12 @@ pub fn main() -> anyhow::Result<()> {
 
     eprintln!("Uploading");
 
-    let rst = Command::new("scp")
-        .arg("./detect_cameras.sh")
-        .arg("pi@mate.local:~/mate/detect_cameras.sh")
-        .spawn()
-        .context("Spawn scp")?
-        .wait();
-
     let rst = Command::new("scp")
         .arg("./setup_camera.sh")
         .arg("pi@mate.local:~/mate/setup_camera.sh")
         .spawn()
         .context("Spawn scp")?
-        .wait()
-        .and(rst);
+        .wait();
 
     let rst = Command::new("scp")
         .arg("./robot/forward_motor_data.csv")
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bevy::{
     input::gamepad::{
@@ -9,10 +9,13 @@ use bevy::{
 };
 use common::{
     store::tokens,
-    types::{DepthControlMode, LevelingMode, Meters, MotorId, Movement, Percent},
+    types::{
+        Degrees, DepthControlMode, GimbalAxis, GimbalMode, LevelingMode, Meters, MotorId, Movement,
+        Percent, ServoCommand,
+    },
 };
 
-use super::robot::{Robot, Updater};
+use super::robot::{self, Robot, ServoCommands, Updater};
 
 pub struct GamepadPlugin;
 
@@ -25,6 +28,9 @@ impl Plugin for GamepadPlugin {
     }
 }
 
+/// Number of inputs bound to `Action::EmergencyStop` that must be held at once
+const EMERGENCY_STOP_CHORD: usize = 2;
+
 #[derive(Resource, Clone, Debug)]
 pub struct CurrentGamepad(pub Gamepad, pub InputState);
 
@@ -41,6 +47,8 @@ pub struct InputState {
 
     pub servo_position_normal: f32,
     pub servo_position_inverted: f32,
+
+    pub emergency_stop_held: HashSet<Input>,
 }
 
 impl InputState {
@@ -66,6 +74,22 @@ impl InputState {
                         }
                     });
                 }
+                Action::EmergencyStop => {
+                    if value == 0.0 {
+                        self.emergency_stop_held.remove(&input);
+                        return;
+                    }
+
+                    // Only fire once, when the last input of the chord goes down
+                    if !self.emergency_stop_held.insert(input)
+                        || self.emergency_stop_held.len() != EMERGENCY_STOP_CHORD
+                    {
+                        return;
+                    }
+
+                    warn!("Emergency stop pressed");
+                    commands.add(robot::emergency_stop);
+                }
                 Action::Disarm => {
                     if value == 0.0 {
                         return;
@@ -91,13 +115,16 @@ impl InputState {
                         return;
                     }
 
-                    self.movement.set_by_id(self.servo, Percent::ZERO);
+                    // Level with the horizon when stabilized
+                    set_servo(commands, self.servo, ServoCommand::Angle(Degrees(0.0)));
                 }
                 Action::SelectServoIncrement => {
                     if value == 0.0 {
                         return;
                     }
 
+                    // Don't leave the old servo drifting if a trigger is held
+                    set_servo(commands, self.servo, ServoCommand::Velocity(Percent::ZERO));
                     self.servo = next_servo(self.servo);
                 }
                 Action::SelectServoDecrement => {
@@ -105,6 +132,8 @@ impl InputState {
                         return;
                     }
 
+                    // Don't leave the old servo drifting if a trigger is held
+                    set_servo(commands, self.servo, ServoCommand::Velocity(Percent::ZERO));
                     self.servo = last_servo(self.servo);
                 }
                 Action::IncreaseGain => {
@@ -147,25 +176,43 @@ impl InputState {
                 Action::RotateServo => {
                     self.servo_position_normal = value;
 
-                    self.movement.set_by_id(
-                        self.servo,
-                        Percent::new(
-                            (self.servo_position_normal - self.servo_position_inverted) as f64,
-                        ),
+                    let velocity = Percent::new(
+                        (self.servo_position_normal - self.servo_position_inverted) as f64,
                     );
+                    set_servo(commands, self.servo, ServoCommand::Velocity(velocity));
                 }
                 Action::RotateServoInverted => {
                     self.servo_position_inverted = value;
 
-                    self.movement.set_by_id(
-                        self.servo,
-                        Percent::new(
-                            (self.servo_position_normal - self.servo_position_inverted) as f64,
-                        ),
+                    let velocity = Percent::new(
+                        (self.servo_position_normal - self.servo_position_inverted) as f64,
                     );
+                    set_servo(commands, self.servo, ServoCommand::Velocity(velocity));
+                }
+                Action::ToggleGimbal(axis) => {
+                    if value == 0.0 {
+                        return;
+                    }
+
+                    let axis = *axis;
+                    let servo = self.servo;
+                    commands.add(move |world: &mut World| {
+                        if let Some(robot) = world.get_resource::<Robot>() {
+                            let old_mode = robot.store().get(&tokens::GIMBAL_MODE).map(|it| *it);
+                            let new_mode = match old_mode {
+                                Some(GimbalMode::Stabilized { servo: old, .. }) if old == servo => {
+                                    GimbalMode::Disabled
+                                }
+                                _ => GimbalMode::Stabilized { servo, axis },
+                            };
+                            Updater::from_world(world).emit_update(&tokens::GIMBAL_MODE, new_mode);
+                        } else {
+                            error!("No robot resource");
+                        }
+                    })
                 }
                 Action::SetServo(pct, servo) => {
-                    self.movement.set_by_id(*servo, *pct);
+                    set_servo(commands, *servo, ServoCommand::Position(*pct));
                 }
                 Action::Pitch => {
                     if self.hold_axis {
@@ -268,6 +315,16 @@ impl InputState {
     }
 }
 
+fn set_servo(commands: &mut Commands, servo: MotorId, command: ServoCommand) {
+    commands.add(move |world: &mut World| {
+        if let Some(mut servos) = world.get_resource_mut::<ServoCommands>() {
+            servos.0.insert(servo, command);
+        } else {
+            error!("No servo commands resource");
+        }
+    });
+}
+
 // fn next_servo(id: MotorId) -> MotorId {
 //     match id {
 //         MotorId::FrontLeftBottom
@@ -339,6 +396,7 @@ impl Default for InputState {
             hold_axis: false,
             servo_position_normal: 0.0,
             servo_position_inverted: 0.0,
+            emergency_stop_held: Default::default(),
         }
     }
 }
@@ -409,6 +467,9 @@ fn create_mapping() -> ControllerMappings {
     let default_mapping: ControllerMapping = [
         (Input::Button(GamepadButtonType::Select), Action::Disarm),
         (Input::Button(GamepadButtonType::Start), Action::Arm),
+        // Both stick buttons must be pressed together
+        (Input::Button(GamepadButtonType::LeftThumb), Action::EmergencyStop),
+        (Input::Button(GamepadButtonType::RightThumb), Action::EmergencyStop),
         // (Input::Button(GamepadButtonType::LeftThumb), Action::ResetGain),
         // (Input::Button(GamepadButtonType::RightThumb), Action::HoldAxis),
         (Input::Button(GamepadButtonType::DPadUp), Action::IncreaseGain),
@@ -482,6 +543,8 @@ pub enum Input {
 pub enum Action {
     Arm,
     Disarm,
+    /// Chorded, every input bound to this has to be held to trigger
+    EmergencyStop,
 
     SetControlMapping(&'static str),
 
@@ -491,6 +554,8 @@ pub enum Action {
     RotateServo,
     RotateServoInverted,
     SetServo(Percent, MotorId),
+    /// Stabilizes the selected servo
+    ToggleGimbal(GimbalAxis),
 
     IncreaseGain,
     DecreaseGain,
@@ -94,6 +94,10 @@ fn setup_network(mut commands: Commands) -> anyhow::Result<()> {
                             .send_packet(token, packet)
                             .log_error("Could not send Message");
                     }
+                    Protocol::LogRecord(record) => {
+                        tx.try_send(RobotEvent::LogRecord(record))
+                            .log_error("Could not send RobotEvent");
+                    }
                     Protocol::Log(level, msg) => match level {
                         LogLevel::Debug => debug!("Peer logged: `{msg}`"),
                         LogLevel::Info => info!("Peer logged: `{msg}`"),
@@ -110,6 +114,16 @@ fn setup_network(mut commands: Commands) -> anyhow::Result<()> {
                         tx.try_send(RobotEvent::Ping(ping, pong))
                             .log_error("Could not send RobotEvent");
                     }
+                    Protocol::EmergencyStopState(latched) => {
+                        tx.try_send(RobotEvent::EmergencyStop(latched))
+                            .log_error("Could not send RobotEvent");
+                    }
+                    Protocol::EmergencyStop | Protocol::ResetEmergencyStop => {
+                        warn!("Peer sent emergency stop command to surface");
+                    }
+                    Protocol::CameraSubscriptions(_) => {
+                        warn!("Peer sent camera subscriptions to surface");
+                    }
                 },
                 Event::Error(token, error) => {
                     let addrs = token.and_then(|token| clients.remove(&token));
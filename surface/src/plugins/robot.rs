@@ -5,11 +5,15 @@ use common::error::LogErrorExt;
 use common::protocol::Protocol;
 use common::store::adapters::{BackingType, TypeAdapter};
 use common::store::{self, tokens, Key, Store, Token, Update, UpdateCallback};
-use common::types::Armed;
+use common::types::{
+    Armed, ArmingState, CameraHealth, CameraStreamState, DepthControlMode, LevelingMode, LogRecord,
+    MotorId, PreArmFailure, SealTestState, ServoCommand, ThrusterTestState,
+};
 use crossbeam::channel::{bounded, Receiver, Sender};
 use fxhash::FxHashMap as HashMap;
 use networking::error::NetError;
 use std::any::Any;
+use std::collections::VecDeque;
 use std::net::SocketAddr;
 use std::time::SystemTime;
 
@@ -23,10 +27,14 @@ impl Plugin for RobotPlugin {
         app.add_event::<Update>();
         app.init_resource::<Robot>();
         app.init_resource::<Adapters>();
+        app.init_resource::<ServoCommands>();
+        app.init_resource::<RobotLogs>();
         app.add_system(update_robot.in_base_set(CoreSet::PreUpdate));
         app.add_system(updates_to_packets.in_base_set(CoreSet::PostUpdate));
         app.add_system(events_to_notifs.in_base_set(CoreSet::PostUpdate));
+        app.add_system(collect_logs.in_base_set(CoreSet::PreUpdate));
         app.add_system(arming_system.in_schedule(CoreSchedule::FixedUpdate));
+        app.add_system(servo_system.in_schedule(CoreSchedule::FixedUpdate));
     }
 }
 
@@ -45,6 +53,7 @@ pub struct Robot(
     Sender<Update>,
     Receiver<Update>,
     Armed,
+    bool,
 );
 impl Robot {
     pub const fn store(&self) -> &Store<NotificationHandler> {
@@ -57,10 +66,18 @@ impl Robot {
 
     pub fn arm(&mut self) {
         self.3 = Armed::Armed;
+        self.4 = false;
+    }
+
+    /// Arm request that overrides any failing pre-arm checks that allow it
+    pub fn force_arm(&mut self) {
+        self.3 = Armed::Armed;
+        self.4 = true;
     }
 
     pub fn disarm(&mut self) {
         self.3 = Armed::Disarmed;
+        self.4 = false;
     }
 }
 
@@ -73,10 +90,24 @@ impl Default for Robot {
             tx,
             rx,
             Armed::Disarmed,
+            false,
         )
     }
 }
 
+/// Latest command for each servo, sent to the robot's servo controller
+#[derive(Resource, Default, Debug, Clone)]
+pub struct ServoCommands(pub HashMap<MotorId, ServoCommand>);
+
+/// Log records the robot sent, oldest first
+#[derive(Resource, Default, Debug)]
+pub struct RobotLogs(pub VecDeque<LogRecord>);
+
+impl RobotLogs {
+    /// Older records are dropped past this
+    pub const CAPACITY: usize = 5000;
+}
+
 /// Way for systems to update store
 /// For use with bevy's `Local` system argurment
 pub struct Updater(Sender<Update>);
@@ -103,6 +134,9 @@ impl FromWorld for Updater {
 pub enum RobotEvent {
     Store(Update),
     Ping(SystemTime, SystemTime),
+    /// Robot confirmed the state of its emergency stop latch
+    EmergencyStop(bool),
+    LogRecord(LogRecord),
 
     Connected(SocketAddr),
     Disconnected(SocketAddr),
@@ -116,6 +150,28 @@ fn update_robot(mut robot: ResMut<Robot>, mut events: EventReader<RobotEvent>) {
         match event {
             RobotEvent::Store(update) => {
                 robot.0.handle_update_shared(update);
+
+                // Drop refused requests so the next arm attempt is a new request
+                if let Some(state) = store::handle_update(&tokens::ARMING_STATE, update) {
+                    if let ArmingState::Refused(_) = *state {
+                        robot.disarm();
+                    }
+                }
+
+                // The robot dropped a hold on a sensor fault, the next toggle engages it again
+                if store::handle_update(&tokens::DEPTH_CONTROL_DISENGAGED, update).is_some() {
+                    robot
+                        .0
+                        .insert(&tokens::DEPTH_CONTROL_MODE, DepthControlMode::Disabled);
+                }
+                if store::handle_update(&tokens::LEVELING_DISENGAGED, update).is_some() {
+                    robot
+                        .0
+                        .insert(&tokens::LEVELING_MODE, LevelingMode::Disabled);
+                }
+            }
+            RobotEvent::EmergencyStop(true) => {
+                robot.disarm();
             }
             RobotEvent::Connected(..) | RobotEvent::Disconnected(..) => {
                 robot.0.reset();
@@ -165,7 +221,11 @@ fn updates_to_packets(
 }
 
 /// Generate notifications for some robot events
-fn events_to_notifs(mut events: EventReader<RobotEvent>, mut notifs: EventWriter<Notification>) {
+fn events_to_notifs(
+    mut events: EventReader<RobotEvent>,
+    mut notifs: EventWriter<Notification>,
+    mut camera_health: Local<HashMap<String, CameraHealth>>,
+) {
     for event in events.iter() {
         match event {
             RobotEvent::Connected(addr) => {
@@ -186,25 +246,180 @@ fn events_to_notifs(mut events: EventReader<RobotEvent>, mut notifs: EventWriter
                     anyhow!("{error}"),
                 ));
             }
+            RobotEvent::EmergencyStop(true) => {
+                notifs.send(Notification::Error(
+                    "Emergency Stop".to_owned(),
+                    anyhow!("Motor outputs disabled, reset the emergency stop to arm again"),
+                ));
+            }
+            RobotEvent::EmergencyStop(false) => {
+                notifs.send(Notification::Info(
+                    "Emergency Stop Reset".to_owned(),
+                    "Robot can be armed again".to_owned(),
+                ));
+            }
             RobotEvent::Store(store) => {
-                if let Some(leak) = store::handle_update(&tokens::LEAK, store) {
-                    if *leak {
+                if let Some(probes) = store::handle_update(&tokens::LEAK_PROBES, store) {
+                    let mut leaking = probes
+                        .iter()
+                        .filter(|(_, it)| it.leaking)
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>();
+                    leaking.sort();
+
+                    if !leaking.is_empty() {
                         notifs.send(Notification::Info(
                             "Leak Detected!".to_owned(),
-                            "Take robot to surface!".to_owned(),
+                            format!("Take robot to surface! Wet: {}", leaking.join(", ")),
                         ));
                     }
                 }
+                if let Some(report) = store::handle_update(&tokens::THRUSTER_TEST_REPORT, store) {
+                    match &report.state {
+                        ThrusterTestState::Complete => {
+                            let failed = report
+                                .results
+                                .iter()
+                                .filter(|(_, it)| !it.result.passed())
+                                .map(|(motor, it)| format!("{motor:?}: {:?}", it.result))
+                                .collect::<Vec<_>>();
+
+                            if failed.is_empty() {
+                                notifs.send(Notification::Info(
+                                    "Thruster Test Passed".to_owned(),
+                                    "All thrusters match the mixing model".to_owned(),
+                                ));
+                            } else {
+                                notifs.send(Notification::Error(
+                                    "Thruster Test Failed".to_owned(),
+                                    anyhow!("{}", failed.join(", ")),
+                                ));
+                            }
+                        }
+                        ThrusterTestState::Aborted(reason) => {
+                            notifs.send(Notification::Error(
+                                "Thruster Test Aborted".to_owned(),
+                                anyhow!("{reason}"),
+                            ));
+                        }
+                        ThrusterTestState::Running(_) => {}
+                    }
+                }
+                if let Some(report) = store::handle_update(&tokens::SEAL_TEST_REPORT, store) {
+                    match &report.state {
+                        SealTestState::Passed => {
+                            notifs.send(Notification::Info(
+                                "Seal Test Passed".to_owned(),
+                                format!("Pressure rose {}/min", report.rise_rate),
+                            ));
+                        }
+                        SealTestState::Failed => {
+                            notifs.send(Notification::Error(
+                                "Seal Test Failed".to_owned(),
+                                anyhow!("Pressure rose {}/min", report.rise_rate),
+                            ));
+                        }
+                        SealTestState::Aborted(reason) => {
+                            notifs.send(Notification::Error(
+                                "Seal Test Aborted".to_owned(),
+                                anyhow!("{reason}"),
+                            ));
+                        }
+                        SealTestState::Settling | SealTestState::Measuring => {}
+                    }
+                }
+                if let Some(disengaged) =
+                    store::handle_update(&tokens::DEPTH_CONTROL_DISENGAGED, store)
+                {
+                    notifs.send(Notification::Error(
+                        "Depth Hold Disengaged".to_owned(),
+                        anyhow!("{:?} implausible: {}", disengaged.sensor, disengaged.fault),
+                    ));
+                }
+                if let Some(disengaged) = store::handle_update(&tokens::LEVELING_DISENGAGED, store)
+                {
+                    notifs.send(Notification::Error(
+                        "Leveling Disengaged".to_owned(),
+                        anyhow!("{:?} implausible: {}", disengaged.sensor, disengaged.fault),
+                    ));
+                }
+                if let Some(state) = store::handle_update(&tokens::ARMING_STATE, store) {
+                    match &*state {
+                        ArmingState::Refused(failures) => {
+                            notifs.send(Notification::Error(
+                                "Arming Refused".to_owned(),
+                                anyhow!("{}", PreArmFailure::describe_all(failures)),
+                            ));
+                        }
+                        ArmingState::ForceArmed(failures) => {
+                            notifs.send(Notification::Info(
+                                "Force Armed".to_owned(),
+                                format!("Overridden: {}", PreArmFailure::describe_all(failures)),
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some(health) = store::handle_update(&tokens::CAMERA_HEALTH, store) {
+                    for (camera, health) in &*health {
+                        // Every failure leaves the camera waiting to restart
+                        let was_restarting = camera_health
+                            .get(camera)
+                            .map(|it| it.state == CameraStreamState::Restarting)
+                            .unwrap_or(false);
+
+                        if health.state == CameraStreamState::Restarting && !was_restarting {
+                            notifs.send(Notification::Error(
+                                format!("Camera {camera} Failed"),
+                                anyhow!(
+                                    "{}",
+                                    health.last_error.as_deref().unwrap_or("Unknown error")
+                                ),
+                            ));
+                        }
+                    }
+
+                    *camera_health = (*health).clone();
+                }
             }
             _ => {}
         }
     }
 }
 
+fn collect_logs(mut events: EventReader<RobotEvent>, mut logs: ResMut<RobotLogs>) {
+    for event in events.iter() {
+        if let RobotEvent::LogRecord(record) = event {
+            if logs.0.len() == RobotLogs::CAPACITY {
+                logs.0.pop_front();
+            }
+            logs.0.push_back(record.clone());
+        }
+    }
+}
+
 fn arming_system(updater: Local<Updater>, robot: Option<ResMut<Robot>>) {
     if let Some(robot) = robot {
         updater.emit_update(&tokens::ARMED, robot.3);
+        updater.emit_update(&tokens::ARMING_FORCE, robot.4);
+    }
+}
+
+/// Sends an emergency stop to the robot, bypassing the store
+pub fn emergency_stop(world: &mut World) {
+    if let Some(mut robot) = world.get_resource_mut::<Robot>() {
+        robot.disarm();
     }
+    world.send_event(NetworkEvent::SendPacket(Protocol::EmergencyStop));
+}
+
+/// Clears a latched emergency stop on the robot
+pub fn reset_emergency_stop(world: &mut World) {
+    world.send_event(NetworkEvent::SendPacket(Protocol::ResetEmergencyStop));
+}
+
+fn servo_system(updater: Local<Updater>, servos: Res<ServoCommands>) {
+    updater.emit_update(&tokens::SERVO_COMMANDS, servos.0.clone());
 }
 
 pub struct NotificationHandler(Sender<Update>);
@@ -1,5 +1,7 @@
 use std::mem;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -10,15 +12,49 @@ use bevy::{
     prelude::{Commands, World},
 };
 use common::store::Token;
+use common::types::ArmingState;
+use common::types::CameraHealth;
+use common::types::CameraRotation;
+use common::types::CameraSettings;
+use common::types::CameraStreamState;
+use common::types::Degrees;
 use common::types::DepthControlMode;
 use common::types::DepthCorrection;
+use common::types::EnclosureFrame;
+use common::types::EscCalibration;
+use common::types::EscSweepPhase;
+use common::types::EscSweepRequest;
+use common::types::EscSweepState;
+use common::types::GimbalAxis;
+use common::types::GimbalMode;
+use common::types::GimbalState;
+use common::types::LeakProbeState;
+use common::types::LeakResponse;
 use common::types::LevelingCorrection;
 use common::types::LevelingMode;
+use common::types::LogLevel;
+use common::types::LogRecord;
 use common::types::MovementOverride;
 use common::types::Percent;
 use common::types::PidConfig;
 use common::types::PidResult;
+use common::types::PowerFault;
+use common::types::PowerFrame;
+use common::types::PreArmFailure;
+use common::types::Recording;
 use common::types::RobotStatus;
+use common::types::SealTestConfig;
+use common::types::SealTestReport;
+use common::types::SealTestRequest;
+use common::types::SealTestState;
+use common::types::SensorFault;
+use common::types::SensorId;
+use common::types::ServoCommand;
+use common::types::ServoConfig;
+use common::types::ThrusterTestConfig;
+use common::types::ThrusterTestReport;
+use common::types::ThrusterTestResult;
+use common::types::ThrusterTestState;
 use common::{
     error::LogErrorExt,
     protocol::Protocol,
@@ -44,6 +80,10 @@ use tracing::error;
 use crate::plugins::gamepad::CurrentGamepad;
 use crate::plugins::notification::NotificationResource;
 use crate::plugins::orientation::OrientationDisplay;
+use crate::plugins::robot::emergency_stop;
+use crate::plugins::robot::reset_emergency_stop;
+use crate::plugins::robot::RobotLogs;
+use crate::plugins::robot::ServoCommands;
 use crate::plugins::robot::Updater;
 use crate::plugins::video::pipeline::MatId;
 use crate::plugins::video::pipeline::PipelineStage;
@@ -145,6 +185,21 @@ impl UiComponent for MenuBar {
                         }
                     });
                 }
+                if ui.button("Emergency Stop").clicked() {
+                    commands.add(emergency_stop);
+                }
+                if ui.button("Reset Emergency Stop").clicked() {
+                    commands.add(reset_emergency_stop);
+                }
+                if ui.button("Force Arm Robot").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(mut robot) = world.get_resource_mut::<Robot>() {
+                            robot.force_arm();
+                        } else {
+                            error!("No robot resource");
+                        }
+                    });
+                }
                 if ui.button("Disarm Robot").clicked() {
                     commands.add(|world: &mut World| {
                         if let Some(mut robot) = world.get_resource_mut::<Robot>() {
@@ -154,6 +209,76 @@ impl UiComponent for MenuBar {
                         }
                     });
                 }
+                if ui.button("ESC Calibration").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::esc_calibration_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open esc calibration window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
+                if ui.button("Servos").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::servo_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open servo window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
+                if ui.button("Thruster Self-Test").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::thruster_test_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open thruster test window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
+                if ui.button("Seal Test").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::seal_test_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open seal test window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
+                if ui.button("Leak Probes").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::leak_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open leak window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
                 if ui.button("Motor overrides").clicked() {
                     commands.add(|world: &mut World| {
                         if let Some(ui) = world.get_resource::<UiMessages>() {
@@ -198,8 +323,50 @@ impl UiComponent for MenuBar {
                         }
                     });
                 }
+                if ui.button("Recordings").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::recordings_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open recordings window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
+                if ui.button("Camera Settings").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::camera_settings_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open camera settings window");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
             });
             egui::menu::menu_button(ui, "Debug", |ui| {
+                if ui.button("Robot Logs").clicked() {
+                    commands.add(|world: &mut World| {
+                        if let Some(ui) = world.get_resource::<UiMessages>() {
+                            let id = rand::random();
+                            ui.0.try_send(UiMessage::OpenPanel(
+                                PaneId::Extension(id),
+                                panes::log_console_window(id, ui.0.clone()),
+                            ))
+                            .log_error("Open log console");
+                        } else {
+                            error!("No UiMessage resource found");
+                        }
+                    });
+                }
                 if ui.button("Egui Settings").clicked() {
                     commands.add(|world: &mut World| {
                         if let Some(ui) = world.get_resource::<UiMessages>() {
@@ -222,7 +389,11 @@ impl UiComponent for MenuBar {
 #[derive(Debug, Default)]
 pub struct StatusBar {
     status: Option<Arc<RobotStatus>>,
+    arming: Option<Arc<ArmingState>>,
     leak: Option<Arc<bool>>,
+    power: Option<Arc<PowerFrame>>,
+    power_faults: Option<Arc<Vec<PowerFault>>>,
+    sensor_health: Option<Arc<HashMap<SensorId, Vec<SensorFault>>>>,
     leveling: Option<Arc<LevelingMode>>,
     depth: Option<Arc<DepthControlMode>>,
     movement_override: Option<Arc<MovementOverride>>,
@@ -234,7 +405,11 @@ impl UiComponent for StatusBar {
             return;
         };
         self.status = robot.store().get(&tokens::STATUS);
+        self.arming = robot.store().get(&tokens::ARMING_STATE);
         self.leak = robot.store().get(&tokens::LEAK);
+        self.power = robot.store().get(&tokens::POWER);
+        self.power_faults = robot.store().get(&tokens::POWER_FAULTS);
+        self.sensor_health = robot.store().get(&tokens::SENSOR_HEALTH);
         self.leveling = robot.store().get(&tokens::LEVELING_MODE);
         self.depth = robot.store().get(&tokens::DEPTH_CONTROL_MODE);
         self.movement_override = robot.store().get(&tokens::MOVEMENT_OVERRIDE);
@@ -248,6 +423,9 @@ impl UiComponent for StatusBar {
                     RobotStatus::Ready => Color32::GREEN,
                     RobotStatus::Disarmed => Color32::RED,
                     RobotStatus::NoPeer => Color32::LIGHT_BLUE,
+                    RobotStatus::EmergencyStopped => Color32::RED,
+                    RobotStatus::SealTestFailed => Color32::YELLOW,
+                    RobotStatus::Degraded => Color32::YELLOW,
                 };
                 ui.colored_label(
                     color,
@@ -257,6 +435,30 @@ impl UiComponent for StatusBar {
                 ui.label("No status data");
             }
 
+            match self.arming.as_deref() {
+                Some(ArmingState::Refused(failures)) => {
+                    ui.colored_label(
+                        Color32::RED,
+                        RichText::new(format!(
+                            "Arming refused: {}",
+                            PreArmFailure::describe_all(failures)
+                        ))
+                        .heading(),
+                    );
+                }
+                Some(ArmingState::ForceArmed(failures)) => {
+                    ui.colored_label(
+                        Color32::YELLOW,
+                        RichText::new(format!(
+                            "Force armed: {}",
+                            PreArmFailure::describe_all(failures)
+                        ))
+                        .heading(),
+                    );
+                }
+                _ => {}
+            }
+
             if let Some(ref leak) = self.leak {
                 let color = if **leak { Color32::RED } else { Color32::GREEN };
                 ui.colored_label(
@@ -267,6 +469,61 @@ impl UiComponent for StatusBar {
                 ui.label("No leak data");
             }
 
+            if let Some(ref power) = self.power {
+                let faulted = self
+                    .power_faults
+                    .as_ref()
+                    .map(|it| !it.is_empty())
+                    .unwrap_or(false);
+                let color = if faulted {
+                    Color32::RED
+                } else {
+                    Color32::GREEN
+                };
+                ui.colored_label(
+                    color,
+                    RichText::new(format!(
+                        "Power: {} {} {}",
+                        power.voltage, power.current, power.energy_used
+                    ))
+                    .heading(),
+                );
+            } else {
+                ui.label("No power data");
+            }
+
+            if let Some(ref sensor_health) = self.sensor_health {
+                let mut faulted = sensor_health
+                    .iter()
+                    .filter(|(_, faults)| !faults.is_empty())
+                    .map(|(sensor, faults)| {
+                        let faults = faults
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        (*sensor, faults)
+                    })
+                    .collect::<Vec<_>>();
+                faulted.sort_by_key(|(sensor, _)| *sensor);
+
+                if faulted.is_empty() {
+                    ui.colored_label(Color32::GREEN, RichText::new("Sensors: Ok").heading());
+                } else {
+                    let faulted = faulted
+                        .iter()
+                        .map(|(sensor, faults)| format!("{sensor:?} ({faults})"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    ui.colored_label(
+                        Color32::RED,
+                        RichText::new(format!("Sensors: {faulted}")).heading(),
+                    );
+                }
+            } else {
+                ui.label("No sensor health data");
+            }
+
             if let Some(ref leveling) = self.leveling {
                 let color = if matches!(**leveling, LevelingMode::Enabled(_)) {
                     Color32::GREEN
@@ -825,8 +1082,16 @@ impl UiComponent for CamerasUi {
     fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, _commands: &mut Commands) {
         ui.collapsing("Cameras", |ui| {
             if let Some(ref cameras) = self.0 {
-                for Camera { name, location } in &**cameras {
-                    ui.label(format!("{name}: {location}"));
+                for Camera {
+                    name,
+                    location,
+                    settings,
+                } in &**cameras
+                {
+                    ui.label(format!(
+                        "{name}: {location}, {}x{}@{}",
+                        settings.width, settings.height, settings.framerate
+                    ));
                     // TODO Maybe show preview
                 }
             } else {
@@ -1622,3 +1887,1231 @@ impl UiComponent for MovementOverrideUi {
         });
     }
 }
+
+#[derive(Debug, Default)]
+pub struct ThrusterTestUi {
+    report: Option<Arc<ThrusterTestReport>>,
+    config: ThrusterTestConfig,
+}
+
+impl UiComponent for ThrusterTestUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.report = robot.store().get(&tokens::THRUSTER_TEST_REPORT);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        ui.label("Spins each thruster in turn while armed, keep the robot clear");
+
+        let mut speed = self.config.speed.get() * 100.0;
+        ui.add(
+            Slider::new(&mut speed, 5.0..=30.0)
+                .text("Speed")
+                .suffix("%"),
+        );
+        self.config.speed = Percent::new(speed / 100.0);
+
+        let mut spin_time = self.config.spin_time.as_secs_f64();
+        ui.add(
+            Slider::new(&mut spin_time, 0.5..=5.0)
+                .text("Spin time")
+                .suffix("s"),
+        );
+        self.config.spin_time = Duration::from_secs_f64(spin_time);
+
+        ui.horizontal(|ui| {
+            if ui.button("Start").clicked() {
+                let config = self.config;
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::THRUSTER_TEST, config);
+                });
+            }
+            if ui.button("Cancel").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::THRUSTER_TEST);
+                });
+            }
+        });
+
+        ui.separator();
+
+        let Some(ref report) = self.report else {
+            ui.label("No test has been run");
+            return;
+        };
+
+        match &report.state {
+            ThrusterTestState::Running(motor) => {
+                ui.label(format!("Testing {motor:?}"));
+            }
+            ThrusterTestState::Complete => {
+                let passed = report
+                    .results
+                    .iter()
+                    .filter(|(_, it)| it.result.passed())
+                    .count();
+                ui.label(format!(
+                    "Complete, {passed}/{} passed",
+                    report.results.len()
+                ));
+            }
+            ThrusterTestState::Aborted(reason) => {
+                ui.colored_label(Color32::RED, format!("Aborted: {reason}"));
+            }
+        }
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::remainder().clip(false).resizable(true), 4)
+            .header(TABLE_ROW_HEIGHT, |mut row| {
+                row.col(|ui| {
+                    ui.label("Motor");
+                });
+                row.col(|ui| {
+                    ui.label("Result");
+                });
+                row.col(|ui| {
+                    ui.label("Response (P/R/Y)");
+                });
+                row.col(|ui| {
+                    ui.label("Agreement");
+                });
+            })
+            .body(|body| {
+                body.rows(TABLE_ROW_HEIGHT, report.results.len(), |idx, mut row| {
+                    let (motor, measurement) = &report.results[idx];
+
+                    let color = match measurement.result {
+                        ThrusterTestResult::Pass => Color32::GREEN,
+                        ThrusterTestResult::NoResponse => Color32::YELLOW,
+                        ThrusterTestResult::Reversed | ThrusterTestResult::WrongAxis => {
+                            Color32::RED
+                        }
+                    };
+
+                    row.col(|ui| {
+                        ui.label(format!("{motor:?}"));
+                    });
+                    row.col(|ui| {
+                        ui.colored_label(color, format!("{:?}", measurement.result));
+                    });
+                    row.col(|ui| {
+                        ui.label(format!(
+                            "{} / {} / {}",
+                            measurement.pitch, measurement.roll, measurement.yaw
+                        ));
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{:.2}", measurement.agreement));
+                    });
+                });
+            });
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SealTestUi {
+    enclosure: Option<Arc<EnclosureFrame>>,
+    report: Option<Arc<SealTestReport>>,
+    config: SealTestConfig,
+}
+
+impl UiComponent for SealTestUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.enclosure = robot.store().get(&tokens::ENCLOSURE);
+        self.report = robot.store().get(&tokens::SEAL_TEST_REPORT);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        match self.enclosure.as_deref() {
+            Some(enclosure) => {
+                ui.label(format!(
+                    "Enclosure: {}, {}",
+                    enclosure.pressure, enclosure.temperature
+                ));
+            }
+            None => {
+                ui.label("No enclosure data");
+            }
+        }
+
+        ui.label("Pump the enclosure down before starting");
+
+        let mut window = self.config.window.as_secs_f64() / 60.0;
+        ui.add(
+            Slider::new(&mut window, 1.0..=30.0)
+                .text("Window")
+                .suffix("min"),
+        );
+        self.config.window = Duration::from_secs_f64(window * 60.0);
+
+        ui.add(
+            Slider::new(&mut self.config.max_rise_rate.0, 0.1..=10.0)
+                .text("Max rise")
+                .suffix("mbar/min"),
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Start").clicked() {
+                let request = SealTestRequest {
+                    requested: SystemTime::now(),
+                    config: self.config,
+                };
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::SEAL_TEST, request);
+                });
+            }
+            if ui.button("Cancel").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::SEAL_TEST);
+                });
+            }
+        });
+
+        ui.separator();
+
+        let Some(ref report) = self.report else {
+            ui.label("No test has been run");
+            return;
+        };
+
+        match &report.state {
+            SealTestState::Settling => {
+                ui.label(format!("Settling, {:.0?} left", report.remaining));
+            }
+            SealTestState::Measuring => {
+                ui.label(format!("Measuring, {:.0?} left", report.remaining));
+            }
+            SealTestState::Passed => {
+                ui.colored_label(Color32::GREEN, "Passed");
+            }
+            SealTestState::Failed => {
+                ui.colored_label(Color32::RED, "Failed");
+            }
+            SealTestState::Aborted(reason) => {
+                ui.colored_label(Color32::RED, format!("Aborted: {reason}"));
+            }
+        }
+
+        ui.label(format!("Start pressure: {}", report.start_pressure));
+        ui.label(format!("Pressure: {}", report.pressure));
+        ui.label(format!("Rise rate: {}/min", report.rise_rate));
+    }
+}
+
+#[derive(Debug)]
+pub struct LeakUi {
+    probes: Option<Arc<HashMap<String, LeakProbeState>>>,
+    response: Option<Arc<LeakResponse>>,
+
+    ascend_speed: f64,
+}
+
+impl Default for LeakUi {
+    fn default() -> Self {
+        Self {
+            probes: None,
+            response: None,
+            ascend_speed: 0.3,
+        }
+    }
+}
+
+impl UiComponent for LeakUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.probes = robot.store().get(&tokens::LEAK_PROBES);
+        self.response = robot.store().get(&tokens::LEAK_RESPONSE);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        match self.probes.as_deref() {
+            Some(probes) => {
+                let mut probes: Vec<_> = probes.iter().collect();
+                probes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+                for (name, state) in probes {
+                    let since = state
+                        .first_detected
+                        .and_then(|it| it.elapsed().ok())
+                        .map(|it| format!(", first wet {:.0?} ago", it))
+                        .unwrap_or_default();
+
+                    if state.leaking {
+                        ui.colored_label(Color32::RED, format!("{name}: Leaking{since}"));
+                    } else {
+                        ui.colored_label(Color32::GREEN, format!("{name}: Dry{since}"));
+                    }
+                }
+            }
+            None => {
+                ui.label("No leak probe data");
+            }
+        }
+
+        ui.separator();
+
+        let current = self.response.as_deref().copied().unwrap_or_default();
+        ui.label(format!("Response: {current:?}"));
+
+        ui.add(Slider::new(&mut self.ascend_speed, 0.05..=1.0).text("Ascend speed"));
+
+        let mut response = None;
+        ui.horizontal(|ui| {
+            if ui.button("Notify").clicked() {
+                response = Some(LeakResponse::Notify);
+            }
+            if ui.button("Ascend").clicked() {
+                response = Some(LeakResponse::Ascend(Percent::new(self.ascend_speed)));
+            }
+            if ui.button("Disarm").clicked() {
+                response = Some(LeakResponse::Disarm);
+            }
+        });
+
+        if let Some(response) = response {
+            commands.add(move |world: &mut World| {
+                Updater::from_world(world).emit_update(&tokens::LEAK_RESPONSE, response);
+            });
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EscCalibrationUi {
+    calibration: Option<Arc<HashMap<MotorId, EscCalibration>>>,
+    overrides: Option<Arc<HashMap<MotorId, EscCalibration>>>,
+    sweep: Option<Arc<EscSweepState>>,
+
+    motor: MotorId,
+    editing: Option<EscCalibration>,
+    rate: u64,
+    limit: u64,
+}
+
+impl Default for EscCalibrationUi {
+    fn default() -> Self {
+        Self {
+            calibration: None,
+            overrides: None,
+            sweep: None,
+            motor: MotorId::FrontLeftBottom,
+            editing: None,
+            rate: 10,
+            limit: 150,
+        }
+    }
+}
+
+/// Adds a single channel to the calibration overrides
+fn apply_esc_calibration(
+    overrides: Option<&HashMap<MotorId, EscCalibration>>,
+    motor: MotorId,
+    calibration: EscCalibration,
+    commands: &mut Commands,
+) {
+    let mut overrides = overrides.cloned().unwrap_or_default();
+    overrides.insert(motor, calibration);
+
+    commands.add(move |world: &mut World| {
+        Updater::from_world(world).emit_update(&tokens::ESC_CALIBRATION_OVERRIDE, overrides);
+    });
+}
+
+impl UiComponent for EscCalibrationUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.calibration = robot.store().get(&tokens::ESC_CALIBRATION);
+        self.overrides = robot.store().get(&tokens::ESC_CALIBRATION_OVERRIDE);
+        self.sweep = robot.store().get(&tokens::ESC_SWEEP_STATE);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        let motor_ids = [
+            MotorId::FrontLeftBottom,
+            MotorId::FrontLeftTop,
+            MotorId::FrontRightBottom,
+            MotorId::FrontRightTop,
+            MotorId::BackLeftBottom,
+            MotorId::BackLeftTop,
+            MotorId::BackRightBottom,
+            MotorId::BackRightTop,
+            MotorId::Camera1,
+            MotorId::Camera2,
+            MotorId::Camera3,
+            MotorId::Camera4,
+            MotorId::Aux1,
+            MotorId::Aux2,
+            MotorId::Aux3,
+            MotorId::Aux4,
+        ];
+
+        let last_motor = self.motor;
+        ComboBox::from_id_source("esc_motor")
+            .selected_text(format!("{:?}", self.motor))
+            .show_ui(ui, |ui| {
+                for motor_id in motor_ids {
+                    ui.selectable_value(&mut self.motor, motor_id, format!("{motor_id:?}"));
+                }
+            });
+        if last_motor != self.motor {
+            self.editing = None;
+        }
+
+        let current = self
+            .calibration
+            .as_ref()
+            .and_then(|it| it.get(&self.motor))
+            .copied();
+        let Some(current) = current else {
+            ui.label("No calibration data");
+            return;
+        };
+
+        let editing = self.editing.get_or_insert(current);
+
+        let pulse_editor = |ui: &mut egui::Ui, label: &str, pulse: &mut Duration| {
+            let mut micros = pulse.as_micros() as u64;
+            ui.add(
+                egui::DragValue::new(&mut micros)
+                    .clamp_range(0..=3000)
+                    .prefix(format!("{label}: "))
+                    .suffix("µs"),
+            );
+            *pulse = Duration::from_micros(micros);
+        };
+
+        ui.horizontal(|ui| {
+            pulse_editor(ui, "Min", &mut editing.min);
+            pulse_editor(ui, "Center", &mut editing.center);
+            pulse_editor(ui, "Max", &mut editing.max);
+            pulse_editor(ui, "Deadband", &mut editing.deadband);
+            ui.checkbox(&mut editing.reverse, "Reverse");
+        });
+        let edited = *editing;
+
+        ui.horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                self.editing = Some(current);
+            }
+            if ui.button("Apply").clicked() {
+                apply_esc_calibration(self.overrides.as_deref(), self.motor, edited, commands);
+            }
+            if ui.button("Clear Overrides").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::ESC_CALIBRATION_OVERRIDE);
+                });
+                self.editing = None;
+            }
+        });
+
+        ui.separator();
+        ui.label("Deadband sweep, robot must be armed");
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.rate)
+                    .clamp_range(1..=100)
+                    .prefix("Rate: ")
+                    .suffix("µs/s"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.limit)
+                    .clamp_range(10..=400)
+                    .prefix("Limit: ")
+                    .suffix("µs"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Start Sweep").clicked() {
+                let request = EscSweepRequest::Start {
+                    motor: self.motor,
+                    rate: Duration::from_micros(self.rate),
+                    limit: Duration::from_micros(self.limit),
+                };
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::ESC_SWEEP, request);
+                });
+            }
+            if ui.button("Thrust Started").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world)
+                        .emit_update(&tokens::ESC_SWEEP, EscSweepRequest::MarkThrust);
+                });
+            }
+            if ui.button("Cancel Sweep").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::ESC_SWEEP);
+                });
+            }
+        });
+
+        if let Some(ref sweep) = self.sweep {
+            ui.label(format!(
+                "{:?} {:?} at {}µs",
+                sweep.motor,
+                sweep.phase,
+                sweep.pulse.as_micros()
+            ));
+            ui.label(format!(
+                "Thrust starts, forward: {:?}, reverse: {:?}",
+                sweep.forward_start, sweep.reverse_start
+            ));
+
+            if sweep.phase == EscSweepPhase::Complete {
+                let base = self
+                    .calibration
+                    .as_ref()
+                    .and_then(|it| it.get(&sweep.motor))
+                    .copied();
+                let result = base.and_then(|base| sweep.apply(base));
+
+                ui.add_enabled_ui(result.is_some(), |ui| {
+                    if ui.button("Apply Sweep Result").clicked() {
+                        if let Some(result) = result {
+                            apply_esc_calibration(
+                                self.overrides.as_deref(),
+                                sweep.motor,
+                                result,
+                                commands,
+                            );
+                            self.editing = None;
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ServoUi {
+    positions: Option<Arc<HashMap<MotorId, Percent>>>,
+    overrides: Option<Arc<HashMap<MotorId, ServoConfig>>>,
+    commands: HashMap<MotorId, ServoCommand>,
+    gimbal_mode: Option<Arc<GimbalMode>>,
+    gimbal_state: Option<Arc<GimbalState>>,
+
+    servo: MotorId,
+    axis: GimbalAxis,
+    editing: Option<ServoConfig>,
+    preset_name: String,
+}
+
+impl Default for ServoUi {
+    fn default() -> Self {
+        Self {
+            positions: None,
+            overrides: None,
+            commands: Default::default(),
+            gimbal_mode: None,
+            gimbal_state: None,
+            servo: MotorId::Camera1,
+            axis: GimbalAxis::Pitch,
+            editing: None,
+            preset_name: String::new(),
+        }
+    }
+}
+
+fn send_servo_command(servo: MotorId, command: ServoCommand, commands: &mut Commands) {
+    commands.add(move |world: &mut World| {
+        if let Some(mut servos) = world.get_resource_mut::<ServoCommands>() {
+            servos.0.insert(servo, command);
+        } else {
+            error!("No servo commands resource");
+        }
+    });
+}
+
+impl UiComponent for ServoUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        if let Some(servos) = world.get_resource::<ServoCommands>() {
+            self.commands = servos.0.clone();
+        }
+
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.positions = robot.store().get(&tokens::SERVO_POSITIONS);
+        self.overrides = robot.store().get(&tokens::SERVO_CONFIG_OVERRIDE);
+        self.gimbal_mode = robot.store().get(&tokens::GIMBAL_MODE);
+        self.gimbal_state = robot.store().get(&tokens::GIMBAL_STATE);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        let servo_ids = [
+            MotorId::Camera1,
+            MotorId::Camera2,
+            MotorId::Camera3,
+            MotorId::Camera4,
+            MotorId::Aux1,
+            MotorId::Aux2,
+            MotorId::Aux3,
+            MotorId::Aux4,
+        ];
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::remainder().clip(false).resizable(true), 3)
+            .header(TABLE_ROW_HEIGHT, |mut row| {
+                row.col(|ui| {
+                    ui.label("Servo");
+                });
+                row.col(|ui| {
+                    ui.label("Position");
+                });
+                row.col(|ui| {
+                    ui.label("Command");
+                });
+            })
+            .body(|mut body| {
+                for servo in servo_ids {
+                    body.row(TABLE_ROW_HEIGHT, |mut row| {
+                        row.col(|ui| {
+                            ui.label(format!("{servo:?}"));
+                        });
+                        row.col(|ui| {
+                            let position = self.positions.as_ref().and_then(|it| it.get(&servo));
+                            if let Some(position) = position {
+                                ui.label(format!("{position}"));
+                            } else {
+                                ui.label("-");
+                            }
+                        });
+                        row.col(|ui| {
+                            if let Some(command) = self.commands.get(&servo) {
+                                ui.label(format!("{command:?}"));
+                            } else {
+                                ui.label("-");
+                            }
+                        });
+                    });
+                }
+            });
+
+        ui.separator();
+
+        let last_servo = self.servo;
+        ComboBox::from_id_source("servo_select")
+            .selected_text(format!("{:?}", self.servo))
+            .show_ui(ui, |ui| {
+                for servo in servo_ids {
+                    ui.selectable_value(&mut self.servo, servo, format!("{servo:?}"));
+                }
+            });
+        if last_servo != self.servo {
+            self.editing = None;
+        }
+
+        let current_config = self
+            .overrides
+            .as_ref()
+            .and_then(|it| it.get(&self.servo).cloned())
+            .unwrap_or_default();
+        let current_position = self
+            .positions
+            .as_ref()
+            .and_then(|it| it.get(&self.servo).copied());
+
+        let stabilized = matches!(
+            self.gimbal_mode.as_deref(),
+            Some(GimbalMode::Stabilized { servo, .. }) if *servo == self.servo
+        );
+        let gimbal_state = self
+            .gimbal_state
+            .as_deref()
+            .filter(|it| stabilized && it.servo == self.servo);
+
+        if let Some(state) = gimbal_state {
+            // Tilt is relative to the horizon while stabilized
+            let calibration = current_config.calibration;
+            let (low, high) = if calibration.min_angle.0 <= calibration.max_angle.0 {
+                (calibration.min_angle.0, calibration.max_angle.0)
+            } else {
+                (calibration.max_angle.0, calibration.min_angle.0)
+            };
+
+            let mut tilt = state.tilt.0;
+            let response = ui.add(Slider::new(&mut tilt, low..=high).text("Tilt (deg)"));
+            if response.changed() {
+                send_servo_command(self.servo, ServoCommand::Angle(Degrees(tilt)), commands);
+            }
+        } else {
+            let mut target = current_position.unwrap_or_default().get();
+            let response = ui.add(Slider::new(&mut target, -1.0..=1.0).text("Position"));
+            if response.changed() {
+                send_servo_command(
+                    self.servo,
+                    ServoCommand::Position(Percent::new(target)),
+                    commands,
+                );
+            }
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("Center").clicked() {
+                send_servo_command(self.servo, ServoCommand::Angle(Degrees(0.0)), commands);
+            }
+
+            let mut presets: Vec<_> = current_config.presets.keys().cloned().collect();
+            presets.sort();
+            for preset in presets {
+                if ui.button(preset.as_str()).clicked() {
+                    send_servo_command(self.servo, ServoCommand::Preset(preset), commands);
+                }
+            }
+        });
+
+        ui.separator();
+        ui.label("Stabilization");
+
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("gimbal_axis")
+                .selected_text(format!("{:?}", self.axis))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.axis, GimbalAxis::Pitch, "Pitch");
+                    ui.selectable_value(&mut self.axis, GimbalAxis::Roll, "Roll");
+                });
+
+            if stabilized {
+                if ui.button("Stop Stabilizing").clicked() {
+                    commands.add(|world: &mut World| {
+                        Updater::from_world(world)
+                            .emit_update(&tokens::GIMBAL_MODE, GimbalMode::Disabled);
+                    });
+                }
+            } else if ui.button("Stabilize").clicked() {
+                let mode = GimbalMode::Stabilized {
+                    servo: self.servo,
+                    axis: self.axis,
+                };
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::GIMBAL_MODE, mode);
+                });
+            }
+        });
+
+        if let Some(state) = gimbal_state {
+            ui.label(format!(
+                "Tilt: {}, Vehicle: {}, Servo: {}",
+                state.tilt, state.vehicle, state.servo_angle
+            ));
+            if state.saturated {
+                ui.colored_label(Color32::YELLOW, "Servo at end of travel");
+            }
+        } else if let Some(GimbalMode::Stabilized { servo, axis }) = self.gimbal_mode.as_deref() {
+            ui.label(format!("{servo:?} stabilized on {axis:?}"));
+        }
+
+        ui.separator();
+        ui.label("Limits and presets");
+
+        let editing = self.editing.get_or_insert_with(|| current_config.clone());
+
+        let mut min = editing.min.get();
+        let mut max = editing.max.get();
+        ui.add(Slider::new(&mut min, -1.0..=1.0).text("Min"));
+        ui.add(Slider::new(&mut max, -1.0..=1.0).text("Max"));
+        editing.min = Percent::new(min.min(max));
+        editing.max = Percent::new(max.max(min));
+        ui.add(
+            egui::DragValue::new(&mut editing.max_rate)
+                .clamp_range(0.01..=10.0)
+                .speed(0.01)
+                .prefix("Max rate: ")
+                .suffix("/s"),
+        );
+
+        let pulse_editor = |ui: &mut egui::Ui, label: &str, pulse: &mut Duration| {
+            let mut micros = pulse.as_micros() as u64;
+            ui.add(
+                egui::DragValue::new(&mut micros)
+                    .clamp_range(500..=2500)
+                    .prefix(format!("{label}: "))
+                    .suffix("µs"),
+            );
+            *pulse = Duration::from_micros(micros);
+        };
+        let calibration = &mut editing.calibration;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut calibration.min_angle.0)
+                    .clamp_range(-180.0..=180.0)
+                    .prefix("Min angle: ")
+                    .suffix("deg"),
+            );
+            pulse_editor(ui, "at", &mut calibration.min_pulse);
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut calibration.max_angle.0)
+                    .clamp_range(-180.0..=180.0)
+                    .prefix("Max angle: ")
+                    .suffix("deg"),
+            );
+            pulse_editor(ui, "at", &mut calibration.max_pulse);
+        });
+
+        let mut presets: Vec<_> = editing
+            .presets
+            .iter()
+            .map(|(name, position)| (name.clone(), *position))
+            .collect();
+        presets.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, position) in presets {
+            ui.horizontal(|ui| {
+                ui.label(format!("{name}: {position}"));
+                if ui.button("Remove").clicked() {
+                    editing.presets.remove(&name);
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.preset_name);
+
+            let can_save = !self.preset_name.is_empty() && current_position.is_some();
+            ui.add_enabled_ui(can_save, |ui| {
+                if ui.button("Save Current Position").clicked() {
+                    if let Some(position) = current_position {
+                        editing
+                            .presets
+                            .insert(mem::take(&mut self.preset_name), position);
+                    }
+                }
+            });
+        });
+
+        let edited = editing.clone();
+
+        ui.horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                self.editing = Some(current_config);
+            }
+            if ui.button("Apply").clicked() {
+                let mut overrides = self.overrides.as_deref().cloned().unwrap_or_default();
+                overrides.insert(self.servo, edited);
+
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world)
+                        .emit_update(&tokens::SERVO_CONFIG_OVERRIDE, overrides);
+                });
+            }
+            if ui.button("Clear Overrides").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::SERVO_CONFIG_OVERRIDE);
+                });
+                self.editing = None;
+            }
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CameraSettingsUi {
+    cameras: Option<Arc<Vec<Camera>>>,
+    settings: Option<Arc<HashMap<String, CameraSettings>>>,
+    health: Option<Arc<HashMap<String, CameraHealth>>>,
+    record: Option<Arc<HashMap<String, bool>>>,
+
+    selected: Option<String>,
+    editing: Option<CameraSettings>,
+}
+
+impl UiComponent for CameraSettingsUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.cameras = robot.store().get(&tokens::CAMERAS);
+        self.settings = robot.store().get(&tokens::CAMERA_SETTINGS);
+        self.health = robot.store().get(&tokens::CAMERA_HEALTH);
+        self.record = robot.store().get(&tokens::CAMERA_RECORD);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        // Disabled cameras are only known through their settings
+        let mut names: Vec<String> = self
+            .cameras
+            .iter()
+            .flat_map(|it| it.iter().map(|camera| camera.name.clone()))
+            .chain(self.settings.iter().flat_map(|it| it.keys().cloned()))
+            .chain(self.health.iter().flat_map(|it| it.keys().cloned()))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        if names.is_empty() {
+            ui.label("No cameras found");
+            return;
+        }
+
+        let last_selected = self.selected.clone();
+        ComboBox::from_id_source("camera_settings_select")
+            .selected_text(self.selected.as_deref().unwrap_or("Select camera"))
+            .show_ui(ui, |ui| {
+                for name in &names {
+                    ui.selectable_value(&mut self.selected, Some(name.clone()), name);
+                }
+            });
+        if last_selected != self.selected {
+            self.editing = None;
+        }
+
+        let Some(ref selected) = self.selected else {
+            return;
+        };
+
+        if let Some(health) = self.health.as_ref().and_then(|it| it.get(selected)) {
+            let state = match health.state {
+                CameraStreamState::Running => {
+                    format!("Running for {}s", health.uptime.as_secs())
+                }
+                CameraStreamState::Restarting => "Restarting".to_owned(),
+                CameraStreamState::Stopped => "Stopped".to_owned(),
+            };
+            let recording = if health.recording { ", recording" } else { "" };
+            ui.label(format!("{state}{recording}, {} restarts", health.restarts));
+
+            if let Some(ref error) = health.last_error {
+                ui.collapsing("Last Error", |ui| {
+                    ui.label(error);
+                });
+            }
+        }
+
+        // Takes effect right away unlike the stream settings
+        let mut record = self
+            .record
+            .as_ref()
+            .and_then(|it| it.get(selected).copied())
+            .unwrap_or(false);
+        if ui.checkbox(&mut record, "Record on Robot").changed() {
+            let mut recording = self.record.as_deref().cloned().unwrap_or_default();
+            recording.insert(selected.clone(), record);
+
+            commands.add(move |world: &mut World| {
+                Updater::from_world(world).emit_update(&tokens::CAMERA_RECORD, recording);
+            });
+        }
+
+        let current = self
+            .settings
+            .as_ref()
+            .and_then(|it| it.get(selected).copied())
+            .unwrap_or_default();
+        let editing = self.editing.get_or_insert(current);
+
+        ui.checkbox(&mut editing.enabled, "Enabled");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut editing.width)
+                    .clamp_range(160..=3840)
+                    .prefix("Width: "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut editing.height)
+                    .clamp_range(120..=2160)
+                    .prefix("Height: "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut editing.framerate)
+                    .clamp_range(1..=60)
+                    .prefix("Framerate: ")
+                    .suffix("fps"),
+            );
+        });
+        ui.add(
+            egui::DragValue::new(&mut editing.bitrate)
+                .clamp_range(100..=20000)
+                .prefix("Bitrate: ")
+                .suffix("kbit/s"),
+        );
+        ComboBox::from_id_source("camera_rotation")
+            .selected_text(format!("{:?}", editing.rotation))
+            .show_ui(ui, |ui| {
+                for rotation in [
+                    CameraRotation::None,
+                    CameraRotation::Clockwise90,
+                    CameraRotation::Rotate180,
+                    CameraRotation::CounterClockwise90,
+                ] {
+                    ui.selectable_value(&mut editing.rotation, rotation, format!("{rotation:?}"));
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut editing.flip_horizontal, "Flip Horizontal");
+            ui.checkbox(&mut editing.flip_vertical, "Flip Vertical");
+        });
+        ui.checkbox(&mut editing.multicast, "Multicast")
+            .on_hover_text("Stream to any viewer on the network instead of only subscribers");
+        let edited = *editing;
+
+        ui.horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                self.editing = Some(current);
+            }
+            if ui.button("Apply").clicked() {
+                let mut settings = self.settings.as_deref().cloned().unwrap_or_default();
+                settings.insert(selected.clone(), edited);
+
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::CAMERA_SETTINGS, settings);
+                });
+            }
+        });
+    }
+}
+
+/// Lists the video segments recorded on the robot so they can be copied off after a run
+#[derive(Debug, Default)]
+pub struct RecordingsUi(Option<Arc<Vec<Recording>>>);
+
+impl UiComponent for RecordingsUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        let Some(robot) = world.get_resource::<Robot>() else {
+            return;
+        };
+        self.0 = robot.store().get(&tokens::RECORDINGS);
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, _commands: &mut Commands) {
+        let Some(ref recordings) = self.0 else {
+            ui.label("No recordings");
+            return;
+        };
+        if recordings.is_empty() {
+            ui.label("No recordings");
+            return;
+        }
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .columns(Column::remainder().clip(false).resizable(true), 4)
+            .header(TABLE_ROW_HEIGHT, |mut row| {
+                row.col(|ui| {
+                    ui.label("Camera");
+                });
+                row.col(|ui| {
+                    ui.label("File");
+                });
+                row.col(|ui| {
+                    ui.label("Size");
+                });
+                row.col(|ui| {
+                    ui.label("Age");
+                });
+            })
+            .body(|body| {
+                body.rows(TABLE_ROW_HEIGHT, recordings.len(), |idx, mut row| {
+                    let recording = &recordings[idx];
+                    row.col(|ui| {
+                        ui.label(&recording.camera);
+                    });
+                    row.col(|ui| {
+                        ui.label(&recording.path);
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{:.1}MiB", recording.size as f64 / 1024.0 / 1024.0));
+                    });
+                    row.col(|ui| {
+                        let age = recording.modified.elapsed().unwrap_or_default();
+                        ui.label(format!("{}s", age.as_secs()));
+                    });
+                });
+            });
+    }
+}
+
+/// Searchable view of the logs forwarded by the robot
+#[derive(Debug)]
+pub struct LogConsoleUi {
+    /// Newest matching records, oldest first
+    records: Vec<LogRecord>,
+    total: usize,
+    filter: Option<Arc<String>>,
+
+    search: String,
+    min_level: LogLevel,
+    editing_filter: Option<String>,
+}
+
+impl LogConsoleUi {
+    /// Limits how much is copied out of `RobotLogs` each frame
+    const MAX_SHOWN: usize = 1000;
+}
+
+impl Default for LogConsoleUi {
+    fn default() -> Self {
+        Self {
+            records: Vec::new(),
+            total: 0,
+            filter: None,
+            search: String::new(),
+            min_level: LogLevel::Debug,
+            editing_filter: None,
+        }
+    }
+}
+
+impl UiComponent for LogConsoleUi {
+    fn pre_draw(&mut self, world: &World, _commands: &mut Commands) {
+        if let Some(robot) = world.get_resource::<Robot>() {
+            self.filter = robot.store().get(&tokens::LOG_FILTER);
+        }
+        let Some(logs) = world.get_resource::<RobotLogs>() else {
+            return;
+        };
+
+        let search = self.search.to_lowercase();
+        let matches = |record: &&LogRecord| {
+            if record.level < self.min_level {
+                return false;
+            }
+            if search.is_empty() {
+                return true;
+            }
+
+            record.message.to_lowercase().contains(&search)
+                || record.target.to_lowercase().contains(&search)
+                || record
+                    .spans
+                    .iter()
+                    .any(|it| it.to_lowercase().contains(&search))
+                || record
+                    .fields
+                    .iter()
+                    .any(|(_, value)| value.to_lowercase().contains(&search))
+        };
+
+        let mut records: Vec<LogRecord> = logs
+            .0
+            .iter()
+            .rev()
+            .filter(matches)
+            .take(Self::MAX_SHOWN)
+            .cloned()
+            .collect();
+        records.reverse();
+
+        self.records = records;
+        self.total = logs.0.len();
+    }
+
+    fn draw(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui, commands: &mut Commands) {
+        let current = self
+            .filter
+            .as_deref()
+            .cloned()
+            .unwrap_or_else(|| "info".to_owned());
+        let editing = self.editing_filter.get_or_insert(current);
+
+        let mut reset = false;
+        ui.horizontal(|ui| {
+            ui.label("Robot filter:");
+            ui.text_edit_singleline(editing)
+                .on_hover_text("Directives such as `info,robot::systems::cameras=debug`");
+
+            if ui.button("Apply").clicked() {
+                let filter = editing.clone();
+                commands.add(move |world: &mut World| {
+                    Updater::from_world(world).emit_update(&tokens::LOG_FILTER, filter);
+                });
+            }
+            if ui.button("Reset").clicked() {
+                commands.add(|world: &mut World| {
+                    Updater::from_world(world).emit_delete(&tokens::LOG_FILTER);
+                });
+                reset = true;
+            }
+        });
+        if reset {
+            self.editing_filter = None;
+        }
+
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("log_console_level")
+                .selected_text(format!("{:?}", self.min_level))
+                .show_ui(ui, |ui| {
+                    for level in [
+                        LogLevel::Debug,
+                        LogLevel::Info,
+                        LogLevel::Warn,
+                        LogLevel::Error,
+                    ] {
+                        ui.selectable_value(&mut self.min_level, level, format!("{level:?}"));
+                    }
+                });
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.search);
+        });
+        ui.label(format!(
+            "Showing {} of {} records",
+            self.records.len(),
+            self.total
+        ));
+
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, self.records.len(), |ui, rows| {
+                for record in &self.records[rows] {
+                    let color = match record.level {
+                        LogLevel::Debug => Color32::GRAY,
+                        LogLevel::Info => ui.visuals().text_color(),
+                        LogLevel::Warn => Color32::YELLOW,
+                        LogLevel::Error => Color32::RED,
+                    };
+
+                    ui.label(
+                        RichText::new(format_record(record))
+                            .monospace()
+                            .color(color),
+                    );
+                }
+            });
+    }
+}
+
+fn format_record(record: &LogRecord) -> String {
+    let since_epoch = record
+        .time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() % (60 * 60 * 24);
+    let time = format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    );
+
+    // Derived `Debug` ignores padding
+    let level = format!("{:?}", record.level);
+    let mut line = format!("{time} {level:>5} ");
+    if !record.spans.is_empty() {
+        line.push_str(&record.spans.join(":"));
+        line.push(' ');
+    }
+    line.push_str(&format!("{}: {}", record.target, record.message));
+    for (name, value) in &record.fields {
+        line.push_str(&format!(" {name}={value}"));
+    }
+
+    line
+}
@@ -211,6 +211,190 @@ pub fn motor_override_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
     pane
 }
 
+pub fn thruster_test_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Thruster Self-Test")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close thruster test window");
+            }
+        })
+    };
+
+    pane.add(components::ThrusterTestUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn seal_test_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Seal Test")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close seal test window");
+            }
+        })
+    };
+
+    pane.add(components::SealTestUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn leak_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Leak Probes")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close leak window");
+            }
+        })
+    };
+
+    pane.add(components::LeakUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn esc_calibration_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("ESC Calibration")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close esc calibration window");
+            }
+        })
+    };
+
+    pane.add(components::EscCalibrationUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn servo_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Servos")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close servo window");
+            }
+        })
+    };
+
+    pane.add(components::ServoUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn camera_settings_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Camera Settings")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close camera settings window");
+            }
+        })
+    };
+
+    pane.add(components::CameraSettingsUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn recordings_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Recordings")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close recordings window");
+            }
+        })
+    };
+
+    pane.add(components::RecordingsUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
+pub fn log_console_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
+    let mut pane = {
+        Pane::new(move |ctx, add_contents| {
+            let mut open = true;
+
+            egui::Window::new("Robot Logs")
+                .id(Id::new(id))
+                .open(&mut open)
+                .show(ctx, add_contents);
+
+            if !open {
+                ui.try_send(UiMessage::ClosePanel(PaneId::Extension(id)))
+                    .log_error("Close log console");
+            }
+        })
+    };
+
+    pane.add(components::LogConsoleUi::default());
+    pane.add(components::PreserveSize::default());
+
+    pane
+}
+
 pub fn video_window(id: ExtensionId, ui: Sender<UiMessage>) -> Pane {
     let mut pane = {
         Pane::new(move |ctx, add_contents| {
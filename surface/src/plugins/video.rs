@@ -9,6 +9,7 @@ use anyhow::Context;
 use bevy::render::texture::Volume;
 use bevy::{prelude::*, render::render_resource::Extent3d};
 use bevy_egui::EguiContexts;
+use common::protocol::Protocol;
 use common::store::tokens;
 use common::{
     error::LogErrorExt,
@@ -28,7 +29,8 @@ use tracing::{error, span, Level};
 
 use self::pipeline::{MatId, Mats, PipelineProto, ProcessorFn, SourceFn};
 
-use super::robot::Updater;
+use super::networking::NetworkEvent;
+use super::robot::{Robot, RobotEvent, Updater};
 
 pub mod camera;
 pub mod pipeline;
@@ -40,6 +42,8 @@ pub struct VideoPlugin;
 impl Plugin for VideoPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(video_sink);
+        app.add_system(refresh_video_cameras);
+        app.add_system(camera_subscriptions);
         app.add_system(spawn_video_captures);
         app.add_system(update_pipelines);
         app.add_system(video_frames);
@@ -272,6 +276,48 @@ fn video_sink(
     }
 }
 
+/// Picks up new stream settings for cameras that are already open
+fn refresh_video_cameras(robot: Option<Res<Robot>>, mut cameras: Query<&mut VideoCamera>) {
+    let Some(robot) = robot else {
+        return;
+    };
+    let Some(list) = robot.store().get(&tokens::CAMERAS) else {
+        return;
+    };
+
+    for mut camera in &mut cameras {
+        if let Some(updated) = list.iter().find(|it| it.name == camera.0.name) {
+            if *updated != camera.0 {
+                camera.0 = updated.clone();
+            }
+        }
+    }
+}
+
+/// Tells the robot which cameras are open here so it only streams those to this surface
+fn camera_subscriptions(
+    cameras: Query<&VideoCamera>,
+    mut robot_events: EventReader<RobotEvent>,
+    mut net: EventWriter<NetworkEvent>,
+    mut last: Local<Option<Vec<String>>>,
+) {
+    // The robot forgets our subscriptions when we disconnect
+    let reconnected = robot_events
+        .iter()
+        .any(|it| matches!(it, RobotEvent::Connected(_)));
+
+    let mut subscriptions: Vec<String> = cameras.iter().map(|it| it.0.name.clone()).collect();
+    subscriptions.sort();
+    subscriptions.dedup();
+
+    if reconnected || last.as_ref() != Some(&subscriptions) {
+        net.send(NetworkEvent::SendPacket(Protocol::CameraSubscriptions(
+            subscriptions.clone(),
+        )));
+        *last = Some(subscriptions);
+    }
+}
+
 /// Spawn video capture for each video entity
 fn spawn_video_captures(
     mut cmds: Commands,
@@ -1,6 +1,9 @@
 use anyhow::Context;
-use common::types::Camera;
-use opencv::videoio::{self, VideoCapture, VideoCaptureTrait};
+use common::types::{Camera, CameraRotation, CameraSettings};
+use opencv::{
+    core::{self, Mat, MatTraitConst},
+    videoio::{self, VideoCapture, VideoCaptureTrait},
+};
 
 use super::pipeline::{MatId, Mats, SourceFn};
 
@@ -9,13 +12,17 @@ pub fn camera_source(camera: Camera) -> anyhow::Result<SourceFn> {
     let mut src = VideoCapture::from_file(&gen_src(&camera), videoio::CAP_GSTREAMER)
         .context("Open video capture")?;
 
-    let cameras_to_rotate = &["/dev/video4"];
+    let settings = camera.settings;
+    let transformed = settings.rotation != CameraRotation::None
+        || settings.flip_horizontal
+        || settings.flip_vertical;
+    let mut scratch = Mat::default();
 
     Ok(Box::new(move |mats: &mut Mats| {
         mats.entry(MatId::Camera).or_default();
         mats.entry(MatId::RotateIntermediate).or_default();
 
-        let mat_id = if !cameras_to_rotate.contains(&camera.name.as_str()) {
+        let mat_id = if !transformed {
             MatId::Camera
         } else {
             MatId::RotateIntermediate
@@ -24,24 +31,64 @@ pub fn camera_source(camera: Camera) -> anyhow::Result<SourceFn> {
         let raw = mats.get(&mat_id).unwrap();
         let rst = src.read(&mut *raw.borrow_mut()).context("Read stream")?;
 
-        if rst && cameras_to_rotate.contains(&camera.name.as_str()) {
-            let rotated = mats.get(&MatId::Camera).unwrap();
-            opencv::core::rotate(
-                &*raw.borrow(),
-                &mut *rotated.borrow_mut(),
-                opencv::core::ROTATE_180,
-            )
-            .context("Rotate")?;
+        if rst && transformed {
+            let out = mats.get(&MatId::Camera).unwrap();
+            transform(&raw.borrow(), &mut scratch, &mut out.borrow_mut(), settings)?;
         }
 
         Ok(rst)
     }))
 }
 
+/// Applies the flip and then the rotation from `settings`
+fn transform(
+    raw: &Mat,
+    scratch: &mut Mat,
+    out: &mut Mat,
+    settings: CameraSettings,
+) -> anyhow::Result<()> {
+    let flip_code = match (settings.flip_horizontal, settings.flip_vertical) {
+        (true, true) => Some(-1),
+        (true, false) => Some(1),
+        (false, true) => Some(0),
+        (false, false) => None,
+    };
+    let rotate_code = match settings.rotation {
+        CameraRotation::None => None,
+        CameraRotation::Clockwise90 => Some(core::ROTATE_90_CLOCKWISE),
+        CameraRotation::Rotate180 => Some(core::ROTATE_180),
+        CameraRotation::CounterClockwise90 => Some(core::ROTATE_90_COUNTERCLOCKWISE),
+    };
+
+    match (flip_code, rotate_code) {
+        (Some(flip_code), Some(rotate_code)) => {
+            core::flip(raw, scratch, flip_code).context("Flip")?;
+            core::rotate(scratch, out, rotate_code).context("Rotate")?;
+        }
+        (Some(flip_code), None) => {
+            core::flip(raw, out, flip_code).context("Flip")?;
+        }
+        (None, Some(rotate_code)) => {
+            core::rotate(raw, out, rotate_code).context("Rotate")?;
+        }
+        (None, None) => {
+            raw.copy_to(out).context("Copy")?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Generates the gstreamer pipeline to recieve data from `camera`
 fn gen_src(camera: &Camera) -> String {
     let ip = camera.location.ip();
     let port = camera.location.port();
+    let CameraSettings {
+        width,
+        height,
+        framerate,
+        ..
+    } = camera.settings;
 
-    format!("udpsrc address={ip} port={port} caps=application/x-rtp,media=video,clock-rate=90000,encoding-name=H264,a-framerate=30,payload=96 ! rtph264depay ! h264parse ! avdec_h264 ! videoconvert ! video/x-raw,format=BGR ! appsink drop=1")
+    format!("udpsrc address={ip} port={port} caps=application/x-rtp,media=video,clock-rate=90000,encoding-name=H264,a-framerate={framerate},payload=96 ! rtph264depay ! h264parse ! avdec_h264 ! videoconvert ! video/x-raw,format=BGR,width={width},height={height} ! appsink drop=1")
 }